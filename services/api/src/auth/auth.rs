@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::errors::ApiError;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub sub: String,
@@ -16,3 +18,23 @@ pub struct AuthUser {
     pub email: String,
     pub role: String,
 }
+
+/// Role of users that can see and manage the jobs of all users.
+pub const ADMIN_ROLE: &str = "admin";
+
+impl AuthUser {
+    pub fn is_admin(&self) -> bool {
+        self.role == ADMIN_ROLE
+    }
+}
+
+/// For routes that deal with the data of every user.
+pub fn require_admin(auth_user: &AuthUser) -> Result<(), ApiError> {
+    match auth_user.is_admin() {
+        true => Ok(()),
+        false => Err(ApiError::ForbiddenError(format!(
+            "User {} is not an admin",
+            auth_user.id
+        ))),
+    }
+}
@@ -0,0 +1,39 @@
+use serde::Deserialize;
+use shared::database::schemas::common::SimpleRecordId;
+use surrealdb::sql::Thing;
+use surrealdb::{Surreal, engine::remote::ws::Client};
+
+use crate::auth::auth::AuthUser;
+use crate::errors::ApiError;
+
+#[derive(Deserialize)]
+struct UserRecord {
+    #[allow(dead_code)]
+    id: SimpleRecordId,
+}
+
+/// The OAuth flow puts the id of the users record, e.g., "users:abc123", in the JWT subject.
+pub fn parse_user_id(user_id: &str) -> Result<Thing, ApiError> {
+    surrealdb::sql::thing(user_id)
+        .map_err(|_| ApiError::UnauthorizedError(format!("Invalid user id {}", user_id)))
+}
+
+/// Make sure that the authenticated user has a users record, and return its id.
+pub async fn get_user(db: &Surreal<Client>, auth_user: &AuthUser) -> Result<Thing, ApiError> {
+    let user_id = parse_user_id(&auth_user.id)?;
+
+    let mut response = db
+        .query("SELECT id FROM $user")
+        .bind(("user", user_id.clone()))
+        .await?;
+
+    let users: Vec<UserRecord> = response.take(0)?;
+    if users.is_empty() {
+        return Err(ApiError::UnauthorizedError(format!(
+            "User {} does not exist",
+            auth_user.id
+        )));
+    }
+
+    Ok(user_id)
+}
@@ -1,11 +1,14 @@
 use std::env::VarError;
 
+use axum::Json;
 use axum::response::{IntoResponse, Response};
 use axum::{extract::multipart::MultipartError, http::StatusCode};
 
 use shared::database::DatabaseError;
+use shared::fastq::FastqValidationError;
 use shared::minio::MinIoError;
 use shared::nats::NatsError;
+use shared::schema::{sample_sheet::SampleSheetError, upload::SampleSheetErrors};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -22,7 +25,34 @@ pub enum ApiError {
     #[error("Failed to insert db record")]
     DatabaseRecordInsertError(String),
 
+    #[error("Unauthorized")]
+    UnauthorizedError(String),
+
+    #[error("Forbidden")]
+    ForbiddenError(String),
+
+    #[error("Record not found")]
+    RecordNotFoundError(String),
+
+    #[error("Invalid upload session")]
+    UploadSessionError(String),
+
+    #[error("Invalid upload config")]
+    InvalidUploadConfig(String),
+
+    #[error("Invalid sample sheet")]
+    InvalidSampleSheet(Vec<SampleSheetError>),
+
+    #[error("Invalid query")]
+    InvalidQueryError(String),
+
+    #[error("Invalid status")]
+    InvalidStatusError(String),
+
     // Shared errors
+    #[error(transparent)]
+    InvalidFastq(#[from] FastqValidationError),
+
     #[error(transparent)]
     MinIo(#[from] MinIoError),
 
@@ -75,6 +105,38 @@ impl IntoResponse for ApiError {
             ApiError::DatabaseRecordInsertError(s) => {
                 (StatusCode::BAD_REQUEST, format!("Database error: {:?}", s))
             }
+            ApiError::UnauthorizedError(s) => {
+                (StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", s))
+            }
+            ApiError::ForbiddenError(s) => (StatusCode::FORBIDDEN, format!("Forbidden: {}", s)),
+            ApiError::RecordNotFoundError(s) => {
+                (StatusCode::NOT_FOUND, format!("Record not found: {}", s))
+            }
+            ApiError::UploadSessionError(s) => (
+                StatusCode::CONFLICT,
+                format!("Invalid upload session: {}", s),
+            ),
+            ApiError::InvalidUploadConfig(s) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Invalid upload config: {}", s),
+            ),
+            ApiError::InvalidQueryError(s) => {
+                (StatusCode::BAD_REQUEST, format!("Invalid query: {}", s))
+            }
+            ApiError::InvalidStatusError(s) => {
+                (StatusCode::CONFLICT, format!("Invalid status: {}", s))
+            }
+            ApiError::InvalidSampleSheet(errors) => {
+                return (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(SampleSheetErrors { errors }),
+                )
+                    .into_response();
+            }
+            // Return the offending record as json, so clients can show where the file is broken.
+            ApiError::InvalidFastq(e) => {
+                return (StatusCode::UNPROCESSABLE_ENTITY, Json(e)).into_response();
+            }
             ApiError::MinIo(s) => (StatusCode::BAD_REQUEST, format!("MinIO error: {:?}", s)),
             ApiError::Nats(s) => (StatusCode::BAD_REQUEST, format!("Nats error: {:?}", s)),
             ApiError::Database(s) => (StatusCode::BAD_REQUEST, format!("Database error: {:?}", s)),
@@ -6,6 +6,7 @@ use tokio::net::TcpListener;
 mod auth;
 mod minio_upload;
 mod nats;
+mod reconcile;
 mod routes;
 mod schema;
 
@@ -16,17 +17,32 @@ mod errors;
 use errors::ApiError;
 
 use shared::database::connect_db;
-use shared::minio::connect_minio;
+use shared::minio::{Presigner, connect_minio};
 use shared::nats::connect_nats;
 
 use tower_http::cors::{Any, CorsLayer};
 
-use crate::state::{MinIO, Nats, SurrealDB};
+use crate::nats::publisher::outbox::relay_outbox;
+use crate::nats::subscriber::sample_status::relay_sample_status;
+use crate::reconcile::periodic_reconcile;
+use crate::state::{MinIO, Nats, SampleStatusEvents, SurrealDB};
+
+/// How many status events an event stream can fall behind before it skips some.
+const STATUS_EVENT_CAPACITY: usize = 1_024;
 
 fn app(state: ConnectionState) -> Router {
     let cors = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::PATCH])
+        .allow_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::DELETE,
+            Method::PATCH,
+        ])
+        .allow_headers(Any)
+        // So that the browser can read e.g. Upload-Offset.
+        .expose_headers(Any)
         .allow_credentials(false);
 
     routes::create_routes(state).layer(cors)
@@ -41,12 +57,24 @@ async fn main() -> Result<(), ApiError> {
 
     let db = connect_db(3).await?;
     let minio = connect_minio().await?;
+    let presigner = Presigner::from_env()?;
     let nats = connect_nats().await?;
 
+    let (status_sender, _) = tokio::sync::broadcast::channel(STATUS_EVENT_CAPACITY);
+    tokio::spawn(relay_sample_status(nats.clone(), status_sender.clone()));
+    tokio::spawn(relay_outbox(db.clone(), nats.clone()));
+    tokio::spawn(periodic_reconcile(db.clone(), minio.clone(), nats.clone()));
+
     let state = ConnectionState {
         surrealdb: SurrealDB { client: db },
-        minio: MinIO { client: minio },
+        minio: MinIO {
+            client: minio,
+            presigner,
+        },
         nats: Nats { client: nats },
+        status_events: SampleStatusEvents {
+            sender: status_sender,
+        },
     };
 
     let app = app(state);
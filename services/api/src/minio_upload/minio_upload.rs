@@ -1,22 +1,61 @@
-use axum::body::Bytes;
+use axum::extract::multipart::Field;
+use log::{error, info};
 use minio::s3::Client;
 
+use crate::errors::ApiError;
 use crate::schema::file_upload::UploadField;
-use shared::minio::{errors::MinIoError, upload::minio_upload_bytes};
+use shared::fastq::{FastqValidator, ValidationMode};
+use shared::minio::MultipartUpload;
 use shared::utils::time::time_now;
 use uuid;
 
+/// Bucket that every upload goes to.
+pub const UPLOAD_BUCKET: &str = "my-bucket";
+
+/// Add uuid to make sure key is unique. Returns the uuid and the key.
+pub fn unique_key(key: &str) -> (String, String) {
+    let file_uuid = uuid::Uuid::now_v7().to_string();
+    let minio_key = format!("{}/{}", file_uuid, key);
+    (file_uuid, minio_key)
+}
+
+/// Stream a multipart field chunk by chunk into a MinIO multipart upload,
+/// so that we never hold more than a single part in memory. The chunks are
+/// validated as FASTQ on the way, and the upload is aborted if they aren't.
 pub async fn file_upload(
     bucket: &str,
     key: &str,
-    file_contents: Bytes,
+    field: &mut Field<'_>,
     minio_client: &Client,
-) -> Result<UploadField, MinIoError> {
-    // Add uuid to make sure key is unique.
-    let file_uuid = uuid::Uuid::now_v7().to_string();
-    let minio_key = format!("{}/{}", file_uuid, key);
+) -> Result<UploadField, ApiError> {
+    let (file_uuid, minio_key) = unique_key(key);
+
+    let mut upload = MultipartUpload::new(minio_client, bucket, &minio_key).await?;
+
+    // If anything goes wrong mid-stream (client disconnects, body
+    // timeout, MinIO error), abort so we don't leave dangling parts.
+    let mut validator = FastqValidator::new(ValidationMode::from_env());
+    let streamed: Result<(), ApiError> = async {
+        while let Some(chunk) = field.chunk().await? {
+            validator.push(&chunk)?;
+            upload.write(chunk).await?;
+        }
+        validator.finish(true)?;
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = streamed {
+        error!("Failed to stream {} to MinIO: {:?}", key, e);
+        // The stream error is what the client needs to know about.
+        if let Err(abort_error) = upload.abort().await {
+            error!("Failed to abort upload of {}: {:?}", key, abort_error);
+        }
+        return Err(e);
+    }
 
-    let url = minio_upload_bytes(minio_client, bucket, &minio_key, file_contents.into()).await?;
+    info!("Streamed {} bytes for {}", upload.total_bytes(), key);
+    let url = upload.complete().await?;
 
     Ok(UploadField {
         file_name: key.into(),
@@ -1,2 +1,5 @@
 mod minio_upload;
-pub use minio_upload::file_upload;
+pub use minio_upload::{UPLOAD_BUCKET, file_upload, unique_key};
+
+mod validate;
+pub use validate::validate_fastq_object;
@@ -0,0 +1,46 @@
+use futures::StreamExt;
+use log::info;
+use minio::s3::{Client, types::S3Api};
+use shared::fastq::{FastqValidator, ValidationMode, ValidationSummary};
+use shared::minio::MinIoError;
+
+use crate::errors::ApiError;
+
+/// Validate an object that is already in MinIO as FASTQ. In prefix mode
+/// we stop reading once enough records have been checked.
+pub async fn validate_fastq_object(
+    minio_client: &Client,
+    bucket: &str,
+    key: &str,
+    mode: ValidationMode,
+) -> Result<ValidationSummary, ApiError> {
+    let object_response = minio_client
+        .get_object(bucket, key)
+        .send()
+        .await
+        .map_err(MinIoError::from)?;
+
+    let (mut stream, _) = object_response
+        .content
+        .to_stream()
+        .await
+        .map_err(MinIoError::from)?;
+
+    let mut validator = FastqValidator::new(mode);
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(MinIoError::from)?;
+        validator.push(&chunk)?;
+
+        if validator.is_done() {
+            break;
+        }
+    }
+
+    let summary = validator.finish(true)?;
+    info!(
+        "Validated {} records ({} bases) in {}/{}",
+        summary.records, summary.bases, bucket, key
+    );
+
+    Ok(summary)
+}
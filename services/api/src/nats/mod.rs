@@ -1 +1,2 @@
-pub mod publisher;
\ No newline at end of file
+pub mod publisher;
+pub mod subscriber;
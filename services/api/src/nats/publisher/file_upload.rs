@@ -1,28 +1,26 @@
-use async_nats::jetstream::Context;
+use shared::database::schemas::outbox::OutboxData;
 use shared::nats::errors::NatsError;
+use shared::nats::schema::envelope::Envelope;
 use shared::nats::schema::fastq_service::FastqMessage;
 use shared::nats::streams::config::{StreamConsumerConfig, StreamType};
 
-pub async fn nats_publish_upload(
-    nats: Context,
-    fastq_message: &FastqMessage,
-) -> Result<(), NatsError> {
+use crate::nats::publisher::outbox::outbox_entry;
+
+/// The outbox entry that sends `fastq_message` to the fastq preprocessor.
+/// Write it in the same transaction as the sample it is about.
+pub fn upload_outbox_entry(
+    user_id: &str,
+    fastq_message: FastqMessage,
+) -> Result<OutboxData, NatsError> {
     let cfg = StreamConsumerConfig::from(StreamType::FileUpload);
 
     // Here, we could publish to any allowed stream subject, but for
     // convenience since we only have one consumer handling one subject,
     // we set the publishing subject be equal to the consumer filter subject.
-    let ack = nats
-        .publish(
-            cfg.consumer.deliver_subject,
-            serde_json::to_string(fastq_message)
-                .expect("Failed to serialize NATS message.")
-                // Fix.
-                .into(),
-        )
-        .await?;
-
-    ack.await?;
+    let subject = cfg
+        .consumer
+        .filter_subject()
+        .expect("File upload stream has no consumer.");
 
-    Ok(())
+    outbox_entry(subject, &Envelope::new(user_id, fastq_message))
 }
@@ -1 +1,2 @@
 pub mod file_upload;
+pub mod outbox;
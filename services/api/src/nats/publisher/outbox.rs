@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use async_nats::HeaderMap;
+use async_nats::header::NATS_MESSAGE_ID;
+use async_nats::jetstream::Context;
+use log::{error, info, warn};
+use serde_json::json;
+use shared::database::schemas::outbox::{Outbox, OutboxData};
+use shared::nats::errors::NatsError;
+use shared::nats::schema::envelope::{Envelope, Payload};
+use shared::utils::time::time_now;
+use surrealdb::{Surreal, engine::remote::ws::Client};
+
+use crate::errors::ApiError;
+
+pub const OUTBOX_TABLE: &str = "outbox";
+
+/// How long to wait before looking again, when the outbox is empty or NATS is down.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Max number of entries to publish in one go.
+const BATCH_SIZE: usize = 100;
+
+/// An outbox entry that publishes `envelope` to `subject`.
+pub fn outbox_entry<T: Payload>(
+    subject: &str,
+    envelope: &Envelope<T>,
+) -> Result<OutboxData, NatsError> {
+    Ok(OutboxData {
+        subject: subject.to_string(),
+        payload: String::from_utf8_lossy(&envelope.to_bytes()?).to_string(),
+        msg_id: envelope.correlation_id.clone(),
+        attempts: 0,
+        error: None,
+        created_at: time_now(),
+    })
+}
+
+/// Publish whatever is in the outbox, for as long as the API runs.
+///
+/// Several API instances may publish the same entry, or an instance may die
+/// between publishing and removing it. JetStream drops the duplicates by their
+/// Nats-Msg-Id, as long as they are published within its duplicate window.
+pub async fn relay_outbox(db: Surreal<Client>, nats: Context) {
+    info!("Relaying outbox...");
+    loop {
+        match publish_pending(&db, &nats).await {
+            // There might be more.
+            Ok(published) if published == BATCH_SIZE => continue,
+            Ok(_) => {}
+            Err(e) => error!("Outbox relay failed: {:?}", e),
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Publish the oldest entries in order, and stop at the first that fails.
+/// Returns the number of published entries.
+async fn publish_pending(db: &Surreal<Client>, nats: &Context) -> Result<usize, ApiError> {
+    let mut response = db
+        .query("SELECT * FROM type::table($table) ORDER BY created_at ASC LIMIT $limit")
+        .bind(json!({
+            "table": OUTBOX_TABLE,
+            "limit": BATCH_SIZE,
+        }))
+        .await?;
+
+    let entries: Vec<Outbox> = response.take(0)?;
+
+    let mut published = 0;
+    for entry in entries {
+        let Some(id) = entry.id else {
+            continue;
+        };
+
+        match publish(nats, &entry.data).await {
+            Ok(()) => {
+                db.query("DELETE $entry")
+                    .bind(("entry", id.surrealdb_id()?))
+                    .await?;
+                published += 1;
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to publish outbox entry {}: {:?}",
+                    id.formatted_id(),
+                    e
+                );
+                db.query("UPDATE $entry SET attempts += 1, error = $error")
+                    .bind(("entry", id.surrealdb_id()?))
+                    .bind(("error", format!("{:?}", e)))
+                    .await?;
+                break;
+            }
+        }
+    }
+
+    Ok(published)
+}
+
+async fn publish(nats: &Context, entry: &OutboxData) -> Result<(), NatsError> {
+    let mut headers = HeaderMap::new();
+    headers.insert(NATS_MESSAGE_ID, entry.msg_id.as_str());
+
+    let ack = nats
+        .publish_with_headers(entry.subject.clone(), headers, entry.payload.clone().into())
+        .await?;
+    ack.await?;
+
+    Ok(())
+}
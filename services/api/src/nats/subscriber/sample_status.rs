@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use async_nats::jetstream::Context;
+use futures::StreamExt;
+use log::{error, info, warn};
+use shared::nats::errors::NatsError;
+use shared::nats::schema::sample_status::SampleStatusMessage;
+use shared::nats::streams::{config::StreamType, stream::create_ephemeral_consumer};
+use tokio::sync::broadcast;
+
+/// How long to wait before attaching a new consumer after losing the old one.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Relay status events from NATS to the open event streams, for as long as
+/// the API runs. Events published while there is no consumer are not replayed,
+/// clients get the current status from the sample endpoints instead.
+pub async fn relay_sample_status(nats: Context, sender: broadcast::Sender<SampleStatusMessage>) {
+    let id = uuid::Uuid::now_v7().simple().to_string();
+
+    loop {
+        if let Err(e) = relay_messages(&nats, &sender, &id).await {
+            error!("Sample status relay failed: {:?}", e);
+        }
+
+        warn!(
+            "Sample status relay stopped, restarting in {:?}",
+            RECONNECT_DELAY
+        );
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn relay_messages(
+    nats: &Context,
+    sender: &broadcast::Sender<SampleStatusMessage>,
+    id: &str,
+) -> Result<(), NatsError> {
+    let consumer = create_ephemeral_consumer(nats, StreamType::SampleStatus, id).await?;
+    let mut messages = consumer
+        .messages()
+        .await
+        .map_err(|err| NatsError::GetConsumerError(err.to_string()))?;
+
+    info!("Relaying sample status events...");
+    while let Some(message) = messages.next().await {
+        let message = message.map_err(|err| NatsError::GetConsumerError(err.to_string()))?;
+
+        match serde_json::from_slice::<SampleStatusMessage>(&message.payload) {
+            // Sending only fails when no one is listening, which is fine.
+            Ok(status) => {
+                let _ = sender.send(status);
+            }
+            Err(e) => error!("Got invalid sample status message: {:?}", e),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,2 @@
+mod reconcile;
+pub use reconcile::{ReconcileOptions, periodic_reconcile, reconcile};
@@ -0,0 +1,417 @@
+use std::collections::HashSet;
+use std::env;
+use std::time::Duration;
+
+use async_nats::jetstream::Context;
+use chrono::Utc;
+use log::{error, info, warn};
+use minio::s3::Client as MinioClient;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use shared::{
+    database::schemas::{common::SimpleRecordId, fastq_sample::FastqSampleData, outbox::Outbox},
+    minio::{MinioObject, minio_delete_object, minio_list_objects, upload::object_url},
+    nats::{
+        pending::unacked_messages,
+        schema::{envelope::Envelope, fastq_service::FastqMessage},
+        status::publish_sample_status,
+        streams::config::{RetryPolicy, StreamType},
+    },
+    schema::schema::Status,
+    utils::time::time_now,
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+
+use crate::errors::ApiError;
+use crate::minio_upload::UPLOAD_BUCKET;
+use crate::nats::publisher::file_upload::upload_outbox_entry;
+use crate::nats::publisher::outbox::OUTBOX_TABLE;
+use crate::routes::samples::FASTQ_SAMPLE_TABLE;
+
+/// Where the fastq_service uploads the filtered files.
+const PROCESSED_BUCKET: &str = "file-upload-processed";
+
+/// Anything younger than this might still be in flight, e.g., an object
+/// whose sample is being written, so it is left alone.
+pub const DEFAULT_MIN_AGE_MINUTES: i64 = 60;
+
+pub struct ReconcileOptions {
+    /// Only report, don't repair anything.
+    pub dry_run: bool,
+    pub min_age_minutes: i64,
+}
+
+impl Default for ReconcileOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: true,
+            min_age_minutes: DEFAULT_MIN_AGE_MINUTES,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Repair {
+    /// Publish the job again.
+    Requeue,
+    /// Give up on the sample, it failed as often as the stream retries it.
+    MarkError,
+}
+
+/// A created or pending sample without a job on the stream or in the outbox.
+#[derive(Serialize, Debug)]
+pub struct StuckSample {
+    pub id: String,
+    pub status: Status,
+    pub attempts: usize,
+    pub updated_at: String,
+    pub repair: Repair,
+}
+
+/// Inconsistencies between SurrealDB, MinIO and NATS. On a dry run, these
+/// are what would be repaired, otherwise what was repaired.
+#[derive(Serialize, Debug, Default)]
+pub struct ReconcileReport {
+    pub dry_run: bool,
+    /// Keys of uploaded files that no sample, batch or open upload session refers to.
+    pub orphan_uploads: Vec<String>,
+    /// Keys of filtered files that no preprocess run refers to.
+    pub orphan_processed: Vec<String>,
+    pub stuck_samples: Vec<StuckSample>,
+    /// Repairs that failed, these are retried on the next run.
+    pub errors: Vec<String>,
+}
+
+impl ReconcileReport {
+    pub fn is_empty(&self) -> bool {
+        self.orphan_uploads.is_empty()
+            && self.orphan_processed.is_empty()
+            && self.stuck_samples.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+struct SampleRecord {
+    id: SimpleRecordId,
+    owner: Option<SimpleRecordId>,
+    #[serde(flatten)]
+    data: FastqSampleData,
+}
+
+/// Scan the three stores and report, or repair, whatever doesn't add up.
+pub async fn reconcile(
+    db: &Surreal<Client>,
+    minio: &MinioClient,
+    nats: &Context,
+    options: &ReconcileOptions,
+) -> Result<ReconcileReport, ApiError> {
+    let cutoff = chrono::Duration::try_minutes(options.min_age_minutes)
+        .and_then(|min_age| Utc::now().checked_sub_signed(min_age))
+        .ok_or(ApiError::InvalidQueryError(format!(
+            "min_age_minutes {} is out of range",
+            options.min_age_minutes
+        )))?;
+
+    let mut report = ReconcileReport {
+        dry_run: options.dry_run,
+        ..Default::default()
+    };
+
+    let referenced = referenced_uploads(db).await?;
+    for object in orphans(minio, UPLOAD_BUCKET, &referenced, cutoff).await? {
+        delete_orphan(minio, UPLOAD_BUCKET, &object, options, &mut report).await;
+        report.orphan_uploads.push(object.key);
+    }
+
+    let referenced = referenced_processed(db).await?;
+    for object in orphans(minio, PROCESSED_BUCKET, &referenced, cutoff).await? {
+        delete_orphan(minio, PROCESSED_BUCKET, &object, options, &mut report).await;
+        report.orphan_processed.push(object.key);
+    }
+
+    let cutoff = cutoff.format("%Y-%m-%d %H:%M:%S").to_string();
+    for sample in stuck_samples(db, nats, &cutoff).await? {
+        let stuck = StuckSample {
+            id: sample.id.key().to_string(),
+            status: sample.data.status,
+            attempts: sample.data.attempts,
+            updated_at: sample.data.updated_at.clone(),
+            repair: repair_for(&sample.data),
+        };
+
+        if !options.dry_run
+            && let Err(e) = repair_sample(db, nats, &sample, stuck.repair).await
+        {
+            report
+                .errors
+                .push(format!("{}: {:?}", sample.id.formatted_id(), e));
+        }
+        report.stuck_samples.push(stuck);
+    }
+
+    info!(
+        "Reconciled (dry run: {}): {} orphan uploads, {} orphan processed files, {} stuck samples, {} errors",
+        report.dry_run,
+        report.orphan_uploads.len(),
+        report.orphan_processed.len(),
+        report.stuck_samples.len(),
+        report.errors.len()
+    );
+
+    Ok(report)
+}
+
+/// Run reconcile every RECONCILE_INTERVAL_MINUTES, for as long as the API runs.
+/// Only reports, unless RECONCILE_REPAIR is "true".
+pub async fn periodic_reconcile(db: Surreal<Client>, minio: MinioClient, nats: Context) {
+    let Some(interval) = env::var("RECONCILE_INTERVAL_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .filter(|minutes| *minutes > 0)
+    else {
+        info!("RECONCILE_INTERVAL_MINUTES not set, not reconciling periodically.");
+        return;
+    };
+
+    let options = ReconcileOptions {
+        dry_run: env::var("RECONCILE_REPAIR").as_deref() != Ok("true"),
+        ..Default::default()
+    };
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval * 60)).await;
+
+        match reconcile(&db, &minio, &nats, &options).await {
+            Ok(report) if !report.is_empty() => warn!("Inconsistencies found: {:?}", report),
+            Ok(_) => {}
+            Err(e) => error!("Reconcile failed: {:?}", e),
+        }
+    }
+}
+
+/// Objects in `bucket`, older than `cutoff`, whose url isn't in `referenced`.
+async fn orphans(
+    minio: &MinioClient,
+    bucket: &str,
+    referenced: &HashSet<String>,
+    cutoff: chrono::DateTime<Utc>,
+) -> Result<Vec<MinioObject>, ApiError> {
+    let mut orphans: Vec<MinioObject> = Vec::new();
+    for object in minio_list_objects(minio, bucket).await? {
+        let old_enough = object
+            .last_modified
+            .is_none_or(|modified| modified < cutoff);
+        if old_enough && !referenced.contains(&object_url(bucket, &object.key)?) {
+            orphans.push(object);
+        }
+    }
+
+    Ok(orphans)
+}
+
+async fn delete_orphan(
+    minio: &MinioClient,
+    bucket: &str,
+    object: &MinioObject,
+    options: &ReconcileOptions,
+    report: &mut ReconcileReport,
+) {
+    if options.dry_run {
+        return;
+    }
+
+    if let Err(e) = minio_delete_object(minio, bucket, &object.key).await {
+        report
+            .errors
+            .push(format!("{}/{}: {:?}", bucket, object.key, e));
+    }
+}
+
+/// Urls of uploaded files that are still needed. Files of an open upload
+/// session are not referenced anywhere else yet.
+async fn referenced_uploads(db: &Surreal<Client>) -> Result<HashSet<String>, ApiError> {
+    let mut response = db
+        .query(format!(
+            "SELECT VALUE url FROM {samples};
+             SELECT VALUE mate_url FROM {samples} WHERE mate_url != NONE;
+             SELECT VALUE uploads.url FROM batches;
+             SELECT VALUE [bucket, key] FROM upload_sessions WHERE status != $done;",
+            samples = FASTQ_SAMPLE_TABLE,
+        ))
+        .bind(("done", Status::Done))
+        .await?;
+
+    let mut urls: HashSet<String> = HashSet::new();
+    urls.extend(response.take::<Vec<String>>(0)?);
+    urls.extend(response.take::<Vec<String>>(1)?);
+    urls.extend(response.take::<Vec<Vec<String>>>(2)?.into_iter().flatten());
+    for session in response.take::<Vec<(String, String)>>(3)? {
+        urls.insert(object_url(&session.0, &session.1)?);
+    }
+
+    Ok(urls)
+}
+
+/// Urls of filtered files that belong to a preprocess run.
+async fn referenced_processed(db: &Surreal<Client>) -> Result<HashSet<String>, ApiError> {
+    let mut response = db
+        .query(
+            "SELECT VALUE url FROM fastq_preprocess;
+             SELECT VALUE mate_url FROM fastq_preprocess WHERE mate_url != NONE;
+             SELECT VALUE report_url FROM fastq_preprocess WHERE report_url != NONE;",
+        )
+        .await?;
+
+    let mut urls: HashSet<String> = HashSet::new();
+    urls.extend(response.take::<Vec<String>>(0)?);
+    urls.extend(response.take::<Vec<String>>(1)?);
+    urls.extend(response.take::<Vec<String>>(2)?);
+
+    Ok(urls)
+}
+
+/// Created or pending samples, that haven't changed since `cutoff`, and
+/// that no message on the stream or in the outbox is about.
+async fn stuck_samples(
+    db: &Surreal<Client>,
+    nats: &Context,
+    cutoff: &str,
+) -> Result<Vec<SampleRecord>, ApiError> {
+    let mut queued: HashSet<String> = HashSet::new();
+
+    for envelope in unacked_messages::<FastqMessage>(nats, StreamType::FileUpload).await? {
+        queued.insert(envelope.payload.fastq_sample_id.formatted_id());
+    }
+
+    let mut response = db
+        .query("SELECT * FROM type::table($table)")
+        .bind(("table", OUTBOX_TABLE))
+        .await?;
+    let entries: Vec<Outbox> = response.take(0)?;
+    for entry in entries {
+        if let Ok(envelope) = Envelope::<FastqMessage>::from_slice(entry.data.payload.as_bytes()) {
+            queued.insert(envelope.payload.fastq_sample_id.formatted_id());
+        }
+    }
+
+    let mut response = db
+        .query(
+            "SELECT *, array::first(<-uploaded.in) AS owner FROM type::table($table)
+             WHERE status IN $statuses AND updated_at < $cutoff",
+        )
+        .bind(json!({
+            "table": FASTQ_SAMPLE_TABLE,
+            "statuses": [Status::Created, Status::Pending],
+            "cutoff": cutoff,
+        }))
+        .await?;
+    let samples: Vec<SampleRecord> = response.take(0)?;
+
+    Ok(samples
+        .into_iter()
+        .filter(|sample| !queued.contains(&sample.id.formatted_id()))
+        .collect())
+}
+
+/// Pending samples that were started as often as the stream delivers a
+/// message are given up on. Everything else gets another go.
+fn repair_for(sample: &FastqSampleData) -> Repair {
+    let max_deliveries = RetryPolicy::default().max_deliveries as usize;
+
+    match sample.status {
+        Status::Pending if sample.attempts >= max_deliveries => Repair::MarkError,
+        _ => Repair::Requeue,
+    }
+}
+
+async fn repair_sample(
+    db: &Surreal<Client>,
+    nats: &Context,
+    sample: &SampleRecord,
+    repair: Repair,
+) -> Result<(), ApiError> {
+    let owner = sample
+        .owner
+        .as_ref()
+        .ok_or(ApiError::RecordNotFoundError(format!(
+            "Owner of {}",
+            sample.id.formatted_id()
+        )))?;
+
+    match repair {
+        Repair::Requeue => {
+            let fastq_message = FastqMessage {
+                url: sample.data.url.clone(),
+                mate_url: sample.data.mate_url.clone(),
+                fastq_sample_id: sample.id.clone(),
+                config: sample.data.config.clone(),
+            };
+            let outbox_entry = upload_outbox_entry(&owner.formatted_id(), fastq_message)?;
+
+            // The lost message is replaced, so the sample now follows the new one.
+            // Unless the sample moved on in the meantime, then nothing is sent.
+            let mut response = db
+                .query(
+                    "BEGIN TRANSACTION;
+                     LET $updated = UPDATE $sample SET run_id = $run_id
+                        WHERE status IN $statuses RETURN AFTER;
+                     IF $updated {
+                        CREATE type::table($table) CONTENT $outbox_entry;
+                     };
+                     RETURN $updated;
+                     COMMIT TRANSACTION;",
+                )
+                .bind(("sample", sample.id.surrealdb_id()?))
+                .bind(("run_id", outbox_entry.msg_id.clone()))
+                .bind(("statuses", [Status::Created, Status::Pending]))
+                .bind(("table", OUTBOX_TABLE))
+                .bind(("outbox_entry", outbox_entry))
+                .await?;
+
+            // The result of the transaction is the last statement.
+            let last = response.num_statements() - 1;
+            let updated: Vec<SampleRecord> = response.take(last)?;
+            if updated.is_empty() {
+                info!("{} moved on, not requeued", sample.id.formatted_id());
+                return Ok(());
+            }
+        }
+        Repair::MarkError => {
+            let error = "Gave up after the job was lost".to_string();
+
+            // Unless the sample moved on in the meantime.
+            let mut response = db
+                .query(
+                    "UPDATE $sample SET status = $status, error = $error, updated_at = $updated_at
+                     WHERE status IN $sources RETURN AFTER",
+                )
+                .bind(("sample", sample.id.surrealdb_id()?))
+                .bind(json!({
+                    "status": Status::Error,
+                    "error": error,
+                    "sources": Status::sources(Status::Error),
+                    "updated_at": time_now(),
+                }))
+                .await?;
+            let updated: Vec<SampleRecord> = response.take(0)?;
+
+            if !updated.is_empty()
+                && let Err(e) = publish_sample_status(
+                    nats,
+                    &owner.formatted_id(),
+                    sample.id.key(),
+                    Status::Error,
+                    Some(error),
+                )
+                .await
+            {
+                warn!("Failed to publish status of {}: {:?}", sample.id.key(), e);
+            }
+        }
+    }
+
+    info!("Repaired {} with {:?}", sample.id.formatted_id(), repair);
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use axum::{
+    Json,
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use log::info;
+use serde::Deserialize;
+use serde_json::json;
+use shared::nats::dead_letter;
+
+use crate::auth::auth::{AuthUser, require_admin};
+use crate::errors::ApiError;
+use crate::state::ConnectionState;
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 500;
+
+/// e.g., /dead-letters?start=120&limit=50
+#[derive(Deserialize)]
+pub struct DeadLetterQuery {
+    start: Option<u64>,
+    limit: Option<usize>,
+}
+
+/// List dead-lettered jobs, oldest first. `next` is the start of the next page.
+pub async fn list_dead_letters(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Query(query): Query<DeadLetterQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&auth_user)?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(ApiError::InvalidQueryError(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+
+    let (dead_letters, next) =
+        dead_letter::list_dead_letters(&state.nats.client, query.start, limit).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({"dead_letters": dead_letters, "next": next})),
+    ))
+}
+
+/// Publish a dead-lettered job again, which gives it a fresh set of deliveries.
+pub async fn replay_dead_letter(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(sequence): Path<u64>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&auth_user)?;
+
+    let nats = state.nats.client;
+
+    let dead_letter = dead_letter::get_dead_letter(&nats, sequence)
+        .await?
+        .ok_or(ApiError::RecordNotFoundError(sequence.to_string()))?;
+
+    dead_letter::replay_dead_letter(&nats, &dead_letter).await?;
+
+    info!(
+        "User {} replayed dead letter {} to {}",
+        auth_user.id, sequence, dead_letter.message.subject
+    );
+
+    Ok((StatusCode::OK, Json(json!({"replayed": sequence}))))
+}
@@ -0,0 +1,19 @@
+mod dead_letter;
+use axum::{
+    Router, middleware,
+    routing::{get, post},
+};
+pub use dead_letter::{list_dead_letters, replay_dead_letter};
+
+use crate::auth::middleware::auth_middleware;
+use crate::state::ConnectionState;
+
+pub fn routes() -> Router<ConnectionState> {
+    let router = Router::new()
+        .route("/dead-letters", get(list_dead_letters))
+        .route("/dead-letters/{sequence}/replay", post(replay_dead_letter))
+        // Dead letters hold the jobs of every user, so these are for admins only.
+        .route_layer(middleware::from_fn(auth_middleware));
+
+    router
+}
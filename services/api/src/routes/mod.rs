@@ -2,6 +2,9 @@ use crate::state::ConnectionState;
 use axum::Router;
 
 mod auth;
+mod dead_letter;
+mod reconcile;
+pub(crate) mod samples;
 mod todo;
 mod upload;
 
@@ -10,6 +13,9 @@ pub fn create_routes(state: ConnectionState) -> Router {
     let router = Router::new()
         .merge(todo::routes())
         .merge(upload::routes())
+        .merge(samples::routes())
+        .merge(dead_letter::routes())
+        .merge(reconcile::routes())
         .merge(auth::router())
         .with_state(state);
 
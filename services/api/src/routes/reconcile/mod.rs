@@ -0,0 +1,15 @@
+mod reconcile;
+use axum::{Router, middleware, routing::post};
+pub use reconcile::run_reconcile;
+
+use crate::auth::middleware::auth_middleware;
+use crate::state::ConnectionState;
+
+pub fn routes() -> Router<ConnectionState> {
+    let router = Router::new()
+        .route("/reconcile", post(run_reconcile))
+        // Looks at the samples and files of every user, so for admins only.
+        .route_layer(middleware::from_fn(auth_middleware));
+
+    router
+}
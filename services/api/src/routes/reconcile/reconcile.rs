@@ -0,0 +1,57 @@
+use axum::{
+    Json,
+    extract::{Extension, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use log::info;
+use serde::Deserialize;
+
+use crate::auth::auth::{AuthUser, require_admin};
+use crate::errors::ApiError;
+use crate::reconcile::{ReconcileOptions, reconcile};
+use crate::state::ConnectionState;
+
+/// e.g., /reconcile?dry_run=false&min_age_minutes=30
+#[derive(Deserialize)]
+pub struct ReconcileQuery {
+    dry_run: Option<bool>,
+    min_age_minutes: Option<i64>,
+}
+
+/// Report inconsistencies between the database, the buckets and the streams.
+/// Unless dry_run is false, nothing is repaired.
+pub async fn run_reconcile(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Query(query): Query<ReconcileQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&auth_user)?;
+
+    let default = ReconcileOptions::default();
+    let options = ReconcileOptions {
+        dry_run: query.dry_run.unwrap_or(default.dry_run),
+        min_age_minutes: query.min_age_minutes.unwrap_or(default.min_age_minutes),
+    };
+
+    if options.min_age_minutes < 0 {
+        return Err(ApiError::InvalidQueryError(
+            "min_age_minutes can't be negative".into(),
+        ));
+    }
+
+    let report = reconcile(
+        &state.surrealdb.client,
+        &state.minio.client,
+        &state.nats.client,
+        &options,
+    )
+    .await?;
+
+    info!(
+        "User {} reconciled, dry run: {}",
+        auth_user.id, options.dry_run
+    );
+
+    Ok((StatusCode::OK, Json(report)))
+}
@@ -0,0 +1,68 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::{
+    extract::{Extension, State},
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures::stream;
+use log::warn;
+use shared::nats::schema::sample_status::SampleStatusMessage;
+use shared::schema::sample::SAMPLE_STATUS_EVENT;
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+
+use crate::auth::auth::AuthUser;
+use crate::errors::ApiError;
+use crate::state::ConnectionState;
+
+/// Wait for the next status change of one of the user's samples.
+async fn next_event(user_id: &str, receiver: &mut Receiver<SampleStatusMessage>) -> Option<Event> {
+    loop {
+        match receiver.recv().await {
+            Ok(message) if message.user_id == user_id => {
+                match Event::default()
+                    .event(SAMPLE_STATUS_EVENT)
+                    .json_data(&message.event)
+                {
+                    Ok(event) => return Some(event),
+                    Err(e) => warn!("Failed to serialize status event: {:?}", e),
+                }
+            }
+            Ok(_) => continue,
+            // The client is too slow, skip what it missed rather than disconnecting it.
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(
+                    "Status event stream of {} skipped {} events",
+                    user_id, skipped
+                );
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Stream status changes (created -> pending -> done/error) of the user's
+/// samples as server-sent events, e.g.,
+///
+/// event: status
+/// data: {"sample_id":"abc123","status":"pending","updated_at":"2025-01-31 12:00:00"}
+pub async fn sample_events(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    let receiver = state.status_events.sender.subscribe();
+
+    let events = stream::unfold(
+        (auth_user.id, receiver),
+        |(user_id, mut receiver)| async move {
+            let event = next_event(&user_id, &mut receiver).await?;
+            Some((Ok::<Event, Infallible>(event), (user_id, receiver)))
+        },
+    );
+
+    // Keep proxies from closing the connection while nothing happens.
+    Ok(Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(15))))
+}
@@ -0,0 +1,29 @@
+mod events;
+mod samples;
+use axum::{
+    Router, middleware,
+    routing::{get, post},
+};
+pub use events::sample_events;
+pub use samples::{
+    FASTQ_SAMPLE_TABLE, cancel_sample, get_sample, list_runs, list_samples, reprocess_sample,
+};
+
+use crate::auth::middleware::auth_middleware;
+use crate::state::ConnectionState;
+
+pub fn routes() -> Router<ConnectionState> {
+    let router = Router::new()
+        .route("/samples", get(list_samples))
+        // Live status updates, as server-sent events.
+        .route("/samples/events", get(sample_events))
+        .route("/samples/{sample_id}", get(get_sample))
+        .route("/samples/{sample_id}/cancel", post(cancel_sample))
+        // Preprocess again with new thresholds, and every run so far.
+        .route("/samples/{sample_id}/reprocess", post(reprocess_sample))
+        .route("/samples/{sample_id}/runs", get(list_runs))
+        // Users only ever see the samples they uploaded.
+        .route_layer(middleware::from_fn(auth_middleware));
+
+    router
+}
@@ -0,0 +1,418 @@
+use axum::{
+    Json,
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use log::warn;
+use serde::Deserialize;
+use serde_json::json;
+use shared::{
+    database::schemas::{
+        common::SimpleRecordId,
+        fastq_preprocess::FastqPreprocess,
+        fastq_sample::{FastqSampleConfig, FastqSampleData},
+    },
+    nats::{
+        cancel::publish_job_cancel, schema::fastq_service::FastqMessage,
+        status::publish_sample_status,
+    },
+    schema::sample::{
+        PreprocessSummary, SampleDetail, SampleListQuery, SampleListResponse, SampleSummary,
+    },
+    schema::schema::Status,
+    utils::time::{parse_time_bound, time_now},
+};
+use surrealdb::{Surreal, engine::remote::ws::Client, sql::Thing};
+
+use crate::auth::{auth::AuthUser, user::get_user};
+use crate::errors::ApiError;
+use crate::nats::publisher::file_upload::upload_outbox_entry;
+use crate::nats::publisher::outbox::OUTBOX_TABLE;
+use crate::state::ConnectionState;
+
+pub const FASTQ_SAMPLE_TABLE: &str = "fastq_samples";
+
+/// A fastq sample, with its latest preprocessing result for the detail view.
+#[derive(Deserialize)]
+struct SampleRecord {
+    id: SimpleRecordId,
+    #[serde(default)]
+    preprocess: Option<FastqPreprocess>,
+    #[serde(flatten)]
+    data: FastqSampleData,
+}
+
+#[derive(Deserialize)]
+struct CountRecord {
+    total: usize,
+}
+
+fn sample_summary(id: &SimpleRecordId, data: &FastqSampleData) -> SampleSummary {
+    SampleSummary {
+        id: id.key().to_string(),
+        name: data.name.clone(),
+        status: data.status,
+        error: data.error.clone(),
+        attempts: data.attempts,
+        pipeline: data.pipeline,
+        paired: data.is_paired(),
+        metadata: data.metadata.clone(),
+        created_at: data.created_at.clone(),
+        updated_at: data.updated_at.clone(),
+    }
+}
+
+fn preprocess_summary(preprocess: FastqPreprocess) -> Result<PreprocessSummary, ApiError> {
+    let data = preprocess.data;
+
+    Ok(PreprocessSummary {
+        id: preprocess
+            .id
+            .map(|id| id.key().to_string())
+            .unwrap_or_default(),
+        status: data.status,
+        url: data.url,
+        mate_url: data.mate_url,
+        runtime: data.runtime,
+        min_len: data.config.min_len,
+        max_len: data.config.max_len,
+        min_phred: data.config.min_phred,
+        adapter_trim: data.config.adapter_trim,
+        quality_trim: data.config.quality_trim,
+        result: serde_json::to_value(&data.result)
+            .map_err(|e| ApiError::UnknownError(e.to_string()))?,
+        report_url: data.report_url,
+        created_at: data.created_at,
+        updated_at: data.updated_at,
+    })
+}
+
+/// One of the user's samples. Samples of other users are reported as missing.
+async fn get_owned_sample(
+    db: &Surreal<Client>,
+    user: Thing,
+    sample_id: &str,
+) -> Result<SampleRecord, ApiError> {
+    let mut response = db
+        .query(format!(
+            "SELECT * FROM $user->uploaded->{table} WHERE id = type::thing($table, $id)",
+            table = FASTQ_SAMPLE_TABLE,
+        ))
+        .bind(("user", user))
+        .bind(json!({
+            "table": FASTQ_SAMPLE_TABLE,
+            "id": sample_id,
+        }))
+        .await?;
+
+    let samples: Vec<SampleRecord> = response.take(0)?;
+    samples
+        .into_iter()
+        .next()
+        .ok_or(ApiError::RecordNotFoundError(sample_id.to_string()))
+}
+
+fn time_bound(value: Option<&str>, end_of_day: bool) -> Result<Option<String>, ApiError> {
+    value
+        .map(|value| {
+            parse_time_bound(value, end_of_day).ok_or(ApiError::InvalidQueryError(format!(
+                "Invalid date {}",
+                value
+            )))
+        })
+        .transpose()
+}
+
+/// List the samples that the user uploaded, i.e., the fastq_samples at the
+/// end of the user's uploaded edges, filtered, sorted and paginated.
+pub async fn list_samples(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Query(query): Query<SampleListQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+
+    query.validate().map_err(ApiError::InvalidQueryError)?;
+
+    let from = time_bound(query.from.as_deref(), false)?;
+    let to = time_bound(query.to.as_deref(), true)?;
+
+    let mut conditions: Vec<&str> = Vec::new();
+    if query.pipeline.is_some() {
+        conditions.push("pipeline = $pipeline");
+    }
+    if query.status.is_some() {
+        conditions.push("status = $status");
+    }
+    if from.is_some() {
+        conditions.push("created_at >= $from");
+    }
+    if to.is_some() {
+        conditions.push("created_at <= $to");
+    }
+    let where_clause = match conditions.is_empty() {
+        true => String::new(),
+        false => format!("WHERE {}", conditions.join(" AND ")),
+    };
+
+    let user = get_user(&db, &auth_user).await?;
+
+    // Sort field and order come from enums, so they are safe to format into the query.
+    let mut response = db
+        .query(format!(
+            "SELECT * FROM $user->uploaded->{table} {where_clause}
+             ORDER BY {sort} {order} LIMIT $limit START $start;
+             SELECT count() AS total FROM $user->uploaded->{table} {where_clause} GROUP ALL;",
+            table = FASTQ_SAMPLE_TABLE,
+            where_clause = where_clause,
+            sort = query.sort.field(),
+            order = query.order.keyword(),
+        ))
+        .bind(("user", user))
+        .bind(json!({
+            "pipeline": query.pipeline,
+            "status": query.status,
+            "from": from,
+            "to": to,
+            "limit": query.per_page,
+            "start": query.start(),
+        }))
+        .await?;
+
+    let samples: Vec<SampleRecord> = response.take(0)?;
+    let count: Vec<CountRecord> = response.take(1)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(SampleListResponse {
+            samples: samples
+                .iter()
+                .map(|sample| sample_summary(&sample.id, &sample.data))
+                .collect(),
+            page: query.page,
+            per_page: query.per_page,
+            total: count.first().map(|count| count.total).unwrap_or(0),
+        }),
+    ))
+}
+
+/// Get one of the user's samples, with the result of its latest preprocessing
+/// run through the sample's processed edges. Samples of other users are
+/// reported as missing.
+pub async fn get_sample(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(sample_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+
+    let user = get_user(&db, &auth_user).await?;
+
+    let mut response = db
+        .query(format!(
+            "SELECT *, (SELECT * FROM $parent->processed->fastq_preprocess
+                ORDER BY created_at DESC LIMIT 1)[0] AS preprocess
+             FROM $user->uploaded->{table} WHERE id = type::thing($table, $id)",
+            table = FASTQ_SAMPLE_TABLE,
+        ))
+        .bind(("user", user))
+        .bind(json!({
+            "table": FASTQ_SAMPLE_TABLE,
+            "id": sample_id,
+        }))
+        .await?;
+
+    let samples: Vec<SampleRecord> = response.take(0)?;
+    let sample = samples
+        .into_iter()
+        .next()
+        .ok_or(ApiError::RecordNotFoundError(sample_id))?;
+
+    let config = &sample.data.config;
+    let detail = SampleDetail {
+        summary: sample_summary(&sample.id, &sample.data),
+        url: sample.data.url.clone(),
+        mate_url: sample.data.mate_url.clone(),
+        min_len: config.min_len,
+        max_len: config.max_len,
+        min_phred: config.min_phred,
+        adapter_trim: config.adapter_trim.clone(),
+        quality_trim: config.quality_trim.clone(),
+        preprocess: sample.preprocess.map(preprocess_summary).transpose()?,
+    };
+
+    Ok((StatusCode::OK, Json(detail)))
+}
+
+/// Cancel one of the user's samples, unless it is done already. A queued
+/// job is skipped by the fastq_service, and a running one is stopped.
+pub async fn cancel_sample(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(sample_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+    let nats = state.nats.client;
+
+    let user = get_user(&db, &auth_user).await?;
+    let user_id = user.to_string();
+
+    let sample = get_owned_sample(&db, user, &sample_id).await?;
+
+    if !sample.data.status.can_transition_to(Status::Cancelled) {
+        return Err(ApiError::InvalidStatusError(format!(
+            "Sample {} is {:?}",
+            sample_id, sample.data.status
+        )));
+    }
+
+    // The status might have changed since we read it, e.g., to done.
+    let mut response = db
+        .query(
+            "UPDATE $sample SET status = $status, error = NONE, updated_at = $updated_at
+             WHERE status IN $sources RETURN AFTER",
+        )
+        .bind(("sample", sample.id.surrealdb_id()?))
+        .bind(json!({
+            "status": Status::Cancelled,
+            "sources": Status::sources(Status::Cancelled),
+            "updated_at": time_now(),
+        }))
+        .await?;
+
+    let cancelled: Vec<SampleRecord> = response.take(0)?;
+    let cancelled = cancelled
+        .into_iter()
+        .next()
+        .ok_or(ApiError::InvalidStatusError(format!(
+            "Sample {} was finished meanwhile",
+            sample_id
+        )))?;
+
+    // Both are only notifications. The sample is cancelled either way, and
+    // a job that keeps running can't mark it as done anymore.
+    if let Err(e) =
+        publish_sample_status(&nats, &user_id, &sample_id, Status::Cancelled, None).await
+    {
+        warn!("Failed to publish status of {}: {:?}", sample_id, e);
+    }
+    if let Err(e) = publish_job_cancel(&nats, &user_id, &sample_id).await {
+        warn!("Failed to publish cancel of {}: {:?}", sample_id, e);
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(sample_summary(&cancelled.id, &cancelled.data)),
+    ))
+}
+
+/// Preprocess one of the user's samples again, with new thresholds, from
+/// the file that was already uploaded. The new run is added next to the
+/// earlier ones, see list_runs. Attempts start over, and retries of earlier
+/// runs that are still on the stream are skipped by the worker.
+pub async fn reprocess_sample(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(sample_id): Path<String>,
+    Json(config): Json<FastqSampleConfig>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+    let nats = state.nats.client;
+
+    config.validate().map_err(ApiError::InvalidUploadConfig)?;
+
+    let user = get_user(&db, &auth_user).await?;
+    let user_id = user.to_string();
+
+    let sample = get_owned_sample(&db, user, &sample_id).await?;
+
+    // Not while a run is queued or in progress, that run would end up with the new thresholds.
+    if !sample.data.status.can_transition_to(Status::Created) {
+        return Err(ApiError::InvalidStatusError(format!(
+            "Sample {} is {:?}",
+            sample_id, sample.data.status
+        )));
+    }
+
+    // Message for the fastq preprocessor, sent along with the update through the outbox.
+    let fastq_message = FastqMessage {
+        url: sample.data.url.clone(),
+        mate_url: sample.data.mate_url.clone(),
+        fastq_sample_id: sample.id.clone(),
+        config: config.clone(),
+    };
+    let outbox_entry = upload_outbox_entry(&user_id, fastq_message)?;
+
+    let mut response = db
+        .query(
+            "BEGIN TRANSACTION;
+             LET $queued = UPDATE $sample
+                SET status = $status, error = NONE, config = $config, run_id = $run_id,
+                    attempts = 0, updated_at = $updated_at
+                WHERE status IN $sources RETURN AFTER;
+             IF array::len($queued) > 0 {
+                CREATE type::table($outbox) CONTENT $outbox_entry;
+             };
+             RETURN $queued;
+             COMMIT TRANSACTION;",
+        )
+        .bind(("sample", sample.id.surrealdb_id()?))
+        .bind(json!({
+            "status": Status::Created,
+            "sources": Status::sources(Status::Created),
+            "config": config,
+            "run_id": outbox_entry.msg_id,
+            "updated_at": time_now(),
+            "outbox": OUTBOX_TABLE,
+            "outbox_entry": outbox_entry,
+        }))
+        .await?;
+
+    // The result of the transaction is the last statement.
+    let last = response.num_statements() - 1;
+    let queued: Vec<SampleRecord> = response.take(last)?;
+    let queued = queued
+        .into_iter()
+        .next()
+        .ok_or(ApiError::InvalidStatusError(format!(
+            "Sample {} was queued meanwhile",
+            sample_id
+        )))?;
+
+    if let Err(e) = publish_sample_status(&nats, &user_id, &sample_id, Status::Created, None).await
+    {
+        warn!("Failed to publish status of {}: {:?}", sample_id, e);
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(sample_summary(&queued.id, &queued.data)),
+    ))
+}
+
+/// Every preprocessing run of one of the user's samples, newest first,
+/// each with the thresholds it ran with.
+pub async fn list_runs(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(sample_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+
+    let user = get_user(&db, &auth_user).await?;
+    let sample = get_owned_sample(&db, user, &sample_id).await?;
+
+    let mut response = db
+        .query("SELECT * FROM $sample->processed->fastq_preprocess ORDER BY created_at DESC")
+        .bind(("sample", sample.id.surrealdb_id()?))
+        .await?;
+
+    let runs: Vec<FastqPreprocess> = response.take(0)?;
+    let runs = runs
+        .into_iter()
+        .map(preprocess_summary)
+        .collect::<Result<Vec<PreprocessSummary>, ApiError>>()?;
+
+    Ok((StatusCode::OK, Json(runs)))
+}
@@ -0,0 +1,291 @@
+use async_nats::jetstream::Context;
+use axum::{
+    Json,
+    extract::{Extension, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use log::{error, info};
+use serde_json::json;
+use shared::{
+    database::schemas::batch::{Batch, BatchData, BatchUpload},
+    schema::{
+        sample_sheet::{SampleSheetRow, parse_sample_sheet, validate_sample_sheet_files},
+        schema::Status,
+        upload::{CreateBatchRequest, CreateBatchResponse, UploadConfig},
+    },
+    utils::time::time_now,
+};
+use surrealdb::{Surreal, engine::remote::ws::Client, sql::Thing};
+
+use crate::auth::{auth::AuthUser, user::get_user};
+use crate::errors::ApiError;
+use crate::routes::upload::sample::{new_fastq_sample, register_fastq_sample};
+use crate::state::ConnectionState;
+
+pub const BATCH_TABLE: &str = "batches";
+
+/// Create a batch from a sample sheet. The sheet is validated against the
+/// files the client is about to upload, which it then does through upload
+/// sessions with the returned batch id.
+pub async fn create_batch(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Json(payload): Json<CreateBatchRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+
+    get_user(&db, &auth_user).await?;
+
+    payload
+        .config
+        .validate()
+        .map_err(ApiError::InvalidUploadConfig)?;
+
+    let rows = parse_sample_sheet(&payload.sample_sheet).map_err(ApiError::InvalidSampleSheet)?;
+
+    let errors = validate_sample_sheet_files(&rows, &payload.file_names);
+    if !errors.is_empty() {
+        return Err(ApiError::InvalidSampleSheet(errors));
+    }
+
+    // A row may override some thresholds, e.g., a min length above the max length of the batch.
+    for row in &rows {
+        row_config(&payload.config, row)
+            .validate()
+            .map_err(|e| ApiError::InvalidUploadConfig(format!("Sample {}: {}", row.sample, e)))?;
+    }
+
+    let num_samples = rows.len();
+    let batch = Batch {
+        id: None,
+        data: BatchData {
+            user_id: auth_user.id,
+            name: payload.name,
+            pipeline: payload.pipeline,
+            config: payload.config,
+            rows,
+            uploads: Vec::new(),
+            status: Status::Created,
+            created_at: time_now(),
+            updated_at: time_now(),
+        },
+    };
+
+    let batch_response: Batch =
+        db.create(BATCH_TABLE)
+            .content(batch)
+            .await?
+            .ok_or(ApiError::DatabaseRecordInsertError(
+                "Failed to insert into batches table.".into(),
+            ))?;
+
+    info!(
+        "Created batch {:?} with {} samples",
+        batch_response.id, num_samples
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateBatchResponse {
+            batch_id: batch_response.id.unwrap().key().to_string(),
+            num_samples,
+        }),
+    ))
+}
+
+/// Make sure that the batch is the user's and that the file
+/// belongs to it and has not been uploaded yet.
+pub async fn check_batch_file(
+    db: &Surreal<Client>,
+    auth_user: &AuthUser,
+    batch_id: &str,
+    file_name: &str,
+) -> Result<(), ApiError> {
+    let batch: Batch = db
+        .select((BATCH_TABLE, batch_id))
+        .await?
+        .filter(|batch: &Batch| batch.data.user_id == auth_user.id)
+        .ok_or(ApiError::RecordNotFoundError(batch_id.to_string()))?;
+
+    if !matches!(batch.data.status, Status::Created) {
+        return Err(ApiError::UploadSessionError(format!(
+            "Batch {} is already {:?}",
+            batch_id, batch.data.status
+        )));
+    }
+
+    if !batch
+        .data
+        .rows
+        .iter()
+        .any(|row| row.files().contains(&file_name))
+    {
+        return Err(ApiError::UploadSessionError(format!(
+            "File {} is not in batch {}",
+            file_name, batch_id
+        )));
+    }
+
+    if batch.data.url_of(file_name).is_some() {
+        return Err(ApiError::UploadSessionError(format!(
+            "File {} is already uploaded to batch {}",
+            file_name, batch_id
+        )));
+    }
+
+    Ok(())
+}
+
+/// A file of the batch is in MinIO. Register the sample of its row if this
+/// was the last missing file for it, e.g., the second mate of a pair.
+/// Returns false, and adds nothing, if another upload of the same file,
+/// e.g., from a second tab, got there first. Adding the same upload again,
+/// when finishing its session is retried, does nothing and returns true.
+pub async fn add_batch_upload(
+    db: &Surreal<Client>,
+    nats: Context,
+    user: Thing,
+    batch_id: &str,
+    file_name: String,
+    url: String,
+) -> Result<bool, ApiError> {
+    let upload = BatchUpload {
+        file_name: file_name.clone(),
+        url: url.clone(),
+    };
+
+    // Appending and reading back in a single statement means that, when both
+    // mates finish at the same time, only one of them sees the row complete.
+    // The same goes for two uploads of the same file, only one is added.
+    let mut response = db
+        .query(
+            "UPDATE type::thing($table, $id) SET uploads += $upload,
+             updated_at = $updated_at
+             WHERE $upload.file_name NOTINSIDE uploads.file_name RETURN AFTER",
+        )
+        .bind(json!({
+            "table": BATCH_TABLE,
+            "id": batch_id,
+            "upload": upload,
+            "updated_at": time_now(),
+        }))
+        .await?;
+
+    let batch: Vec<Batch> = response.take(0)?;
+    let batch = match batch.into_iter().next() {
+        Some(batch) => {
+            if let Err(e) = register_completed_rows(db, nats, user, &batch, &file_name).await {
+                // Without the upload, the row is completed again on a retry.
+                if let Err(remove_error) = remove_batch_upload(db, batch_id, &upload).await {
+                    error!(
+                        "Failed to remove {} from batch {}: {:?}",
+                        file_name, batch_id, remove_error
+                    );
+                }
+                return Err(e);
+            }
+            batch
+        }
+        None => {
+            let batch: Option<Batch> = db.select((BATCH_TABLE, batch_id)).await?;
+            match batch {
+                Some(batch) if batch.data.url_of(&file_name) == Some(url.as_str()) => batch,
+                _ => return Ok(false),
+            }
+        }
+    };
+
+    let num_files: usize = batch.data.rows.iter().map(|row| row.files().len()).sum();
+    if batch.data.uploads.len() >= num_files {
+        info!("All {} files of batch {} are uploaded", num_files, batch_id);
+
+        let _: Option<Batch> = db
+            .update((BATCH_TABLE, batch_id))
+            .merge(json!({"status": Status::Done, "updated_at": time_now()}))
+            .await?;
+    }
+
+    Ok(true)
+}
+
+/// Register the samples of the rows that the file was the last missing one of.
+async fn register_completed_rows(
+    db: &Surreal<Client>,
+    nats: Context,
+    user: Thing,
+    batch: &Batch,
+    file_name: &str,
+) -> Result<(), ApiError> {
+    let completed_rows = batch.data.rows.iter().filter(|row| {
+        row.files().contains(&file_name)
+            && row
+                .files()
+                .iter()
+                .all(|file| batch.data.url_of(file).is_some())
+    });
+
+    for row in completed_rows {
+        register_batch_sample(db, nats.clone(), user.clone(), batch, row).await?;
+    }
+
+    Ok(())
+}
+
+async fn remove_batch_upload(
+    db: &Surreal<Client>,
+    batch_id: &str,
+    upload: &BatchUpload,
+) -> Result<(), ApiError> {
+    db.query("UPDATE type::thing($table, $id) SET uploads -= $upload, updated_at = $updated_at")
+        .bind(json!({
+            "table": BATCH_TABLE,
+            "id": batch_id,
+            "upload": upload,
+            "updated_at": time_now(),
+        }))
+        .await?
+        .check()?;
+
+    Ok(())
+}
+
+/// Thresholds in the sheet take precedence over the ones for the whole batch.
+fn row_config(config: &UploadConfig, row: &SampleSheetRow) -> UploadConfig {
+    UploadConfig {
+        min_len: row.min_len.or(config.min_len),
+        max_len: row.max_len.or(config.max_len),
+        min_phred: row.min_phred.or(config.min_phred),
+        ..config.clone()
+    }
+}
+
+/// Create the fastq sample for a row of the sheet, using the row's
+/// thresholds and metadata, and relate it to the batch.
+async fn register_batch_sample(
+    db: &Surreal<Client>,
+    nats: Context,
+    user: Thing,
+    batch: &Batch,
+    row: &SampleSheetRow,
+) -> Result<(), ApiError> {
+    let mut fastq_sample = new_fastq_sample(
+        row.sample.clone(),
+        batch
+            .data
+            .url_of(&row.fastq_1)
+            .unwrap_or_default()
+            .to_string(),
+        row.fastq_2
+            .as_ref()
+            .and_then(|fastq_2| batch.data.url_of(fastq_2))
+            .map(str::to_string),
+        row.pipeline.unwrap_or(batch.data.pipeline),
+    );
+    fastq_sample.apply_upload_config(&row_config(&batch.data.config, row));
+    fastq_sample.metadata.extend(row.metadata.clone());
+
+    register_fastq_sample(db, nats, user, batch.id.as_ref(), None, fastq_sample).await?;
+
+    Ok(())
+}
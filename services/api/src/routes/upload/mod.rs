@@ -1,18 +1,57 @@
+mod batch;
+mod resumable;
+mod sample;
+mod session;
 mod upload;
 use std::time::Duration;
 
-use axum::{Router, extract::DefaultBodyLimit, routing::post};
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{head, post},
+};
+pub use batch::create_batch;
+pub use resumable::{create_resumable_upload, resumable_upload_chunk, resumable_upload_offset};
+pub use session::{complete_upload_session, create_upload_session};
+use shared::minio::multipart::part_size_for;
 use tower_http::timeout::RequestBodyTimeoutLayer;
 pub use upload::upload_file;
 
+use crate::auth::middleware::auth_middleware;
 use crate::state::ConnectionState;
 
+/// Files are streamed straight into MinIO, so the size of an upload
+/// doesn't affect API memory. Allow sequencing runs up to 50Gb.
+pub const MAX_UPLOAD_SIZE: u64 = 50 * 1024 * 1024 * 1024;
+
 pub fn routes() -> Router<ConnectionState> {
     let router = Router::new()
-        .route("/upload", post(upload_file))
-        // For the moment, restrict .fastq.gz uploads to 250Mb
-        // and the request timeout to 5 minutes per file.
-        .layer(DefaultBodyLimit::max(250 * 1024 * 1024))
+        .route(
+            "/upload",
+            post(upload_file).layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE as usize)),
+        )
+        // Sample sheet batches, whose files are uploaded through sessions.
+        .route("/batches", post(create_batch))
+        // Presigned uploads, where the client uploads directly to MinIO.
+        .route("/uploads", post(create_upload_session))
+        .route(
+            "/uploads/{session_id}/complete",
+            post(complete_upload_session),
+        )
+        // Resumable uploads, where the client uploads chunks through the API.
+        .route("/resumable", post(create_resumable_upload))
+        .route(
+            "/resumable/{session_id}",
+            head(resumable_upload_offset)
+                .patch(resumable_upload_chunk)
+                // A chunk is a single part, and is held in memory.
+                .layer(DefaultBodyLimit::max(part_size_for(MAX_UPLOAD_SIZE))),
+        )
+        // Every upload belongs to the user in the bearer token.
+        .route_layer(middleware::from_fn(auth_middleware))
+        // Everything else is JSON, which keeps the default body limit.
+        // Time out if the body stalls for more than 5 minutes.
         .layer(RequestBodyTimeoutLayer::new(Duration::from_secs(60 * 5)));
 
     router
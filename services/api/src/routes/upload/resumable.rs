@@ -0,0 +1,297 @@
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Extension, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use log::{error, info};
+use minio::s3::types::PartInfo;
+use serde_json::json;
+use shared::{
+    database::schemas::upload_session::{UploadSession, UploadSessionData, UploadedPart},
+    fastq::{FastqValidator, ValidationMode, validate::DEFAULT_PREFIX_RECORDS},
+    minio::multipart::{
+        abort_multipart_upload, create_multipart_upload, part_size_for, upload_part,
+    },
+    schema::{
+        schema::Status,
+        upload::{
+            ResumableUploadResponse, UPLOAD_CHUNK_SIZE_HEADER, UPLOAD_LENGTH_HEADER,
+            UPLOAD_OFFSET_HEADER, UploadSessionRequest,
+        },
+    },
+    utils::time::time_now,
+};
+
+use crate::auth::{auth::AuthUser, user::get_user};
+use crate::errors::ApiError;
+use crate::minio_upload::{UPLOAD_BUCKET, unique_key};
+use crate::routes::upload::batch::check_batch_file;
+use crate::routes::upload::session::{
+    UPLOAD_SESSION_TABLE, check_upload_size, finish_upload_session, get_open_upload_session,
+    get_upload_session, set_session_status,
+};
+use crate::state::ConnectionState;
+
+/// Start a resumable upload. Unlike the presigned flow, chunks go through
+/// the API, which lets us keep track of exactly how much has been uploaded
+/// so the client can continue after a dropped connection or page reload.
+pub async fn create_resumable_upload(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Json(payload): Json<UploadSessionRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+    let minio = state.minio.client;
+
+    get_user(&db, &auth_user).await?;
+    check_upload_size(payload.size)?;
+
+    payload
+        .config
+        .validate()
+        .map_err(ApiError::InvalidUploadConfig)?;
+
+    if let Some(batch_id) = &payload.batch_id {
+        check_batch_file(&db, &auth_user, batch_id, &payload.file_name).await?;
+    }
+
+    let bucket = UPLOAD_BUCKET;
+    let (_, key) = unique_key(&payload.file_name);
+
+    let part_size = part_size_for(payload.size);
+    let num_parts = payload.size.div_ceil(part_size as u64) as usize;
+
+    let upload_id = create_multipart_upload(&minio, bucket, &key).await?;
+
+    let upload_session = UploadSession {
+        id: None,
+        data: UploadSessionData {
+            user_id: auth_user.id,
+            file_name: payload.file_name,
+            bucket: bucket.into(),
+            key,
+            upload_id,
+            size: payload.size,
+            part_size,
+            num_parts,
+            pipeline: payload.pipeline,
+            offset: 0,
+            parts: Vec::new(),
+            batch_id: payload.batch_id,
+            config: payload.config,
+            status: Status::Created,
+            created_at: time_now(),
+            updated_at: time_now(),
+        },
+    };
+
+    let session_response: UploadSession = db
+        .create(UPLOAD_SESSION_TABLE)
+        .content(upload_session)
+        .await?
+        .ok_or(ApiError::DatabaseRecordInsertError(
+            "Failed to insert into upload_sessions table.".into(),
+        ))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ResumableUploadResponse {
+            session_id: session_response.id.unwrap().key().to_string(),
+            chunk_size: part_size,
+            offset: 0,
+        }),
+    ))
+}
+
+/// Where the client should continue from. All chunks can be in without the
+/// upload being done, e.g., while it is being finished, or when validating it
+/// failed on a connection error. The last chunk is then reported as missing,
+/// and sending it again only finishes the upload. That way, offset == length
+/// always means that the upload is done.
+fn client_offset(session: &UploadSessionData) -> u64 {
+    match session.offset == session.size
+        && matches!(session.status, Status::Created | Status::Pending)
+    {
+        true => (session.num_parts as u64 - 1) * session.part_size as u64,
+        false => session.offset,
+    }
+}
+
+/// Current offset of an upload, i.e., where the client should continue from.
+/// A finished upload has offset == length, which lets a client that lost
+/// the response to its last chunk know that it is done.
+pub async fn resumable_upload_offset(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+
+    let session = get_upload_session(&db, &auth_user, &session_id).await?;
+
+    if matches!(session.status, Status::Error) {
+        return Err(ApiError::UploadSessionError(format!(
+            "Session {} failed",
+            session_id
+        )));
+    }
+
+    Ok((
+        StatusCode::OK,
+        [
+            (UPLOAD_OFFSET_HEADER, client_offset(&session).to_string()),
+            (UPLOAD_LENGTH_HEADER, session.size.to_string()),
+            (UPLOAD_CHUNK_SIZE_HEADER, session.part_size.to_string()),
+        ],
+    ))
+}
+
+/// Upload the chunk that starts at Upload-Offset. Every chunk is uploaded
+/// as its own MinIO part, so chunks must be exactly chunk_size bytes
+/// (except for the last one). Once the last chunk is in, we complete
+/// the upload and create the fastq sample.
+pub async fn resumable_upload_chunk(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(session_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+    let minio = state.minio.client;
+    let nats = state.nats.client;
+
+    let offset: u64 = headers
+        .get(UPLOAD_OFFSET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .ok_or(ApiError::UploadSessionError(format!(
+            "Missing or invalid {} header",
+            UPLOAD_OFFSET_HEADER
+        )))?;
+
+    let session = get_open_upload_session(&db, &auth_user, &session_id).await?;
+
+    let expected_offset = client_offset(&session);
+    if offset != expected_offset {
+        return Err(ApiError::UploadSessionError(format!(
+            "Expected offset {}, got {}",
+            expected_offset, offset
+        )));
+    }
+
+    let expected_len = (session.size - offset).min(session.part_size as u64);
+    if body.len() as u64 != expected_len {
+        return Err(ApiError::UploadSessionError(format!(
+            "Expected chunk of {} bytes, got {}",
+            expected_len,
+            body.len()
+        )));
+    }
+
+    // Reject files that are obviously not FASTQ before the client uploads
+    // the rest. The whole object is validated again once it is complete.
+    if offset == 0 {
+        let mut validator = FastqValidator::new(ValidationMode::Prefix(DEFAULT_PREFIX_RECORDS));
+        let is_last_chunk = expected_len == session.size;
+
+        if let Err(e) = validator
+            .push(&body)
+            .and_then(|_| validator.finish(is_last_chunk))
+        {
+            error!("Upload session {} is not valid FASTQ: {}", session_id, e);
+            abort_multipart_upload(&minio, &session.bucket, &session.key, &session.upload_id)
+                .await?;
+            set_session_status(&db, &session_id, Status::Error).await?;
+
+            return Err(e.into());
+        }
+    }
+
+    // The last chunk is already in, but finishing the upload failed before.
+    if session.offset == session.size {
+        info!("Upload session {} retries finishing", session_id);
+        let session_size = session.size;
+        let parts = session_parts(&session);
+        finish_upload_session(&db, &minio, nats, &session_id, session, parts).await?;
+
+        return Ok((
+            StatusCode::NO_CONTENT,
+            [(UPLOAD_OFFSET_HEADER, session_size.to_string())],
+        ));
+    }
+
+    let part_number = (offset / session.part_size as u64 + 1) as u16;
+    let part_info = upload_part(
+        &minio,
+        &session.bucket,
+        &session.key,
+        &session.upload_id,
+        part_number,
+        body,
+    )
+    .await?;
+
+    let new_offset = offset + part_info.size;
+    let uploaded_part = UploadedPart {
+        number: part_info.number,
+        etag: part_info.etag,
+        size: part_info.size,
+    };
+
+    // Only move the offset forward if nobody else did it before us. Otherwise,
+    // two concurrent requests for the same chunk could both append a part.
+    let mut response = db
+        .query(
+            "UPDATE type::thing($table, $id) SET offset = $new_offset, parts += $part,
+             updated_at = $updated_at WHERE offset = $offset",
+        )
+        .bind(json!({
+            "table": UPLOAD_SESSION_TABLE,
+            "id": session_id,
+            "new_offset": new_offset,
+            "part": uploaded_part,
+            "updated_at": time_now(),
+            "offset": offset,
+        }))
+        .await?;
+
+    let updated: Vec<UploadSession> = response.take(0)?;
+    let updated = updated
+        .into_iter()
+        .next()
+        .ok_or(ApiError::UploadSessionError(format!(
+            "Chunk at offset {} was uploaded concurrently",
+            offset
+        )))?;
+
+    info!(
+        "Upload session {} at {}/{} bytes",
+        session_id, new_offset, session.size
+    );
+
+    if new_offset == session.size {
+        let parts = session_parts(&updated.data);
+        finish_upload_session(&db, &minio, nats, &session_id, updated.data, parts).await?;
+    }
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(UPLOAD_OFFSET_HEADER, new_offset.to_string())],
+    ))
+}
+
+/// The parts of the session, for completing the multipart upload.
+fn session_parts(session: &UploadSessionData) -> Vec<PartInfo> {
+    session
+        .parts
+        .iter()
+        .map(|part| PartInfo {
+            number: part.number,
+            etag: part.etag.clone(),
+            size: part.size,
+        })
+        .collect()
+}
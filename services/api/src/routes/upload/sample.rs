@@ -0,0 +1,133 @@
+use async_nats::jetstream::Context;
+use log::{info, warn};
+use serde_json::json;
+use shared::{
+    database::schemas::common::SimpleRecordId,
+    database::schemas::fastq_sample::{FastqSample, FastqSampleConfig, FastqSampleData},
+    nats::{schema::fastq_service::FastqMessage, status::publish_sample_status},
+    schema::schema::{Pipeline, Status},
+    utils::time::time_now,
+};
+use std::collections::BTreeMap;
+use surrealdb::{Surreal, engine::remote::ws::Client, sql::Thing};
+
+use crate::errors::ApiError;
+use crate::nats::publisher::file_upload::upload_outbox_entry;
+use crate::nats::publisher::outbox::OUTBOX_TABLE;
+use crate::routes::samples::FASTQ_SAMPLE_TABLE;
+use crate::routes::upload::session::UPLOAD_SESSION_TABLE;
+
+/// Sample data with the default config and no metadata.
+pub fn new_fastq_sample(
+    name: String,
+    url: String,
+    mate_url: Option<String>,
+    pipeline: Pipeline,
+) -> FastqSampleData {
+    FastqSampleData {
+        name,
+        status: Status::Created,
+        error: None,
+        attempts: 0,
+        run_id: None,
+        url,
+        mate_url,
+        pipeline,
+        config: FastqSampleConfig::mock(),
+        metadata: BTreeMap::new(),
+        created_at: time_now(),
+        updated_at: time_now(),
+    }
+}
+
+/// Once a file (or both mates of a pair) is in MinIO, create the fastq sample
+/// record, relate it to the uploading user (and its batch, if any) and send it
+/// off to the fastq preprocessor.
+///
+/// The message to the fastq preprocessor goes through the outbox, in the same
+/// transaction as the sample, so that there is never a sample that nothing
+/// processes, nor a message about a sample that does not exist.
+/// The upload session of the file, if any, is set to done in that
+/// transaction as well, so that a done session always has its sample.
+pub async fn register_fastq_sample(
+    db: &Surreal<Client>,
+    nats: Context,
+    user: Thing,
+    batch: Option<&SimpleRecordId>,
+    upload_session: Option<&str>,
+    mut sample: FastqSampleData,
+) -> Result<FastqSample, ApiError> {
+    let user_id = user.to_string();
+
+    // We pick the id ourselves, since the message needs it before the sample exists.
+    let key = uuid::Uuid::now_v7().simple().to_string();
+    let sample_id = SimpleRecordId::new(FASTQ_SAMPLE_TABLE, &key);
+
+    // Message for the fastq preprocessor.
+    let fastq_message = FastqMessage {
+        url: sample.url.clone(),
+        mate_url: sample.mate_url.clone(),
+        fastq_sample_id: sample_id.clone(),
+        config: sample.config.clone(),
+    };
+    let outbox_entry = upload_outbox_entry(&user_id, fastq_message)?;
+    sample.run_id = Some(outbox_entry.msg_id.clone());
+
+    let mut response = db
+        .query(
+            "BEGIN TRANSACTION;
+             LET $sample = CREATE ONLY $fastq_sample CONTENT $fastq_sample_data;
+             RELATE $user->uploaded->$fastq_sample;
+             IF $batch {
+                RELATE $batch->contains->$fastq_sample;
+             };
+             CREATE type::table($outbox) CONTENT $outbox_entry;
+             IF $upload_session {
+                UPDATE $upload_session SET status = $done, updated_at = $updated_at;
+             };
+             RETURN $sample;
+             COMMIT TRANSACTION;",
+        )
+        .bind(("fastq_sample", sample_id.surrealdb_id()?))
+        .bind(("user", user))
+        .bind((
+            "batch",
+            batch.map(SimpleRecordId::surrealdb_id).transpose()?,
+        ))
+        .bind((
+            "upload_session",
+            upload_session.map(|id| Thing::from((UPLOAD_SESSION_TABLE, id))),
+        ))
+        .bind(json!({
+            "fastq_sample_data": sample,
+            "outbox": OUTBOX_TABLE,
+            "outbox_entry": outbox_entry,
+            "done": Status::Done,
+            "updated_at": time_now(),
+        }))
+        .await?;
+
+    // The result of the transaction is the last statement.
+    let last = response.num_statements() - 1;
+    let sample_response: FastqSample =
+        response
+            .take::<Option<FastqSample>>(last)?
+            .ok_or(ApiError::DatabaseRecordInsertError(format!(
+                "Failed to create {}",
+                sample_id.formatted_id()
+            )))?;
+    info!("Registered {}", sample_id.formatted_id());
+
+    // Only a notification, the sample is there either way.
+    if let Err(e) =
+        publish_sample_status(&nats, &user_id, sample_id.key(), Status::Created, None).await
+    {
+        warn!(
+            "Failed to publish status of {}: {:?}",
+            sample_id.formatted_id(),
+            e
+        );
+    }
+
+    Ok(sample_response)
+}
@@ -0,0 +1,405 @@
+use async_nats::jetstream::Context;
+use axum::{
+    Json,
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use log::{error, info};
+use minio::s3::{Client as MinioClient, types::PartInfo};
+use serde_json::json;
+use shared::{
+    database::schemas::upload_session::{UploadSession, UploadSessionData},
+    fastq::ValidationMode,
+    minio::{
+        minio_delete_object,
+        multipart::{
+            complete_multipart_upload, create_multipart_upload, object_size, part_size_for,
+        },
+        upload::object_url,
+    },
+    schema::{
+        schema::Status,
+        upload::{
+            CompleteUploadRequest, PresignedPart, UploadSessionRequest, UploadSessionResponse,
+        },
+    },
+    utils::time::time_now,
+};
+use surrealdb::{Surreal, engine::remote::ws::Client};
+
+use crate::auth::{
+    auth::AuthUser,
+    user::{get_user, parse_user_id},
+};
+use crate::errors::ApiError;
+use crate::minio_upload::{UPLOAD_BUCKET, unique_key, validate_fastq_object};
+use crate::routes::upload::MAX_UPLOAD_SIZE;
+use crate::routes::upload::batch::{add_batch_upload, check_batch_file};
+use crate::routes::upload::sample::{new_fastq_sample, register_fastq_sample};
+use crate::state::ConnectionState;
+
+pub const UPLOAD_SESSION_TABLE: &str = "upload_sessions";
+
+/// Start a direct-to-MinIO upload. We create the multipart upload
+/// and hand out one presigned PUT url per part, so that the file
+/// itself never passes through the API.
+pub async fn create_upload_session(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Json(payload): Json<UploadSessionRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+    let minio = state.minio.client;
+    let presigner = state.minio.presigner;
+
+    get_user(&db, &auth_user).await?;
+    check_upload_size(payload.size)?;
+
+    payload
+        .config
+        .validate()
+        .map_err(ApiError::InvalidUploadConfig)?;
+
+    if let Some(batch_id) = &payload.batch_id {
+        check_batch_file(&db, &auth_user, batch_id, &payload.file_name).await?;
+    }
+
+    let bucket = UPLOAD_BUCKET;
+    let (_, key) = unique_key(&payload.file_name);
+
+    let part_size = part_size_for(payload.size);
+    let num_parts = payload.size.div_ceil(part_size as u64) as usize;
+
+    let upload_id = create_multipart_upload(&minio, bucket, &key).await?;
+
+    let mut parts: Vec<PresignedPart> = Vec::with_capacity(num_parts);
+    for part_number in 1..=num_parts as u16 {
+        let url = presigner.presigned_part_url(bucket, &key, &upload_id, part_number)?;
+        parts.push(PresignedPart { part_number, url });
+    }
+
+    let upload_session = UploadSession {
+        id: None,
+        data: UploadSessionData {
+            user_id: auth_user.id,
+            file_name: payload.file_name,
+            bucket: bucket.into(),
+            key,
+            upload_id,
+            size: payload.size,
+            part_size,
+            num_parts,
+            pipeline: payload.pipeline,
+            offset: 0,
+            parts: Vec::new(),
+            batch_id: payload.batch_id,
+            config: payload.config,
+            status: Status::Created,
+            created_at: time_now(),
+            updated_at: time_now(),
+        },
+    };
+
+    let session_response: UploadSession = db
+        .create(UPLOAD_SESSION_TABLE)
+        .content(upload_session)
+        .await?
+        .ok_or(ApiError::DatabaseRecordInsertError(
+            "Failed to insert into upload_sessions table.".into(),
+        ))?;
+
+    info!(
+        "Created upload session {:?} with {} parts",
+        session_response.id, num_parts
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(UploadSessionResponse {
+            session_id: session_response.id.unwrap().key().to_string(),
+            part_size,
+            parts,
+        }),
+    ))
+}
+
+/// The client has uploaded all parts. Complete the multipart upload,
+/// verify the object and hand it over to the fastq preprocessor.
+pub async fn complete_upload_session(
+    State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(session_id): Path<String>,
+    Json(payload): Json<CompleteUploadRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let db = state.surrealdb.client;
+    let minio = state.minio.client;
+    let nats = state.nats.client;
+
+    let session = get_open_upload_session(&db, &auth_user, &session_id).await?;
+
+    if payload.parts.len() != session.num_parts {
+        return Err(ApiError::UploadSessionError(format!(
+            "Expected {} parts, got {}",
+            session.num_parts,
+            payload.parts.len()
+        )));
+    }
+
+    if payload
+        .parts
+        .iter()
+        .any(|part| part.part_number == 0 || part.part_number as usize > session.num_parts)
+    {
+        return Err(ApiError::UploadSessionError(
+            "Part number out of range".into(),
+        ));
+    }
+
+    // Together with the checks above, this means that every part is there exactly once.
+    let mut part_numbers: Vec<u16> = payload.parts.iter().map(|part| part.part_number).collect();
+    part_numbers.sort_unstable();
+    part_numbers.dedup();
+    if part_numbers.len() != payload.parts.len() {
+        return Err(ApiError::UploadSessionError(
+            "Duplicate part numbers".into(),
+        ));
+    }
+
+    // All parts except the last one have the same size.
+    let mut parts: Vec<PartInfo> = payload
+        .parts
+        .into_iter()
+        .map(|part| {
+            let offset = (part.part_number as u64 - 1) * session.part_size as u64;
+            PartInfo {
+                number: part.part_number,
+                etag: part.etag,
+                size: (session.size - offset).min(session.part_size as u64),
+            }
+        })
+        .collect();
+    parts.sort_by_key(|part| part.number);
+
+    finish_upload_session(&db, &minio, nats, &session_id, session, parts).await?;
+
+    Ok((StatusCode::OK, Json(json!({"upload": "success"}))))
+}
+
+/// Sessions are split into parts of part_size_for(size), so bounding the
+/// size also bounds the parts, which resumable uploads hold in memory.
+pub fn check_upload_size(size: u64) -> Result<(), ApiError> {
+    if size == 0 {
+        return Err(ApiError::UploadSessionError("File is empty".into()));
+    }
+    if size > MAX_UPLOAD_SIZE {
+        return Err(ApiError::UploadSessionError(format!(
+            "File is larger than the maximum of {} bytes",
+            MAX_UPLOAD_SIZE
+        )));
+    }
+
+    Ok(())
+}
+
+/// Get an upload session of the user. Sessions of other users are
+/// reported as missing, so that their ids can not be probed.
+pub async fn get_upload_session(
+    db: &Surreal<Client>,
+    auth_user: &AuthUser,
+    session_id: &str,
+) -> Result<UploadSessionData, ApiError> {
+    let session: UploadSession = db
+        .select((UPLOAD_SESSION_TABLE, session_id))
+        .await?
+        .filter(|session: &UploadSession| session.data.user_id == auth_user.id)
+        .ok_or(ApiError::RecordNotFoundError(session_id.to_string()))?;
+
+    Ok(session.data)
+}
+
+/// Get an upload session that parts can still be uploaded to.
+pub async fn get_open_upload_session(
+    db: &Surreal<Client>,
+    auth_user: &AuthUser,
+    session_id: &str,
+) -> Result<UploadSessionData, ApiError> {
+    let session = get_upload_session(db, auth_user, session_id).await?;
+
+    if !matches!(session.status, Status::Created) {
+        return Err(ApiError::UploadSessionError(format!(
+            "Session {} is already {:?}",
+            session_id, session.status
+        )));
+    }
+
+    Ok(session)
+}
+
+/// All parts are uploaded. Complete the multipart upload, verify the
+/// object (size and FASTQ content) and hand it over to the fastq preprocessor.
+///
+/// The session is pending while we do so, which keeps concurrent requests,
+/// e.g., a complete that the client retried, from registering it twice.
+/// If finishing fails on something other than the upload itself, the session
+/// goes back to created, so that the client can retry.
+pub async fn finish_upload_session(
+    db: &Surreal<Client>,
+    minio: &MinioClient,
+    nats: Context,
+    session_id: &str,
+    session: UploadSessionData,
+    parts: Vec<PartInfo>,
+) -> Result<(), ApiError> {
+    if !update_session_status(db, session_id, Status::Created, Status::Pending).await? {
+        return Err(ApiError::UploadSessionError(format!(
+            "Session {} is already being finished",
+            session_id
+        )));
+    }
+
+    let result = complete_upload(db, minio, nats, session_id, session, parts).await;
+    if result.is_err() {
+        // Rejected uploads are already set to error, which this leaves alone.
+        if let Err(e) =
+            update_session_status(db, session_id, Status::Pending, Status::Created).await
+        {
+            error!("Failed to release upload session {}: {:?}", session_id, e);
+        }
+    }
+
+    result
+}
+
+async fn complete_upload(
+    db: &Surreal<Client>,
+    minio: &MinioClient,
+    nats: Context,
+    session_id: &str,
+    session: UploadSessionData,
+    parts: Vec<PartInfo>,
+) -> Result<(), ApiError> {
+    // Completing might have gone through on an earlier try, after which
+    // something else failed, see resumable_upload_chunk.
+    let url = match object_size(minio, &session.bucket, &session.key).await {
+        Ok(_) => object_url(&session.bucket, &session.key)?,
+        Err(_) => {
+            complete_multipart_upload(
+                minio,
+                &session.bucket,
+                &session.key,
+                &session.upload_id,
+                parts,
+            )
+            .await?
+        }
+    };
+
+    // Make sure that what ended up in MinIO is what the client said it would upload.
+    let uploaded_size = object_size(minio, &session.bucket, &session.key).await?;
+    if uploaded_size != session.size {
+        error!(
+            "Upload session {} expected {} bytes, got {}",
+            session_id, session.size, uploaded_size
+        );
+        set_session_status(db, session_id, Status::Error).await?;
+        minio_delete_object(minio, &session.bucket, &session.key).await?;
+
+        return Err(ApiError::UploadSessionError(format!(
+            "Expected {} bytes, got {}",
+            session.size, uploaded_size
+        )));
+    }
+
+    // Parts were uploaded without looking at them, so this is the first
+    // chance to make sure that the file is actually FASTQ. Anything else
+    // than invalid content, e.g., a dropped connection to MinIO, keeps the
+    // object around for a retry.
+    match validate_fastq_object(
+        minio,
+        &session.bucket,
+        &session.key,
+        ValidationMode::from_env(),
+    )
+    .await
+    {
+        Err(e @ ApiError::InvalidFastq(_)) => {
+            error!("Upload session {} is not valid FASTQ: {:?}", session_id, e);
+            set_session_status(db, session_id, Status::Error).await?;
+            minio_delete_object(minio, &session.bucket, &session.key).await?;
+
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+        Ok(_) => {}
+    }
+
+    let user = parse_user_id(&session.user_id)?;
+
+    // The session is only done once its file is registered, so that
+    // a retry after a failure here registers it again.
+    match session.batch_id {
+        // Files of a batch become samples once all files of their row are in.
+        Some(batch_id) => {
+            let added =
+                add_batch_upload(db, nats, user, &batch_id, session.file_name.clone(), url).await?;
+            if !added {
+                set_session_status(db, session_id, Status::Error).await?;
+                minio_delete_object(minio, &session.bucket, &session.key).await?;
+
+                return Err(ApiError::UploadSessionError(format!(
+                    "File {} is already uploaded to batch {}",
+                    session.file_name, batch_id
+                )));
+            }
+            set_session_status(db, session_id, Status::Done).await?;
+        }
+        None => {
+            let mut fastq_sample = new_fastq_sample(session.file_name, url, None, session.pipeline);
+            fastq_sample.apply_upload_config(&session.config);
+            register_fastq_sample(db, nats, user, None, Some(session_id), fastq_sample).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn set_session_status(
+    db: &Surreal<Client>,
+    session_id: &str,
+    status: Status,
+) -> Result<(), ApiError> {
+    let _: Option<UploadSession> = db
+        .update((UPLOAD_SESSION_TABLE, session_id))
+        .merge(json!({"status": status, "updated_at": time_now()}))
+        .await?;
+
+    Ok(())
+}
+
+/// Move the session from one status to another, if nobody else did so
+/// before us. Returns whether it was moved.
+async fn update_session_status(
+    db: &Surreal<Client>,
+    session_id: &str,
+    from: Status,
+    to: Status,
+) -> Result<bool, ApiError> {
+    let mut response = db
+        .query(
+            "UPDATE type::thing($table, $id) SET status = $to, updated_at = $updated_at
+             WHERE status = $from RETURN AFTER",
+        )
+        .bind(json!({
+            "table": UPLOAD_SESSION_TABLE,
+            "id": session_id,
+            "from": from,
+            "to": to,
+            "updated_at": time_now(),
+        }))
+        .await?;
+
+    let updated: Vec<UploadSession> = response.take(0)?;
+
+    Ok(!updated.is_empty())
+}
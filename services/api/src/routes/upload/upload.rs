@@ -1,116 +1,118 @@
 use axum::{
     Json,
-    extract::{Multipart, State},
+    extract::{Extension, Multipart, State},
     http::StatusCode,
     response::IntoResponse,
 };
-use bytes::Bytes;
 use log::info;
 use serde_json::json;
-use shared::{
-    database::schemas::{
-        User,
-        fastq_sample::{FastqSample, FastqSampleConfig, FastqSampleData},
-    },
-    nats::schema::fastq_service::FastqMessage,
-    schema::schema::{Pipeline, Status},
-    utils::time::time_now,
-};
+use shared::schema::schema::Pipeline;
+use shared::schema::upload::UploadConfig;
+use shared::utils::pairing::pair_files;
 
+use crate::auth::{auth::AuthUser, user::get_user};
 use crate::errors::ApiError;
-use crate::minio_upload::file_upload;
-use crate::nats::publisher::file_upload::nats_publish_upload;
+use crate::minio_upload::{UPLOAD_BUCKET, file_upload};
+use crate::routes::upload::sample::{new_fastq_sample, register_fastq_sample};
+use crate::schema::file_upload::UploadField;
 use crate::state::ConnectionState;
 
+/// Upload one or more fastq files. Paired-end files are matched up by their
+/// _R1/_R2 file names, or explicitly through a "pairs" field with a json
+/// list of [R1, R2] file names, e.g., [["a.fastq.gz", "b.fastq.gz"]].
+/// An optional json "config" field sets the thresholds for every sample.
 pub async fn upload_file(
     State(state): State<ConnectionState>,
+    Extension(auth_user): Extension<AuthUser>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, ApiError> {
     let db = state.surrealdb.client;
     let minio = state.minio.client;
     let nats = state.nats.client;
 
+    // Check the user before streaming anything into MinIO.
+    let user = get_user(&db, &auth_user).await?;
+
     // Temp solution.
     let mut pipeline: Option<Pipeline> = None;
-    let mut file_name: Option<String> = None;
-    let mut file_contents: Option<Bytes> = None;
+    let mut config = UploadConfig::default();
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    let mut upload_fields: Vec<UploadField> = Vec::new();
 
-    while let Some(field) = multipart.next_field().await? {
-        let name = field.name();
+    while let Some(mut field) = multipart.next_field().await? {
+        // Owned, since streaming the file field needs a mutable borrow.
+        let name = field.name().map(str::to_string);
 
-        match name {
+        match name.as_deref() {
             Some("file") => {
-                file_name = Some(field.file_name().unwrap().to_string());
-                file_contents = Some(field.bytes().await?);
+                let file_name = field
+                    .file_name()
+                    .ok_or(ApiError::InvalidMultiFormError(
+                        "File field is missing a file name".into(),
+                    ))?
+                    .to_string();
+
+                // Stream the file into MinIO while we read the body,
+                // instead of buffering it in memory.
+                upload_fields
+                    .push(file_upload(UPLOAD_BUCKET, &file_name, &mut field, &minio).await?);
             }
             Some("pipeline") => {
-                let pipeline_parsed = serde_json::from_str(&field.text().await?)
-                    .expect("Failed to serialize pipeline field.");
+                let pipeline_parsed = serde_json::from_str(&field.text().await?).map_err(|e| {
+                    ApiError::InvalidMultiFormError(format!("Invalid pipeline field: {}", e))
+                })?;
 
                 pipeline = Some(pipeline_parsed);
             }
+            Some("config") => {
+                config = serde_json::from_str(&field.text().await?)
+                    .map_err(|e| ApiError::InvalidUploadConfig(e.to_string()))?;
+                config.validate().map_err(ApiError::InvalidUploadConfig)?;
+            }
+            Some("pairs") => {
+                pairs = serde_json::from_str(&field.text().await?).map_err(|e| {
+                    ApiError::InvalidMultiFormError(format!("Invalid pairs field: {}", e))
+                })?;
+            }
             _ => {
                 info!("Unexpected field: {:?}", name);
             }
         }
     }
 
-    // We should have better error handling here for
-    // cases where pipeline, file_name or file_contents are None.
-    // For now, we just unwrap.
-
-    // Upload to MinIO
-    let upload_field = file_upload(
-        "my-bucket",
-        &file_name.as_ref().unwrap(),
-        file_contents.unwrap(),
-        &minio,
-    )
-    .await?;
-
-    // Construct our db fastq sample.
-    let fastq_sample = FastqSample {
-        id: None,
-        data: FastqSampleData {
-            name: file_name.unwrap().to_string(),
-            status: Status::Done,
-            url: upload_field.url.clone(),
-            pipeline: pipeline.unwrap(),
-            config: FastqSampleConfig::mock(),
-            created_at: time_now(),
-            updated_at: time_now(),
-        },
-    };
-
-    // MOVE database write to somewhere else later on.
-    // First, create a user (mock new user everytime for now).
-    let user_response: User = db.create("users").content(User::mock()).await?.unwrap();
-
-    // Then, write fastq sample to database.
-    let sample_response: FastqSample = db
-        .create("fastq_samples")
-        .content(fastq_sample)
-        .await?
-        .unwrap();
+    if upload_fields.is_empty() {
+        return Err(ApiError::InvalidMultiFormError("Missing file field".into()));
+    }
+    let pipeline = pipeline.ok_or(ApiError::InvalidMultiFormError(
+        "Missing pipeline field".into(),
+    ))?;
 
-    // Lastly, create a relation between user and fastq sample.
-    let relation_response = db
-        .query("RELATE $user->uploaded->$fastq_sample")
-        .bind(("user", user_response.id.as_ref().unwrap().surrealdb_id()?))
-        .bind((
-            "fastq_sample",
-            sample_response.id.as_ref().unwrap().surrealdb_id()?,
-        ))
-        .await?;
+    let files: Vec<(String, UploadField)> = upload_fields
+        .into_iter()
+        .map(|upload_field| (upload_field.file_name.clone(), upload_field))
+        .collect();
+    let samples = pair_files(files, &pairs).map_err(ApiError::InvalidMultiFormError)?;
 
-    info!("Relation response: {:?}", relation_response);
+    for sample in samples {
+        info!(
+            "Registering sample {} ({})",
+            sample.name,
+            if sample.r2.is_some() {
+                "paired-end"
+            } else {
+                "single-end"
+            }
+        );
 
-    // Send message to fastq preprocessor.
-    let fastq_message = FastqMessage {
-        url: upload_field.url,
-        fastq_sample_id: sample_response.id.unwrap(),
-    };
-    nats_publish_upload(nats, &fastq_message).await?;
+        let mut fastq_sample = new_fastq_sample(
+            sample.name,
+            sample.r1.url,
+            sample.r2.map(|r2| r2.url),
+            pipeline,
+        );
+        fastq_sample.apply_upload_config(&config);
+        register_fastq_sample(&db, nats.clone(), user.clone(), None, None, fastq_sample).await?;
+    }
 
     // multipart ... something.
     Ok((StatusCode::OK, Json(json!({"upload": "success"}))))
@@ -1,5 +1,8 @@
 use minio::s3;
+use shared::minio::Presigner;
+use shared::nats::schema::sample_status::SampleStatusMessage;
 use surrealdb::{Surreal, engine::remote::ws::Client};
+use tokio::sync::broadcast;
 
 #[derive(Debug, Clone)]
 pub struct SurrealDB {
@@ -9,6 +12,8 @@ pub struct SurrealDB {
 #[derive(Debug, Clone)]
 pub struct MinIO {
     pub client: s3::Client,
+    // Signs the urls that the browser uploads to.
+    pub presigner: Presigner,
 }
 
 #[derive(Debug, Clone)]
@@ -16,9 +21,16 @@ pub struct Nats {
     pub client: async_nats::jetstream::Context,
 }
 
+// Status events from NATS, fanned out to every open event stream.
+#[derive(Debug, Clone)]
+pub struct SampleStatusEvents {
+    pub sender: broadcast::Sender<SampleStatusMessage>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ConnectionState {
     pub surrealdb: SurrealDB,
     pub minio: MinIO,
     pub nats: Nats,
+    pub status_events: SampleStatusEvents,
 }
@@ -0,0 +1,26 @@
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Client;
+
+use super::context::get_stored_token;
+
+/// Where the API is served, for the requests of api_client.
+pub const API_URL: &str = "http://localhost:8001";
+
+/// Client for requests to the API, which sends the stored JWT as a bearer token.
+pub fn api_client() -> Result<Client, String> {
+    let token = get_stored_token()
+        .filter(|token| !token.is_empty())
+        .ok_or("Not logged in.")?;
+
+    let mut authorization =
+        HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|e| e.to_string())?;
+    authorization.set_sensitive(true);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, authorization);
+
+    Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|e| e.to_string())
+}
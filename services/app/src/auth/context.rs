@@ -89,7 +89,7 @@ pub fn use_auth() -> AuthContext {
 
 // Token storage helpers using web_sys for localStorage
 #[cfg(feature = "web")]
-fn get_stored_token() -> Option<String> {
+pub(crate) fn get_stored_token() -> Option<String> {
     web_sys::window()?
         .local_storage()
         .ok()??
@@ -119,7 +119,7 @@ fn clear_stored_token() {
 
 // Non-web implementations (for server-side rendering)
 #[cfg(not(feature = "web"))]
-fn get_stored_token() -> Option<String> {
+pub(crate) fn get_stored_token() -> Option<String> {
     None
 }
 
@@ -1,8 +1,10 @@
+mod client;
 mod context;
 mod protected;
 mod server_fns;
 mod types;
 
+pub use client::{api_client, API_URL};
 pub use context::{use_auth, AuthContext};
 pub use protected::ProtectedRoute;
 pub use server_fns::{exchange_oauth_code, get_google_auth_url};
@@ -0,0 +1,50 @@
+use crate::auth::API_URL;
+use reqwest::{Client, StatusCode};
+use shared::schema::schema::Pipeline;
+use shared::schema::upload::{
+    CreateBatchRequest, CreateBatchResponse, SampleSheetErrors, UploadConfig,
+};
+
+/// Create a batch from a sample sheet, before uploading its files with
+/// upload_resumable. The API checks the sheet against `file_names`.
+pub async fn create_batch(
+    client: &Client,
+    name: String,
+    sample_sheet: String,
+    pipeline: Pipeline,
+    file_names: Vec<String>,
+    config: UploadConfig,
+) -> Result<CreateBatchResponse, String> {
+    let request = CreateBatchRequest {
+        name,
+        sample_sheet,
+        pipeline,
+        file_names,
+        config,
+    };
+
+    let response = client
+        .post(format!("{API_URL}/batches"))
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Should not happen since we validate before uploading, but the API is the source of truth.
+    if response.status() == StatusCode::UNPROCESSABLE_ENTITY {
+        let errors: SampleSheetErrors = response.json().await.map_err(|e| e.to_string())?;
+        return Err(errors
+            .errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<String>>()
+            .join("\n"));
+    }
+
+    response
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
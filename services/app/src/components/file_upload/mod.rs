@@ -1,6 +1,14 @@
+mod batch;
+pub use batch::create_batch;
+
 mod main;
 pub use main::UploadMain;
 
+mod resumable;
+pub use resumable::upload_resumable;
+
+mod storage;
+
 mod upload;
 pub use upload::UploadComponent;
 
@@ -0,0 +1,214 @@
+use crate::auth::API_URL;
+use crate::components::file_upload::storage::{
+    clear_stored_session, get_stored_session, store_session,
+};
+use dioxus::html::FileData;
+use futures::StreamExt;
+use reqwest::{Client, StatusCode};
+use shared::schema::schema::Pipeline;
+use shared::schema::upload::{
+    ResumableUploadResponse, UploadConfig, UploadSessionRequest, UPLOAD_CHUNK_SIZE_HEADER,
+    UPLOAD_OFFSET_HEADER,
+};
+use tracing::warn;
+
+/// How many times we try to upload a single chunk before giving up.
+const MAX_CHUNK_ATTEMPTS: usize = 3;
+
+struct ResumableSession {
+    session_id: String,
+    chunk_size: usize,
+    offset: u64,
+}
+
+/// Files can't be identified by path in the browser, so this is the
+/// best we can do to recognize the same file after a page reload.
+fn fingerprint(file: &FileData) -> String {
+    format!("{}:{}:{}", file.name(), file.size(), file.last_modified())
+}
+
+/// Upload a single file in chunks through the API's resumable upload endpoints.
+///
+/// If a previous upload of the same file was interrupted (even before a page
+/// reload), we continue from where the server says it left off instead of
+/// starting over.
+///
+/// `batch_id` is set for files of a sample sheet batch, and `config`
+/// holds the thresholds to filter the file with.
+///
+/// `on_progress` is called with the fraction (0.0 - 1.0) of uploaded bytes.
+pub async fn upload_resumable(
+    client: &Client,
+    file: &FileData,
+    pipeline: Pipeline,
+    batch_id: Option<String>,
+    config: UploadConfig,
+    mut on_progress: impl FnMut(f64),
+) -> Result<(), String> {
+    let fingerprint = fingerprint(file);
+    let size = file.size();
+
+    let mut session = match get_stored_session(&fingerprint) {
+        Some(session_id) => match get_session(client, &session_id).await {
+            Ok(session) => session,
+            // E.g., the session is already finished or does not exist anymore.
+            Err(_) => create_session(client, file, pipeline, batch_id, config).await?,
+        },
+        None => create_session(client, file, pipeline, batch_id, config).await?,
+    };
+    store_session(&fingerprint, &session.session_id);
+    on_progress(session.offset as f64 / size as f64);
+
+    // Skip whatever the server already has.
+    let mut to_skip = session.offset as usize;
+    let mut stream = file.byte_stream();
+    let mut buffer: Vec<u8> = Vec::with_capacity(session.chunk_size);
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+
+        if to_skip >= chunk.len() {
+            to_skip -= chunk.len();
+            continue;
+        }
+        buffer.extend_from_slice(&chunk[to_skip..]);
+        to_skip = 0;
+
+        while buffer.len() >= session.chunk_size {
+            let rest = buffer.split_off(session.chunk_size);
+            let chunk = std::mem::replace(&mut buffer, rest);
+
+            session.offset = upload_chunk(client, &session, chunk).await?;
+            on_progress(session.offset as f64 / size as f64);
+        }
+    }
+
+    // Last chunk may be smaller than chunk_size.
+    if !buffer.is_empty() {
+        session.offset = upload_chunk(client, &session, buffer).await?;
+        on_progress(session.offset as f64 / size as f64);
+    }
+
+    clear_stored_session(&fingerprint);
+
+    Ok(())
+}
+
+async fn create_session(
+    client: &Client,
+    file: &FileData,
+    pipeline: Pipeline,
+    batch_id: Option<String>,
+    config: UploadConfig,
+) -> Result<ResumableSession, String> {
+    let request = UploadSessionRequest {
+        file_name: file.name(),
+        size: file.size(),
+        pipeline,
+        batch_id,
+        config,
+    };
+
+    let response: ResumableUploadResponse = client
+        .post(format!("{API_URL}/resumable"))
+        .json(&request)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(ResumableSession {
+        session_id: response.session_id,
+        chunk_size: response.chunk_size,
+        offset: response.offset,
+    })
+}
+
+/// Ask the server where an existing session left off.
+async fn get_session(client: &Client, session_id: &str) -> Result<ResumableSession, String> {
+    let response = client
+        .head(format!("{API_URL}/resumable/{session_id}"))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    Ok(ResumableSession {
+        session_id: session_id.to_string(),
+        chunk_size: parse_header(&response, UPLOAD_CHUNK_SIZE_HEADER)? as usize,
+        offset: parse_header(&response, UPLOAD_OFFSET_HEADER)?,
+    })
+}
+
+fn parse_header(response: &reqwest::Response, header: &str) -> Result<u64, String> {
+    response
+        .headers()
+        .get(header)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .ok_or(format!("Missing {header} header."))
+}
+
+/// PATCH a chunk at the current offset and return the new offset.
+///
+/// If the connection drops, we ask the server for its offset. If it already
+/// got the chunk we move on, otherwise we try again.
+async fn upload_chunk(
+    client: &Client,
+    session: &ResumableSession,
+    chunk: Vec<u8>,
+) -> Result<u64, String> {
+    let expected_offset = session.offset + chunk.len() as u64;
+    let mut last_error = String::new();
+
+    for attempt in 1..=MAX_CHUNK_ATTEMPTS {
+        let response = client
+            .patch(format!("{API_URL}/resumable/{}", session.session_id))
+            .header(UPLOAD_OFFSET_HEADER, session.offset.to_string())
+            .body(chunk.clone())
+            .send()
+            .await;
+
+        let response = match response {
+            // The file is not FASTQ, so there is no point in retrying.
+            Ok(response) if response.status() == StatusCode::UNPROCESSABLE_ENTITY => {
+                return Err(invalid_fastq_message(
+                    response.json().await.unwrap_or_default(),
+                ));
+            }
+            response => response.and_then(|response| response.error_for_status()),
+        };
+
+        match response {
+            Ok(response) => return parse_header(&response, UPLOAD_OFFSET_HEADER),
+            Err(e) => {
+                warn!("Chunk upload attempt {} failed: {:?}", attempt, e);
+                last_error = e.to_string();
+
+                if let Ok(server_session) = get_session(client, &session.session_id).await {
+                    if server_session.offset == expected_offset {
+                        return Ok(server_session.offset);
+                    }
+                }
+            }
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Turn the API's structured FASTQ validation error into something a user can act on.
+fn invalid_fastq_message(error: serde_json::Value) -> String {
+    format!(
+        "Invalid FASTQ at record {} (line {}): {}",
+        error["record"],
+        error["line"],
+        error["kind"]["kind"]
+            .as_str()
+            .unwrap_or("unknown")
+            .replace('_', " ")
+    )
+}
@@ -0,0 +1,52 @@
+// Keep track of unfinished resumable uploads in localStorage, so that
+// an interrupted upload can be resumed after a page reload. The key is a
+// fingerprint of the file, since we can't keep a handle to the file itself.
+
+#[allow(dead_code)]
+const UPLOAD_SESSION_KEY_PREFIX: &str = "upload_session";
+
+#[allow(dead_code)]
+fn storage_key(fingerprint: &str) -> String {
+    format!("{UPLOAD_SESSION_KEY_PREFIX}:{fingerprint}")
+}
+
+#[cfg(feature = "web")]
+pub fn get_stored_session(fingerprint: &str) -> Option<String> {
+    web_sys::window()?
+        .local_storage()
+        .ok()??
+        .get_item(&storage_key(fingerprint))
+        .ok()?
+}
+
+#[cfg(feature = "web")]
+pub fn store_session(fingerprint: &str, session_id: &str) {
+    if let Some(storage) = web_sys::window()
+        .and_then(|w| w.local_storage().ok())
+        .flatten()
+    {
+        let _ = storage.set_item(&storage_key(fingerprint), session_id);
+    }
+}
+
+#[cfg(feature = "web")]
+pub fn clear_stored_session(fingerprint: &str) {
+    if let Some(storage) = web_sys::window()
+        .and_then(|w| w.local_storage().ok())
+        .flatten()
+    {
+        let _ = storage.remove_item(&storage_key(fingerprint));
+    }
+}
+
+// Non-web implementations.
+#[cfg(not(feature = "web"))]
+pub fn get_stored_session(_fingerprint: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "web"))]
+pub fn store_session(_fingerprint: &str, _session_id: &str) {}
+
+#[cfg(not(feature = "web"))]
+pub fn clear_stored_session(_fingerprint: &str) {}
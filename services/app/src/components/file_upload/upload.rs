@@ -1,5 +1,9 @@
+use crate::auth::api_client;
+use crate::components::file_upload::create_batch;
+use crate::components::file_upload::upload_resumable;
 use crate::components::file_upload::AcceptFileTypes;
 use crate::components::Button;
+use crate::components::{Progress, ProgressIndicator};
 
 use crate::components::{
     Select, SelectItemIndicator, SelectList, SelectOption, SelectTrigger, SelectValue,
@@ -12,7 +16,11 @@ use crate::components::ToastProvider;
 use crate::components::{Input, Label};
 use crate::components::{PopoverContent, PopoverRoot, PopoverTrigger};
 use dioxus_primitives::ContentSide;
+use shared::schema::sample_sheet::{
+    parse_sample_sheet, validate_sample_sheet_files, SampleSheetError,
+};
 use shared::schema::schema::Pipeline;
+use shared::schema::upload;
 use strum::IntoEnumIterator;
 
 use dioxus::html::FileData;
@@ -20,13 +28,18 @@ use dioxus::prelude::*;
 use dioxus_primitives::toast::use_toast;
 use dioxus_primitives::toast::ToastOptions;
 
-use reqwest::multipart::{Form, Part};
-
+use std::collections::HashMap;
 use std::time::Duration;
 
 #[derive(Clone)]
 struct UploadedFileContext {
     uploaded_files: Signal<Vec<UploadedFile>>,
+    // Upload progress (0-100) per file name, for files that are being uploaded.
+    upload_progress: Signal<HashMap<String, f64>>,
+    // Optional CSV/TSV sample sheet, which turns the upload into a batch.
+    sample_sheet: Signal<Option<SampleSheet>>,
+    // Set once the batch is created, so that retries upload to the same batch.
+    batch_id: Signal<Option<String>>,
 }
 
 #[derive(Clone)]
@@ -34,6 +47,39 @@ struct UploadedFile {
     file_data: FileData,
 }
 
+#[derive(Clone)]
+struct SampleSheet {
+    name: String,
+    text: String,
+}
+
+/// Empty (or invalid) number inputs mean "use the default".
+fn parse_optional_number(value: &str) -> Option<usize> {
+    value.trim().parse().ok()
+}
+
+fn parse_optional_text(value: &str) -> Option<String> {
+    Some(value.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn display_optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Check the sheet itself, and that it matches the chosen files.
+fn sample_sheet_errors(
+    sample_sheet: &SampleSheet,
+    files: &[UploadedFile],
+) -> Vec<SampleSheetError> {
+    match parse_sample_sheet(&sample_sheet.text) {
+        Ok(rows) => {
+            let file_names: Vec<String> = files.iter().map(|f| f.file_data.name()).collect();
+            validate_sample_sheet_files(&rows, &file_names)
+        }
+        Err(errors) => errors,
+    }
+}
+
 #[component]
 pub fn FileInput() -> Element {
     let mut uploaded_files = use_context::<UploadedFileContext>().uploaded_files;
@@ -67,6 +113,75 @@ pub fn FileInput() -> Element {
     }
 }
 
+#[component]
+pub fn SampleSheetInput() -> Element {
+    let uploaded_files = use_context::<UploadedFileContext>().uploaded_files;
+    let mut sample_sheet = use_context::<UploadedFileContext>().sample_sheet;
+    let mut batch_id = use_context::<UploadedFileContext>().batch_id;
+    let toast_api = use_toast();
+
+    let handle_chosen_sheet = move |files: Vec<FileData>| async move {
+        let Some(file) = files.into_iter().next() else {
+            return;
+        };
+
+        match file.read_string().await {
+            Ok(text) => {
+                sample_sheet.set(Some(SampleSheet {
+                    name: file.name(),
+                    text,
+                }));
+                // A new sheet is a new batch.
+                batch_id.set(None);
+            }
+            Err(e) => toast_api.error(
+                format!("Failed to read sample sheet: {:?}", e),
+                ToastOptions::new()
+                    .duration(Duration::from_secs(3))
+                    .permanent(false),
+            ),
+        }
+    };
+
+    // Only validate until the batch is created, since uploaded files are removed from the list.
+    let errors = match (sample_sheet.read().as_ref(), batch_id.read().as_ref()) {
+        (Some(sheet), None) => sample_sheet_errors(sheet, &uploaded_files.read()),
+        _ => Vec::new(),
+    };
+
+    rsx! {
+        div { id: "sample-sheet-container",
+            Label { html_for: "sample-sheet-input", "Sample sheet" }
+            input {
+                id: "sample-sheet-input",
+                r#type: "file",
+                accept: ".csv, .tsv, .txt",
+                onchange: move |evt| async move { handle_chosen_sheet(evt.files()).await },
+            }
+            if let Some(sheet) = sample_sheet.read().as_ref() {
+                span { id: "sample-sheet-name", {sheet.name.clone()} }
+                Button {
+                    r#type: "button",
+                    id: "file-list-remove-row-button",
+                    "data-style": "destructive",
+                    onclick: move |_| {
+                        sample_sheet.set(None);
+                        batch_id.set(None);
+                    },
+                    "Remove"
+                }
+            }
+        }
+        if !errors.is_empty() {
+            div { id: "sample-sheet-errors",
+                for error in errors {
+                    div { id: "sample-sheet-error", {error.to_string()} }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 pub fn DragDrop() -> Element {
     let mut uploaded_files = use_context::<UploadedFileContext>().uploaded_files;
@@ -97,6 +212,7 @@ pub fn DragDrop() -> Element {
 #[component]
 pub fn FileList() -> Element {
     let mut uploaded_files = use_context::<UploadedFileContext>().uploaded_files;
+    let upload_progress = use_context::<UploadedFileContext>().upload_progress;
     rsx! {
         // List of chosen files.
         div { id: "file-list-container",
@@ -107,6 +223,14 @@ pub fn FileList() -> Element {
                     .map(|(i, f)| rsx! {
                         div { id: "file-list-row",
                             span { id: "chosen-file-name-span", {f.file_data.name()} }
+                            if let Some(progress) = upload_progress.read().get(&f.file_data.name()) {
+                                Progress {
+                                    id: "file-list-progress",
+                                    value: Some(*progress),
+                                    max: 100.0,
+                                    ProgressIndicator {}
+                                }
+                            }
                             Button {
                                 id: "file-list-remove-row-button",
                                 "data-style": "destructive",
@@ -123,17 +247,20 @@ pub fn FileList() -> Element {
     }
 }
 
-/// TODO - add progress spinner/loader.
 #[component]
 pub fn UploadButton() -> Element {
     let mut uploaded_files = use_context::<UploadedFileContext>().uploaded_files;
+    let mut upload_progress = use_context::<UploadedFileContext>().upload_progress;
+    let mut sample_sheet = use_context::<UploadedFileContext>().sample_sheet;
+    let mut batch_id = use_context::<UploadedFileContext>().batch_id;
     let chosen_pipeline = use_context::<Signal<Option<Pipeline>>>();
+    let upload_config = use_context::<Signal<upload::UploadConfig>>();
 
     let toast_api = use_toast();
 
     let upload_files = move || async move {
         // Check before trying to upload.
-        if chosen_pipeline.read().is_none() {
+        let Some(pipeline) = *chosen_pipeline.read() else {
             toast_api.error(
                 "No pipeline chosen.".to_string(),
                 ToastOptions::new()
@@ -141,69 +268,139 @@ pub fn UploadButton() -> Element {
                     .permanent(false),
             );
             return;
+        };
+
+        let config = upload_config.read().clone();
+        if let Err(e) = config.validate() {
+            toast_api.error(
+                e,
+                ToastOptions::new()
+                    .duration(Duration::from_secs(3))
+                    .permanent(false),
+            );
+            return;
         }
 
-        let client = reqwest::Client::new();
+        let client = match api_client() {
+            Ok(client) => client,
+            Err(e) => {
+                toast_api.error(
+                    e,
+                    ToastOptions::new()
+                        .duration(Duration::from_secs(3))
+                        .permanent(false),
+                );
+                return;
+            }
+        };
 
         let files = uploaded_files.read().clone();
-        for file in files {
-            let file_bytes = match file.file_data.read_bytes().await {
-                Ok(bytes) => bytes,
+
+        // With a sample sheet, the files are uploaded as a batch.
+        let sheet = sample_sheet.read().clone();
+        if let (Some(sheet), None) = (sheet, batch_id.read().clone()) {
+            let errors = sample_sheet_errors(&sheet, &files);
+            if !errors.is_empty() {
+                toast_api.error(
+                    format!("Sample sheet has {} error(s)", errors.len()),
+                    ToastOptions::new()
+                        .duration(Duration::from_secs(3))
+                        .permanent(false),
+                );
+                return;
+            }
+
+            let file_names = files.iter().map(|f| f.file_data.name()).collect();
+            match create_batch(
+                &client,
+                sheet.name,
+                sheet.text,
+                pipeline,
+                file_names,
+                config.clone(),
+            )
+            .await
+            {
+                Ok(response) => {
+                    info!("Created batch with {} samples", response.num_samples);
+                    batch_id.set(Some(response.batch_id));
+                }
                 Err(e) => {
-                    error!("Failed to read file bytes: {:?}", e);
-                    continue;
+                    error!("Failed to create batch: {:?}", e);
+                    toast_api.error(
+                        "Failed to create batch".to_string(),
+                        ToastOptions::new()
+                            .duration(Duration::from_secs(3))
+                            .permanent(false),
+                    );
+                    return;
                 }
-            };
+            }
+        }
+        let current_batch_id = batch_id.read().clone();
+        let mut failed_files: Vec<UploadedFile> = Vec::new();
 
+        for file in files {
             let file_name = file.file_data.name();
-            let file_part = Part::bytes(file_bytes.to_vec())
-                .file_name(file_name)
-                .mime_str("application/octet-stream")
-                .expect("Failed to set MIME type");
-            let pipeline_part = Part::text(chosen_pipeline.read().as_ref().unwrap().to_string());
-
-            let payload = Form::new()
-                .part("file", file_part)
-                .part("pipeline", pipeline_part);
-
-            let response = client
-                .post("http://localhost:8001/upload")
-                .multipart(payload)
-                .send()
-                .await;
-
-            match response {
-                Ok(response) => {
-                    info!("{:?}", response)
-                }
+            upload_progress.write().insert(file_name.clone(), 0.0);
+
+            let on_progress = |fraction: f64| {
+                upload_progress
+                    .write()
+                    .insert(file_name.clone(), fraction * 100.0);
+            };
+
+            // Resumable, so that clicking upload again (even after a page
+            // reload) continues where an interrupted upload left off.
+            match upload_resumable(
+                &client,
+                &file.file_data,
+                pipeline,
+                current_batch_id.clone(),
+                config.clone(),
+                on_progress,
+            )
+            .await
+            {
+                Ok(()) => info!("Uploaded {}", file_name),
                 Err(e) => {
-                    error!("{:?}", e)
+                    error!("Failed to upload {}: {:?}", file_name, e);
+                    failed_files.push(file);
                 }
             }
+
+            upload_progress.write().remove(&file_name);
         }
 
-        // We need some condition to check if
-        // All files were uploaded successfully.
-
-        // Upload success.
-        toast_api.success(
-            "Successfully uploaded files".to_string(),
-            ToastOptions::new()
-                .duration(Duration::from_secs(3))
-                .permanent(false),
-        );
-
-        // Remove locally chosen files.
-        // NOTE - here, we should change to only
-        // remove files that were successfully uploaded.
-        // I.e., Keep files that failed to upload.
-        uploaded_files.write().clear();
+        if failed_files.is_empty() {
+            // The batch is done.
+            sample_sheet.set(None);
+            batch_id.set(None);
+
+            toast_api.success(
+                "Successfully uploaded files".to_string(),
+                ToastOptions::new()
+                    .duration(Duration::from_secs(3))
+                    .permanent(false),
+            );
+        } else {
+            toast_api.error(
+                format!("Failed to upload {} file(s)", failed_files.len()),
+                ToastOptions::new()
+                    .duration(Duration::from_secs(3))
+                    .permanent(false),
+            );
+        }
+
+        // Keep files that failed to upload, so the user can retry.
+        uploaded_files.set(failed_files);
     };
 
     rsx! {
         if uploaded_files.len() > 0 {
             Button {
                 id: "upload-button",
+                disabled: !upload_progress.read().is_empty(),
                 onclick: move |_| async move {
                     upload_files().await;
                 },
@@ -216,6 +413,7 @@ pub fn UploadButton() -> Element {
 #[component]
 pub fn UploadConfig() -> Element {
     let mut chosen_pipeline = use_context::<Signal<Option<Pipeline>>>();
+    let mut upload_config = use_context::<Signal<upload::UploadConfig>>();
     let mut open = use_signal(|| false);
 
     let labels = Pipeline::iter().enumerate().map(|(i, label)| {
@@ -273,11 +471,25 @@ pub fn UploadConfig() -> Element {
 
                             div { id: "metadata-input",
                                 Label { html_for: "identifier", "Identifier" }
-                                Input { id: "identifier", placeholder: "..." }
+                                Input {
+                                    id: "identifier",
+                                    placeholder: "...",
+                                    value: display_optional(&upload_config.read().identifier),
+                                    oninput: move |e: FormEvent| {
+                                        upload_config.write().identifier = parse_optional_text(&e.value());
+                                    },
+                                }
                             }
                             div { id: "metadata-input",
                                 Label { html_for: "comment", "Comment" }
-                                Input { id: "comment", placeholder: "..." }
+                                Input {
+                                    id: "comment",
+                                    placeholder: "...",
+                                    value: display_optional(&upload_config.read().comment),
+                                    oninput: move |e: FormEvent| {
+                                        upload_config.write().comment = parse_optional_text(&e.value());
+                                    },
+                                }
                             }
                         }
 
@@ -307,6 +519,10 @@ pub fn UploadConfig() -> Element {
                                     step: 100,
                                     min: 0,
                                     placeholder: 200,
+                                    value: display_optional(&upload_config.read().min_len),
+                                    oninput: move |e: FormEvent| {
+                                        upload_config.write().min_len = parse_optional_number(&e.value());
+                                    },
                                 }
                             }
                             div { id: "threshold-input",
@@ -317,6 +533,10 @@ pub fn UploadConfig() -> Element {
                                     step: 100,
                                     min: 0,
                                     placeholder: "∞",
+                                    value: display_optional(&upload_config.read().max_len),
+                                    oninput: move |e: FormEvent| {
+                                        upload_config.write().max_len = parse_optional_number(&e.value());
+                                    },
                                 }
                             }
                             div { id: "threshold-input",
@@ -327,7 +547,11 @@ pub fn UploadConfig() -> Element {
                                     step: 1,
                                     min: 10,
                                     placeholder: 15,
-                                    max: 60,
+                                    max: upload::MAX_MIN_PHRED as i64,
+                                    value: display_optional(&upload_config.read().min_phred),
+                                    oninput: move |e: FormEvent| {
+                                        upload_config.write().min_phred = parse_optional_number(&e.value());
+                                    },
                                 }
                             }
                         }
@@ -351,18 +575,27 @@ pub fn UploadConfig() -> Element {
 pub fn UploadComponent() -> Element {
     // Enable modifying our uploaded files.
     let uploaded_files = use_signal(|| Vec::<UploadedFile>::new());
+    let upload_progress = use_signal(|| HashMap::<String, f64>::new());
+    let sample_sheet = use_signal(|| None::<SampleSheet>);
+    let batch_id = use_signal(|| None::<String>);
     let chosen_pipeline: Signal<Option<Pipeline>> = use_signal(|| None);
+    let upload_config = use_signal(upload::UploadConfig::default);
 
     // Provide this context to relevant child components.
     use_context_provider(|| UploadedFileContext {
         uploaded_files: uploaded_files,
+        upload_progress,
+        sample_sheet,
+        batch_id,
     });
 
     //
     use_context_provider(|| chosen_pipeline);
+    use_context_provider(|| upload_config);
 
     rsx! {
         UploadConfig {}
+        ToastProvider { SampleSheetInput {} }
         FileInput {}
         DragDrop {}
         FileList {}
@@ -2,6 +2,7 @@ use dioxus::prelude::*;
 
 #[component]
 pub fn Input(
+    oninput: Option<EventHandler<FormEvent>>,
     #[props(extends=GlobalAttributes)]
     #[props(extends=input)]
     attributes: Vec<Attribute>,
@@ -9,6 +10,15 @@ pub fn Input(
 ) -> Element {
     rsx! {
         document::Link { rel: "stylesheet", href: asset!("./style.css") }
-        input { class: "input", ..attributes, {children} }
+        input {
+            class: "input",
+            oninput: move |e| {
+                if let Some(oninput) = oninput {
+                    oninput.call(e);
+                }
+            },
+            ..attributes,
+            {children}
+        }
     }
 }
@@ -0,0 +1,34 @@
+use reqwest::Client;
+use shared::schema::sample::{SampleListQuery, SampleListResponse, SampleSummary};
+
+use crate::auth::API_URL;
+
+/// A page of the user's samples.
+pub async fn list_samples(
+    client: &Client,
+    query: &SampleListQuery,
+) -> Result<SampleListResponse, String> {
+    client
+        .get(format!("{API_URL}/samples"))
+        .query(query)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Cancel a sample that is not done yet. Returns the sample as it is now.
+pub async fn cancel_sample(client: &Client, sample_id: &str) -> Result<SampleSummary, String> {
+    client
+        .post(format!("{API_URL}/samples/{sample_id}/cancel"))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
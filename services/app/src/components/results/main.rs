@@ -1,6 +1,6 @@
 use dioxus::prelude::*;
 
-use crate::components::results::Table;
+use crate::components::results::SampleTable;
 
 const RESULT_CSS: Asset = asset!("./style.css");
 
@@ -8,6 +8,6 @@ const RESULT_CSS: Asset = asset!("./style.css");
 pub fn TestResults() -> Element {
     rsx! {
         document::Link { rel: "stylesheet", href: RESULT_CSS }
-        Table {}
+        SampleTable {}
     }
 }
@@ -1,5 +1,10 @@
 mod main;
 pub use main::TestResults;
 
+mod api;
+mod samples;
+pub use samples::SampleTable;
+mod status;
+
 mod doodle;
 pub use doodle::Table;
@@ -0,0 +1,98 @@
+use dioxus::prelude::*;
+use shared::schema::sample::{SampleListQuery, SampleSummary};
+use shared::schema::schema::Status;
+use tracing::error;
+
+use crate::auth::api_client;
+use crate::components::results::api::{cancel_sample, list_samples};
+use crate::components::results::status::use_sample_status;
+
+/// Cancel the sample, and show its new status right away
+/// rather than waiting for the status event.
+async fn cancel(mut samples: Signal<Vec<SampleSummary>>, sample_id: String) {
+    let response = match api_client() {
+        Ok(client) => cancel_sample(&client, &sample_id).await,
+        Err(e) => Err(e),
+    };
+
+    match response {
+        Ok(cancelled) => {
+            if let Some(sample) = samples.write().iter_mut().find(|s| s.id == cancelled.id) {
+                *sample = cancelled;
+            }
+        }
+        Err(e) => error!("Failed to cancel sample {}: {}", sample_id, e),
+    }
+}
+
+/// The user's latest samples, whose status updates live while preprocessing runs.
+#[component]
+pub fn SampleTable() -> Element {
+    let mut samples: Signal<Vec<SampleSummary>> = use_signal(|| vec![]);
+    let mut total: Signal<usize> = use_signal(|| 0);
+    let refresh: Signal<usize> = use_signal(|| 0);
+
+    // Runs again whenever refresh changes, e.g., when a new sample shows up.
+    use_effect(move || {
+        refresh();
+        spawn(async move {
+            let response = match api_client() {
+                Ok(client) => list_samples(&client, &SampleListQuery::default()).await,
+                Err(e) => Err(e),
+            };
+
+            match response {
+                Ok(response) => {
+                    samples.set(response.samples);
+                    total.set(response.total);
+                }
+                Err(e) => error!("Failed to fetch samples: {}", e),
+            }
+        });
+    });
+
+    use_sample_status(samples, refresh);
+
+    rsx! {
+        table { id: "test-table",
+            caption { "Samples ({total})" }
+            thead { id: "table-header",
+                tr { id: "table-header-row",
+                    for header in ["Name", "Pipeline", "Status", "Uploaded", "Updated", ""] {
+                        th { id: "table-header-row-item", "{header}" }
+                    }
+                }
+            }
+            tbody { id: "table-body",
+                for sample in samples.read().iter() {
+                    tr { id: "table-body-row", key: "{sample.id}",
+                        td { id: "table-body-row-item", "{sample.name}" }
+                        td { id: "table-body-row-item", "{sample.pipeline}" }
+                        // Hover to see why preprocessing failed.
+                        td {
+                            id: "table-body-row-item",
+                            title: sample.error.clone().unwrap_or_default(),
+                            {format!("{:?}", sample.status)}
+                        }
+                        td { id: "table-body-row-item", "{sample.created_at}" }
+                        td { id: "table-body-row-item", "{sample.updated_at}" }
+                        td { id: "table-body-row-item",
+                            if sample.status.can_transition_to(Status::Cancelled) {
+                                button {
+                                    onclick: {
+                                        let sample_id = sample.id.clone();
+                                        move |_| {
+                                            let sample_id = sample_id.clone();
+                                            spawn(async move { cancel(samples, sample_id).await });
+                                        }
+                                    },
+                                    "Cancel"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
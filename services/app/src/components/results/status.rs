@@ -0,0 +1,115 @@
+use dioxus::prelude::*;
+use futures::StreamExt;
+use futures_timer::Delay;
+use reqwest::header::ACCEPT;
+use shared::schema::sample::{SampleStatusEvent, SampleSummary, SAMPLE_STATUS_EVENT};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::auth::{api_client, API_URL};
+
+/// How many times in a row we try to reconnect before giving up.
+const MAX_RECONNECTS: u32 = 5;
+
+/// Wait before reconnecting, doubled for every failed attempt in a row.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Subscribe to status changes of the user's samples and apply them to
+/// `samples` as they happen. Events for samples that are not in the list,
+/// e.g., one that was just uploaded, bump `refresh` so the list is fetched again.
+pub fn use_sample_status(samples: Signal<Vec<SampleSummary>>, refresh: Signal<usize>) {
+    use_future(move || async move {
+        let mut reconnects = 0;
+
+        while reconnects < MAX_RECONNECTS {
+            match watch_status(samples, refresh).await {
+                // Got at least one event, so the connection did work.
+                Ok(true) => reconnects = 0,
+                Ok(false) => reconnects += 1,
+                Err(e) => {
+                    warn!("Sample status stream failed: {}", e);
+                    reconnects += 1;
+                }
+            }
+
+            // Don't hammer the API while it is down, e.g., when it restarts.
+            Delay::new(RECONNECT_DELAY * 2u32.pow(reconnects)).await;
+        }
+
+        warn!(
+            "Gave up on sample status updates after {} attempts",
+            MAX_RECONNECTS
+        );
+    });
+}
+
+/// Read the event stream until it closes. Returns whether any event was received.
+async fn watch_status(
+    mut samples: Signal<Vec<SampleSummary>>,
+    mut refresh: Signal<usize>,
+) -> Result<bool, String> {
+    let client = api_client()?;
+
+    let response = client
+        .get(format!("{API_URL}/samples/events"))
+        .header(ACCEPT, "text/event-stream")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    info!("Listening for sample status updates...");
+    let mut received = false;
+    let mut stream = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        buffer.extend_from_slice(&chunk.map_err(|e| e.to_string())?);
+
+        // Events are separated by a blank line.
+        while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+            let block: Vec<u8> = buffer.drain(..end + 2).collect();
+            let Some(event) = parse_status_event(&String::from_utf8_lossy(&block)) else {
+                continue;
+            };
+            received = true;
+
+            let mut list = samples.write();
+            match list.iter_mut().find(|s| s.id == event.sample_id) {
+                Some(sample) => {
+                    sample.status = event.status;
+                    sample.error = event.error;
+                    sample.updated_at = event.updated_at;
+                }
+                None => refresh += 1,
+            }
+        }
+    }
+
+    Ok(received)
+}
+
+/// e.g.,
+///
+/// event: status
+/// data: {"sample_id":"abc123","status":"pending","updated_at":"2025-01-31 12:00:00"}
+///
+/// Keep-alive comments (lines starting with ':') and other events are skipped.
+fn parse_status_event(block: &str) -> Option<SampleStatusEvent> {
+    let mut name: Option<&str> = None;
+    let mut data: Vec<&str> = Vec::new();
+
+    for line in block.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            name = Some(value.trim());
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.trim_start());
+        }
+    }
+
+    if name != Some(SAMPLE_STATUS_EVENT) || data.is_empty() {
+        return None;
+    }
+
+    serde_json::from_str(&data.join("\n")).ok()
+}
@@ -0,0 +1,358 @@
+use shared::schema::trimming::AdapterSet;
+
+use crate::config::{Adapter, AdapterEnd, TrimConfig};
+
+// Sequences as in the Illumina adapter sequences document and Porechop.
+const ILLUMINA_ADAPTERS: [(&str, &str); 3] = [
+    ("Illumina TruSeq", "AGATCGGAAGAGC"),
+    ("Illumina Nextera", "CTGTCTCTTATACACATCT"),
+    ("Illumina small RNA", "TGGAATTCTCGG"),
+];
+const NANOPORE_ADAPTERS: [(&str, &str); 2] = [
+    ("ONT ligation adapter", "AATGTACTTCGTTCAGTTACGTATTGCT"),
+    (
+        "ONT rapid adapter",
+        "GTTTTCGCATTTATCGTGAAACGCTTTCGCGTTTTTCGTGCGCCGCTTCA",
+    ),
+];
+
+pub fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    sequence
+        .iter()
+        .rev()
+        .map(|base| match base.to_ascii_uppercase() {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' | b'U' => b'A',
+            b'R' => b'Y',
+            b'Y' => b'R',
+            b'K' => b'M',
+            b'M' => b'K',
+            b'B' => b'V',
+            b'V' => b'B',
+            b'D' => b'H',
+            b'H' => b'D',
+            // S, W and N are their own complement.
+            other => other,
+        })
+        .collect()
+}
+
+/// A sequence that is ligated to the start of a read, and read through into
+/// its reverse complement at the end, e.g., nanopore adapters and primers.
+pub fn both_ends(name: &str, sequence: &str) -> Vec<Adapter> {
+    let sequence = sequence.to_ascii_uppercase().into_bytes();
+
+    vec![
+        Adapter {
+            name: name.to_string(),
+            sequence: sequence.clone(),
+            end: AdapterEnd::Start,
+        },
+        Adapter {
+            name: name.to_string(),
+            sequence: reverse_complement(&sequence),
+            end: AdapterEnd::End,
+        },
+    ]
+}
+
+/// Illumina reads only run into adapters at the end.
+pub fn builtin_adapters(set: AdapterSet) -> Vec<Adapter> {
+    match set {
+        AdapterSet::None => Vec::new(),
+        AdapterSet::Illumina => ILLUMINA_ADAPTERS
+            .iter()
+            .map(|(name, sequence)| Adapter {
+                name: name.to_string(),
+                sequence: sequence.as_bytes().to_vec(),
+                end: AdapterEnd::End,
+            })
+            .collect(),
+        AdapterSet::Nanopore => NANOPORE_ADAPTERS
+            .iter()
+            .flat_map(|(name, sequence)| both_ends(name, sequence))
+            .collect(),
+    }
+}
+
+/// Whether `expected`, a IUPAC code, allows `base`. N's in the read never match.
+fn base_matches(expected: u8, base: u8) -> bool {
+    let allowed: &[u8] = match expected {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => b"",
+    };
+
+    allowed.contains(&base.to_ascii_uppercase())
+}
+
+/// Whether `adapter` and `sequence`, of the same length, differ in
+/// at most max_error_rate of their bases.
+fn is_match(adapter: &[u8], sequence: &[u8], max_error_rate: f64) -> bool {
+    let allowed = (adapter.len() as f64 * max_error_rate).floor() as usize;
+
+    adapter
+        .iter()
+        .zip(sequence)
+        .filter(|(expected, base)| !base_matches(**expected, **base))
+        .nth(allowed)
+        .is_none()
+}
+
+/// Where the adapter starts: its leftmost occurrence in the read, or else
+/// the longest prefix of it that runs off the end of the read.
+fn find_at_end(sequence: &[u8], adapter: &[u8], cfg: &TrimConfig) -> Option<usize> {
+    for start in 0..sequence.len() {
+        let overlap = adapter.len().min(sequence.len() - start);
+        if overlap < cfg.min_overlap {
+            break;
+        }
+        if is_match(
+            &adapter[..overlap],
+            &sequence[start..start + overlap],
+            cfg.max_error_rate,
+        ) {
+            return Some(start);
+        }
+    }
+
+    None
+}
+
+/// Where the adapter ends: its leftmost occurrence in the read, or else
+/// the longest suffix of it at the start of the read.
+fn find_at_start(sequence: &[u8], adapter: &[u8], cfg: &TrimConfig) -> Option<usize> {
+    for end in adapter.len()..=sequence.len() {
+        if is_match(
+            adapter,
+            &sequence[end - adapter.len()..end],
+            cfg.max_error_rate,
+        ) {
+            return Some(end);
+        }
+    }
+
+    let longest = adapter.len().saturating_sub(1).min(sequence.len());
+    for overlap in (cfg.min_overlap..=longest).rev() {
+        if is_match(
+            &adapter[adapter.len() - overlap..],
+            &sequence[..overlap],
+            cfg.max_error_rate,
+        ) {
+            return Some(overlap);
+        }
+    }
+
+    None
+}
+
+/// Trim every adapter off a read, in order. Returns the part
+/// of the read that is left, and the adapters that were found.
+pub fn trim_read<'a>(sequence: &[u8], cfg: &'a TrimConfig) -> (usize, usize, Vec<&'a str>) {
+    let (mut start, mut end) = (0, sequence.len());
+    let mut found: Vec<&str> = Vec::new();
+
+    for adapter in &cfg.adapters {
+        let window = &sequence[start..end];
+        match adapter.end {
+            AdapterEnd::Start => {
+                if let Some(adapter_end) = find_at_start(window, &adapter.sequence, cfg) {
+                    start += adapter_end;
+                    found.push(&adapter.name);
+                }
+            }
+            AdapterEnd::End => {
+                if let Some(adapter_start) = find_at_end(window, &adapter.sequence, cfg) {
+                    end = start + adapter_start;
+                    found.push(&adapter.name);
+                }
+            }
+        }
+    }
+
+    (start, end, found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRUSEQ: &str = "AGATCGGAAGAGC";
+    const PRIMER: &str = "GTGCCAGCAGCCGCGGTAA";
+    const INSERT: &str = "TTTTTTTTTTTTTTTTTTTT";
+
+    fn config(adapters: Vec<Adapter>) -> TrimConfig {
+        TrimConfig {
+            adapters,
+            max_error_rate: 0.1,
+            min_overlap: 3,
+            quality: Default::default(),
+        }
+    }
+
+    fn at_end(sequence: &str) -> Adapter {
+        Adapter {
+            name: "adapter".to_string(),
+            sequence: sequence.as_bytes().to_vec(),
+            end: AdapterEnd::End,
+        }
+    }
+
+    fn at_start(sequence: &str) -> Adapter {
+        Adapter {
+            end: AdapterEnd::Start,
+            ..at_end(sequence)
+        }
+    }
+
+    fn trim(read: &str, cfg: &TrimConfig) -> (usize, usize) {
+        let (start, end, _) = trim_read(read.as_bytes(), cfg);
+        (start, end)
+    }
+
+    /// Replace the bases at `positions` with one that doesn't match.
+    fn mutate(sequence: &str, positions: &[usize]) -> String {
+        sequence
+            .bytes()
+            .enumerate()
+            .map(|(i, base)| match (positions.contains(&i), base) {
+                (true, b'A') => 'C',
+                (true, _) => 'A',
+                (false, base) => base as char,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reverse_complement_iupac() {
+        assert_eq!(reverse_complement(b"ACGTN"), b"NACGT");
+        assert_eq!(reverse_complement(b"acgu"), b"ACGT");
+        assert_eq!(reverse_complement(b"RYKMBDHVSW"), b"WSBDHVKMRY");
+    }
+
+    #[test]
+    fn builtin_adapter_ends() {
+        let illumina = builtin_adapters(AdapterSet::Illumina);
+        assert_eq!(illumina.len(), ILLUMINA_ADAPTERS.len());
+        assert!(
+            illumina
+                .iter()
+                .all(|adapter| adapter.end == AdapterEnd::End)
+        );
+
+        let nanopore = builtin_adapters(AdapterSet::Nanopore);
+        assert_eq!(nanopore.len(), 2 * NANOPORE_ADAPTERS.len());
+        assert_eq!(nanopore[0].end, AdapterEnd::Start);
+        assert_eq!(nanopore[1].end, AdapterEnd::End);
+        assert_eq!(
+            nanopore[1].sequence,
+            reverse_complement(&nanopore[0].sequence)
+        );
+
+        assert!(builtin_adapters(AdapterSet::None).is_empty());
+    }
+
+    #[test]
+    fn adapter_at_end() {
+        let cfg = config(vec![at_end(TRUSEQ)]);
+
+        let read = format!("{INSERT}{TRUSEQ}GGGG");
+        assert_eq!(trim(&read, &cfg), (0, INSERT.len()));
+
+        // 13 bases allow a single mismatch.
+        let read = format!("{INSERT}{}", mutate(TRUSEQ, &[6]));
+        assert_eq!(trim(&read, &cfg), (0, INSERT.len()));
+        let read = format!("{INSERT}{}", mutate(TRUSEQ, &[2, 6]));
+        assert_eq!(trim(&read, &cfg), (0, read.len()));
+
+        // N's in the read are mismatches.
+        let read = format!("{INSERT}AGNTCGGNAGAGC");
+        assert_eq!(trim(&read, &cfg), (0, read.len()));
+    }
+
+    #[test]
+    fn partial_adapter_at_end() {
+        let cfg = config(vec![at_end(TRUSEQ)]);
+
+        let read = format!("{INSERT}AGATCGG");
+        assert_eq!(trim(&read, &cfg), (0, INSERT.len()));
+
+        // Shorter than min_overlap.
+        let read = format!("{INSERT}AG");
+        assert_eq!(trim(&read, &cfg), (0, read.len()));
+        let read = format!("{INSERT}AGA");
+        assert_eq!(trim(&read, &cfg), (0, INSERT.len()));
+    }
+
+    #[test]
+    fn adapter_at_start() {
+        let cfg = config(vec![at_start(PRIMER)]);
+
+        let read = format!("GG{PRIMER}{INSERT}");
+        assert_eq!(trim(&read, &cfg), (2 + PRIMER.len(), read.len()));
+
+        // 19 bases allow a single mismatch.
+        let read = format!("{}{INSERT}", mutate(PRIMER, &[0]));
+        assert_eq!(trim(&read, &cfg), (PRIMER.len(), read.len()));
+        let read = format!("{}{INSERT}", mutate(PRIMER, &[0, 10]));
+        assert_eq!(trim(&read, &cfg), (0, read.len()));
+    }
+
+    #[test]
+    fn partial_adapter_at_start() {
+        let cfg = config(vec![at_start(PRIMER)]);
+
+        let read = format!("{}{INSERT}", &PRIMER[PRIMER.len() - 8..]);
+        assert_eq!(trim(&read, &cfg), (8, read.len()));
+
+        // Shorter than min_overlap.
+        let read = format!("AA{INSERT}");
+        assert_eq!(trim(&read, &cfg), (0, read.len()));
+    }
+
+    #[test]
+    fn iupac_primer_at_both_ends() {
+        let cfg = config(both_ends("16S", "GTGYCAGCMGCCGCGGTAA"));
+
+        let reverse = String::from_utf8(reverse_complement(PRIMER.as_bytes())).unwrap();
+        let read = format!("{PRIMER}{INSERT}{reverse}");
+        let (start, end, found) = trim_read(read.as_bytes(), &cfg);
+        assert_eq!((start, end), (PRIMER.len(), PRIMER.len() + INSERT.len()));
+        assert_eq!(found, vec!["16S", "16S"]);
+
+        // Partial overlaps at both ends, with a mismatch in the one at the start.
+        let read = format!(
+            "{}{INSERT}{}",
+            mutate(&PRIMER[PRIMER.len() - 12..], &[3]),
+            &reverse[..5]
+        );
+        assert_eq!(trim(&read, &cfg), (12, 12 + INSERT.len()));
+    }
+
+    #[test]
+    fn adapter_only_read() {
+        let cfg = config(vec![at_end(TRUSEQ)]);
+
+        let (start, end, found) = trim_read(TRUSEQ.as_bytes(), &cfg);
+        assert_eq!((start, end), (0, 0));
+        assert_eq!(found, vec!["adapter"]);
+
+        let (_, _, found) = trim_read(INSERT.as_bytes(), &cfg);
+        assert!(found.is_empty());
+    }
+}
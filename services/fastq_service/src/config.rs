@@ -1,4 +1,33 @@
+use shared::database::schemas::fastq_sample::FastqSampleConfig;
+use shared::nats::WorkerConfig;
+use shared::schema::trimming::{AdapterTrimConfig, QualityTrimConfig};
+
+use crate::adapters::{both_ends, builtin_adapters};
+
+/// Number of samples that are processed at the same time, unless FASTQ_CONCURRENCY is set.
+const DEFAULT_CONCURRENCY: usize = 2;
+
+fn env_usize(name: &str) -> Option<usize> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|value| *value > 0)
+}
+
+/// Every job runs in its own temp dir, so the only limit is CPU and memory.
+/// Messages are pulled FASTQ_BATCH_SIZE at a time, by default as many as we can process.
+pub fn worker_config() -> WorkerConfig {
+    let concurrency = env_usize("FASTQ_CONCURRENCY").unwrap_or(DEFAULT_CONCURRENCY);
+
+    WorkerConfig {
+        concurrency,
+        batch_size: env_usize("FASTQ_BATCH_SIZE").unwrap_or(concurrency),
+        ..WorkerConfig::default()
+    }
+}
+
 /// Input arguments to fastq_rs.
+#[derive(Debug, Clone)]
 pub struct FilterConfig {
     pub min_len: usize,
     pub max_len: usize,
@@ -24,3 +53,70 @@ impl FilterConfig {
         }
     }
 }
+
+impl From<&FastqSampleConfig> for FilterConfig {
+    /// fastq_rs filters on mean error rate rather than phred,
+    /// so convert min_phred to a max error with 10^(-phred / 10).
+    fn from(config: &FastqSampleConfig) -> Self {
+        Self {
+            min_len: config.min_len,
+            max_len: config.max_len.unwrap_or(usize::MAX),
+            max_error: 10f64.powf(-(config.min_phred as f64) / 10.0),
+            ..Self::default()
+        }
+    }
+}
+
+/// Which end of a read an adapter is trimmed off. The adapter and
+/// everything before it (start) or after it (end) is removed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdapterEnd {
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone)]
+pub struct Adapter {
+    pub name: String,
+    /// Upper case, may contain IUPAC codes.
+    pub sequence: Vec<u8>,
+    pub end: AdapterEnd,
+}
+
+/// Adapters and primers, and then low quality bases, to trim off before filtering.
+#[derive(Debug, Clone)]
+pub struct TrimConfig {
+    pub adapters: Vec<Adapter>,
+    pub max_error_rate: f64,
+    pub min_overlap: usize,
+    pub quality: QualityTrimConfig,
+}
+
+impl TrimConfig {
+    pub fn has_adapters(&self) -> bool {
+        !self.adapters.is_empty()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.has_adapters() || self.quality.is_enabled()
+    }
+}
+
+impl From<&FastqSampleConfig> for TrimConfig {
+    /// Primers are searched for at both ends, see both_ends.
+    fn from(config: &FastqSampleConfig) -> Self {
+        let adapter_trim: &AdapterTrimConfig = &config.adapter_trim;
+
+        let mut adapters = builtin_adapters(adapter_trim.adapters);
+        for primer in &adapter_trim.primers {
+            adapters.extend(both_ends(primer, primer));
+        }
+
+        Self {
+            adapters,
+            max_error_rate: adapter_trim.max_error_rate,
+            min_overlap: adapter_trim.min_overlap,
+            quality: config.quality_trim.clone(),
+        }
+    }
+}
@@ -1,58 +1,130 @@
 use crate::errors::FastqError;
 use log::info;
+use serde_json::json;
 use shared::{
     database::schemas::{
         common::SimpleRecordId,
-        fastq_preprocess::{FastqPreprocess, FastqPreprocessData, FastqPreprocessResult},
+        fastq_preprocess::FastqPreprocessData,
+        fastq_sample::{FastqSample, FastqSampleConfig},
     },
     schema::schema::Status,
     utils::time::time_now,
 };
 use surrealdb::{Surreal, engine::remote::ws::Client};
 
+use crate::handle_message::PreprocessOutput;
+
+/// Write the preprocess result and flip the sample from pending to done, in a
+/// single transaction. Returns false, and writes nothing, if the sample is no
+/// longer pending, e.g., because another delivery of the message already finished it.
+/// Every run gets its own fastq_preprocess, so earlier runs are kept.
 pub async fn write_to_db(
-    fastq_preprocess_result: FastqPreprocessResult,
-    url: String,
-    runtime: usize,
+    output: PreprocessOutput,
+    config: FastqSampleConfig,
     fastq_sample_id: SimpleRecordId,
+    run_id: &str,
     db: &Surreal<Client>,
-) -> Result<(), FastqError> {
+) -> Result<bool, FastqError> {
     // Define our preprocess struct to write to database.
-    let fastq_preprocess = FastqPreprocess {
-        id: None,
-        data: FastqPreprocessData {
-            status: Status::Done,
-            url: url,
-            runtime: runtime,
-            result: fastq_preprocess_result,
-            created_at: time_now(),
-            updated_at: time_now(),
-        },
+    let fastq_preprocess = FastqPreprocessData {
+        status: Status::Done,
+        url: output.url,
+        mate_url: output.mate_url,
+        runtime: output.runtime,
+        result: output.result,
+        config,
+        report_url: output.report_url,
+        created_at: time_now(),
+        updated_at: time_now(),
     };
 
-    // Write actual data to database, capture response for relation.
-    let preprocess_response: Option<FastqPreprocess> = db
-        .create("fastq_preprocess")
-        .content(fastq_preprocess)
+    let mut response = db
+        .query(
+            "BEGIN TRANSACTION;
+             LET $sample = UPDATE $fastq_sample
+                SET status = $status, error = NONE, updated_at = $updated_at
+                WHERE status IN $sources AND (run_id = NONE OR run_id = $run_id) RETURN AFTER;
+             IF array::len($sample) > 0 {
+                LET $preprocess = CREATE ONLY fastq_preprocess CONTENT $fastq_preprocess;
+                LET $preprocess_id = $preprocess.id;
+                RELATE $fastq_sample->processed->$preprocess_id;
+             };
+             RETURN array::len($sample) > 0;
+             COMMIT TRANSACTION;",
+        )
+        .bind(("fastq_sample", fastq_sample_id.surrealdb_id()?))
+        .bind(json!({
+            "status": Status::Done,
+            "sources": Status::sources(Status::Done),
+            "run_id": run_id,
+            "updated_at": time_now(),
+            "fastq_preprocess": fastq_preprocess,
+        }))
         .await?;
-    info!("Preprocess response: {:?}", preprocess_response);
 
-    // Add relation between fastq sample and fastq preprocess.
-    let relation_response = db
-        .query("RELATE $fastq_sample->processed->$fastq_process")
+    // The result of the transaction is the last statement.
+    let last = response.num_statements() - 1;
+    let written: Option<bool> = response.take(last)?;
+    info!(
+        "Preprocess result for {} written: {:?}",
+        fastq_sample_id.formatted_id(),
+        written
+    );
+
+    Ok(written.unwrap_or(false))
+}
+
+/// Move the sample to `status`, if the current status allows it. Starting
+/// (pending) counts as an attempt, and `error` is only kept for the error status.
+/// Only for the message of the current run, see FastqSampleData::run_id.
+/// Returns false if the transition was not allowed.
+pub async fn transition_sample_status(
+    fastq_sample_id: &SimpleRecordId,
+    status: Status,
+    error: Option<String>,
+    run_id: &str,
+    db: &Surreal<Client>,
+) -> Result<bool, FastqError> {
+    let attempt: usize = match status {
+        Status::Pending => 1,
+        _ => 0,
+    };
+
+    // The status check and the update are a single statement, so that
+    // two deliveries of the same message can't both get through.
+    let mut response = db
+        .query(
+            "UPDATE $fastq_sample
+             SET status = $status, error = $error, attempts += $attempt, updated_at = $updated_at
+             WHERE status IN $sources AND (run_id = NONE OR run_id = $run_id) RETURN AFTER",
+        )
+        .bind(("fastq_sample", fastq_sample_id.surrealdb_id()?))
+        .bind(json!({
+            "status": status,
+            "error": error,
+            "attempt": attempt,
+            "sources": Status::sources(status),
+            "run_id": run_id,
+            "updated_at": time_now(),
+        }))
+        .await?;
+
+    let updated: Vec<FastqSample> = response.take(0)?;
+
+    Ok(!updated.is_empty())
+}
+
+/// Name of the sample, for the report. None if the sample is gone.
+pub async fn sample_name(
+    fastq_sample_id: &SimpleRecordId,
+    db: &Surreal<Client>,
+) -> Result<Option<String>, FastqError> {
+    let mut response = db
+        .query("SELECT VALUE name FROM $fastq_sample")
         .bind(("fastq_sample", fastq_sample_id.surrealdb_id()?))
-        .bind((
-            "fastq_preprocess",
-            preprocess_response
-                .unwrap()
-                .id
-                .as_ref()
-                .unwrap()
-                .surrealdb_id()?,
-        ))
         .await?;
 
-    info!("Relation response: {:?}", relation_response);
+    let names: Vec<String> = response.take(0)?;
 
-    Ok(())
+    Ok(names.into_iter().next())
 }
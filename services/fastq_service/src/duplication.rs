@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use shared::database::schemas::fastq_qc::{
+    DuplicationLevel, DuplicationMetrics, OverrepresentedSequence,
+};
+
+use crate::adapters::reverse_complement;
+use crate::records::FastqRecord;
+
+/// Sequences seen after this many distinct ones are not tracked, which bounds memory.
+const MAX_TRACKED: usize = 100_000;
+
+/// Reads longer than TRUNCATE_ABOVE are compared on their first TRUNCATE_TO
+/// bases, so that sequencing errors at the end don't hide duplicates.
+const TRUNCATE_ABOVE: usize = 75;
+const TRUNCATE_TO: usize = 50;
+
+/// Sequences in more than this fraction of the reads are overrepresented.
+const MIN_OVERREPRESENTED_FRACTION: f64 = 0.001;
+const MAX_OVERREPRESENTED: usize = 20;
+
+/// A sequence matches a contaminant if they share this many bases in a row,
+/// or if the shorter of the two is in the other.
+const MIN_CONTAMINANT_MATCH: usize = 20;
+
+const CONTAMINANTS: &str = include_str!("contaminants.tsv");
+
+/// Lowest number of occurrences of every level.
+const DUPLICATION_LEVELS: [(usize, &str); 16] = [
+    (1, "1"),
+    (2, "2"),
+    (3, "3"),
+    (4, "4"),
+    (5, "5"),
+    (6, "6"),
+    (7, "7"),
+    (8, "8"),
+    (9, "9"),
+    (10, ">10"),
+    (50, ">50"),
+    (100, ">100"),
+    (500, ">500"),
+    (1_000, ">1k"),
+    (5_000, ">5k"),
+    (10_000, ">10k"),
+];
+
+/// Name and sequence of every bundled contaminant.
+fn contaminants() -> Vec<(&'static str, &'static [u8])> {
+    CONTAMINANTS
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(name, sequence)| (name, sequence.trim().as_bytes()))
+        .collect()
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty()
+        && haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
+
+fn shares_bases(sequence: &[u8], contaminant: &[u8]) -> bool {
+    if sequence.len() < MIN_CONTAMINANT_MATCH || contaminant.len() < MIN_CONTAMINANT_MATCH {
+        return match sequence.len() < contaminant.len() {
+            true => contains(contaminant, sequence),
+            false => contains(sequence, contaminant),
+        };
+    }
+
+    sequence
+        .windows(MIN_CONTAMINANT_MATCH)
+        .any(|window| contains(contaminant, window))
+}
+
+/// The first contaminant that the sequence, or its reverse complement, matches.
+fn contaminant_hit(sequence: &[u8], contaminants: &[(&str, &[u8])]) -> Option<String> {
+    let sequence = sequence.to_ascii_uppercase();
+    let reverse = reverse_complement(&sequence);
+
+    contaminants
+        .iter()
+        .find(|(_, contaminant)| {
+            shares_bases(&sequence, contaminant) || shares_bases(&reverse, contaminant)
+        })
+        .map(|(name, _)| name.to_string())
+}
+
+/// Counts sequences over every read of a file, turned into DuplicationMetrics at the end.
+pub struct DuplicationCounter {
+    counts: HashMap<Vec<u8>, usize>,
+    num_reads: usize,
+    tracked_reads: usize,
+}
+
+impl DuplicationCounter {
+    pub fn new() -> Self {
+        Self {
+            counts: HashMap::new(),
+            num_reads: 0,
+            tracked_reads: 0,
+        }
+    }
+
+    pub fn add(&mut self, record: &FastqRecord) {
+        self.num_reads += 1;
+
+        let sequence = record[1].as_bytes();
+        let sequence = match sequence.len() > TRUNCATE_ABOVE {
+            true => &sequence[..TRUNCATE_TO],
+            false => sequence,
+        };
+
+        if let Some(count) = self.counts.get_mut(sequence) {
+            *count += 1;
+            self.tracked_reads += 1;
+        } else if self.counts.len() < MAX_TRACKED {
+            self.counts.insert(sequence.to_vec(), 1);
+            self.tracked_reads += 1;
+        }
+    }
+
+    pub fn finish(self) -> DuplicationMetrics {
+        let percent = |count: usize, total: usize| match total {
+            0 => 0.0,
+            _ => 100.0 * count as f64 / total as f64,
+        };
+
+        // Distinct sequences and reads per level.
+        let mut levels = [(0usize, 0usize); DUPLICATION_LEVELS.len()];
+        for count in self.counts.values() {
+            let level = DUPLICATION_LEVELS
+                .iter()
+                .rposition(|(lowest, _)| count >= lowest)
+                .unwrap_or(0);
+            levels[level].0 += 1;
+            levels[level].1 += count;
+        }
+
+        let distinct = self.counts.len();
+        let duplication_levels = DUPLICATION_LEVELS
+            .iter()
+            .zip(levels)
+            .map(|((_, label), (sequences, reads))| DuplicationLevel {
+                level: label.to_string(),
+                percent_deduplicated: percent(sequences, distinct),
+                percent_total: percent(reads, self.tracked_reads),
+            })
+            .collect();
+
+        let min_count = self.num_reads as f64 * MIN_OVERREPRESENTED_FRACTION;
+        let mut frequent: Vec<(&Vec<u8>, &usize)> = self
+            .counts
+            .iter()
+            .filter(|(_, count)| **count > 1 && **count as f64 > min_count)
+            .collect();
+        frequent.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        let contaminants = contaminants();
+        let overrepresented = frequent
+            .into_iter()
+            .take(MAX_OVERREPRESENTED)
+            .map(|(sequence, count)| OverrepresentedSequence {
+                sequence: String::from_utf8_lossy(sequence).to_string(),
+                count: *count,
+                percentage: percent(*count, self.num_reads),
+                possible_source: contaminant_hit(sequence, &contaminants),
+            })
+            .collect();
+
+        DuplicationMetrics {
+            tracked_reads: self.tracked_reads,
+            distinct_sequences: distinct,
+            percent_remaining_if_deduplicated: percent(distinct, self.tracked_reads),
+            duplication_levels,
+            overrepresented,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(sequence: &str) -> FastqRecord {
+        [
+            "@read".to_string(),
+            sequence.to_string(),
+            "+".to_string(),
+            "I".repeat(sequence.len()),
+        ]
+    }
+
+    /// A distinct sequence for every i.
+    fn sequence(i: usize) -> String {
+        (0..12)
+            .map(|b| ['A', 'C', 'G', 'T'][(i >> (2 * b)) % 4])
+            .collect()
+    }
+
+    fn count(sequences: &[(String, usize)]) -> DuplicationMetrics {
+        let mut counter = DuplicationCounter::new();
+        for (sequence, times) in sequences {
+            for _ in 0..*times {
+                counter.add(&record(sequence));
+            }
+        }
+        counter.finish()
+    }
+
+    fn level<'a>(metrics: &'a DuplicationMetrics, label: &str) -> &'a DuplicationLevel {
+        metrics
+            .duplication_levels
+            .iter()
+            .find(|level| level.level == label)
+            .unwrap()
+    }
+
+    #[test]
+    fn duplication_levels() {
+        // 2 sequences once, 1 twice, and 1 each 10, 49 and 50 times.
+        let metrics = count(&[
+            (sequence(0), 1),
+            (sequence(1), 1),
+            (sequence(2), 2),
+            (sequence(3), 10),
+            (sequence(4), 49),
+            (sequence(5), 50),
+        ]);
+
+        assert_eq!(metrics.tracked_reads, 113);
+        assert_eq!(metrics.distinct_sequences, 6);
+        assert_eq!(metrics.duplication_levels.len(), DUPLICATION_LEVELS.len());
+        assert_eq!(
+            metrics.percent_remaining_if_deduplicated,
+            100.0 * 6.0 / 113.0
+        );
+
+        let expected = [
+            ("1", 2, 2),
+            ("2", 1, 2),
+            ("3", 0, 0),
+            ("9", 0, 0),
+            (">10", 2, 59),
+            (">50", 1, 50),
+            (">100", 0, 0),
+        ];
+        for (label, sequences, reads) in expected {
+            let level = level(&metrics, label);
+            assert_eq!(
+                level.percent_deduplicated,
+                100.0 * sequences as f64 / 6.0,
+                "{label}"
+            );
+            assert_eq!(level.percent_total, 100.0 * reads as f64 / 113.0, "{label}");
+        }
+    }
+
+    #[test]
+    fn highest_level() {
+        let metrics = count(&[(sequence(0), 20_000)]);
+
+        assert_eq!(level(&metrics, ">10k").percent_deduplicated, 100.0);
+        assert_eq!(level(&metrics, ">5k").percent_deduplicated, 0.0);
+    }
+
+    #[test]
+    fn empty() {
+        let metrics = DuplicationCounter::new().finish();
+
+        assert_eq!(metrics.tracked_reads, 0);
+        assert_eq!(metrics.percent_remaining_if_deduplicated, 0.0);
+        assert!(
+            metrics
+                .duplication_levels
+                .iter()
+                .all(|level| level.percent_deduplicated == 0.0 && level.percent_total == 0.0)
+        );
+        assert!(metrics.overrepresented.is_empty());
+    }
+
+    #[test]
+    fn long_reads_are_truncated() {
+        let prefix = "ACGT".repeat(20);
+        let metrics = count(&[
+            (format!("{}A", &prefix[..TRUNCATE_ABOVE]), 1),
+            (format!("{}C", &prefix[..TRUNCATE_ABOVE]), 1),
+            (format!("{}G", &prefix[..TRUNCATE_ABOVE - 1]), 1),
+            (format!("{}T", &prefix[..TRUNCATE_ABOVE - 1]), 1),
+        ]);
+
+        // The first two are the same in their first TRUNCATE_TO bases.
+        assert_eq!(metrics.distinct_sequences, 3);
+    }
+
+    #[test]
+    fn untracked_sequences() {
+        let mut sequences: Vec<(String, usize)> =
+            (0..MAX_TRACKED + 1).map(|i| (sequence(i), 1)).collect();
+        sequences.push((sequence(0), 1));
+        sequences.push((sequence(MAX_TRACKED), 1));
+        let metrics = count(&sequences);
+
+        assert_eq!(metrics.distinct_sequences, MAX_TRACKED);
+        assert_eq!(metrics.tracked_reads, MAX_TRACKED + 1);
+    }
+
+    #[test]
+    fn overrepresented_sequences() {
+        let adapter = "AGATCGGAAGAGCACACGTCTGAACTCCAGTCAC";
+        let reverse = String::from_utf8(reverse_complement(adapter.as_bytes())).unwrap();
+
+        let mut sequences: Vec<(String, usize)> = (0..2_000).map(|i| (sequence(i), 1)).collect();
+        sequences.push((adapter.to_string(), 5));
+        sequences.push((reverse.to_lowercase(), 3));
+        sequences.push(("ACGTTGCA".to_string(), 4));
+        // A single read is never overrepresented.
+        sequences.push(("T".repeat(30), 1));
+        let metrics = count(&sequences);
+
+        let overrepresented: Vec<(&str, usize, Option<&str>)> = metrics
+            .overrepresented
+            .iter()
+            .map(|o| (o.sequence.as_str(), o.count, o.possible_source.as_deref()))
+            .collect();
+        assert_eq!(
+            overrepresented,
+            vec![
+                (adapter, 5, Some("Illumina TruSeq Adapter, Read 1")),
+                ("ACGTTGCA", 4, None),
+                (
+                    reverse.to_lowercase().as_str(),
+                    3,
+                    Some("Illumina TruSeq Adapter, Read 1")
+                ),
+            ]
+        );
+        assert_eq!(metrics.overrepresented[0].percentage, 100.0 * 5.0 / 2_013.0);
+    }
+}
@@ -10,12 +10,6 @@ pub enum FastqError {
     #[error("Serialization error")]
     SerializationError(String),
 
-    #[error("Failed to fetch messages from consumer.")]
-    GetConsumerMessagesError(String),
-
-    #[error("Failed to acknowledge message")]
-    MessageAckError(String),
-
     #[error("Failed to initialize logger")]
     LoggerInitializationError(String),
 
@@ -25,6 +19,18 @@ pub enum FastqError {
     #[error("Failed to run fastq_rs")]
     FastqRsError(String),
 
+    #[error("Invalid fastq file")]
+    InvalidFastqError(String),
+
+    #[error("Paired-end mates are out of sync")]
+    MateMismatchError(String),
+
+    #[error("Job was cancelled")]
+    CancelledError(String),
+
+    #[error("Processing task failed")]
+    TaskError(String),
+
     #[error("Failed to write to database")]
     DatabaseWriteError(String),
 
@@ -61,3 +67,9 @@ impl From<surrealdb::Error> for FastqError {
         self::FastqError::DatabaseWriteError(err.to_string())
     }
 }
+
+impl From<tokio::task::JoinError> for FastqError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        self::FastqError::TaskError(err.to_string())
+    }
+}
@@ -1,19 +1,45 @@
 use crate::errors::FastqError;
 use std::path::{Path, PathBuf};
 use std::time;
+use tokio::task::spawn_blocking;
+use tokio_util::sync::CancellationToken;
 
-use crate::config::FilterConfig;
+use crate::config::{FilterConfig, TrimConfig};
+use crate::pairs::sync_mates;
+use crate::qc::{fastq_qc, fastq_qc_with_duplication};
+use crate::trim::{TrimMetrics, trim_reads};
 use fastq_rs::{filter::fastq_filter, stats::fastq_stats};
 use log::info;
 use minio::s3::Client;
 use shared::database::schemas::fastq_preprocess::{FastqMetrics, FastqPreprocessResult};
+use shared::database::schemas::fastq_sample::FastqSampleConfig;
 use shared::file_path;
 use shared::minio::minio_upload_file;
 use shared::utils::file::file_name;
 
-fn fastq_rs_filter(fastq: &Path, outfile: &PathBuf) -> Result<(), FastqError> {
-    let cfg = FilterConfig::default();
+/// Bucket for the filtered files, under <sample id>/<file name>.
+pub const PROCESSED_BUCKET: &str = "file-upload-processed";
+pub const FILTERED_R1: &str = "trimmed.fastq.gz";
+pub const FILTERED_R2: &str = "trimmed_R2.fastq.gz";
+pub const REPORT: &str = "report.html";
 
+/// Every file that preprocessing a sample might upload.
+pub fn processed_keys(key_prefix: &str) -> Vec<String> {
+    [FILTERED_R1, FILTERED_R2, REPORT]
+        .iter()
+        .map(|file| format!("{}/{}", key_prefix, file))
+        .collect()
+}
+
+/// Blocking work can't be interrupted, so check between steps if the job was cancelled.
+fn check_cancelled(cancel: &CancellationToken) -> Result<(), FastqError> {
+    match cancel.is_cancelled() {
+        true => Err(FastqError::CancelledError("Job was cancelled".into())),
+        false => Ok(()),
+    }
+}
+
+fn fastq_rs_filter(fastq: &Path, outfile: &PathBuf, cfg: &FilterConfig) -> Result<(), FastqError> {
     let filter_result = fastq_filter(
         Some(fastq.to_path_buf()),
         cfg.min_len,
@@ -42,43 +68,241 @@ fn fastq_rs_stats(fastq: &Path, outfile: PathBuf) -> Result<(), FastqError> {
     }
 }
 
-/// We can streamline this later on.
-/// * Break into separate functions?
-/// * Consider using a temp dir that is removed once going out of scope.
+/// Trim `fastq` into workdir/read_trimmed/`name`, if there is anything
+/// to trim. Returns the file to filter.
+fn trim_step(
+    workdir: &Path,
+    fastq: &Path,
+    name: &str,
+    cfg: &TrimConfig,
+) -> Result<(PathBuf, TrimMetrics), FastqError> {
+    if !cfg.is_enabled() {
+        return Ok((fastq.to_path_buf(), TrimMetrics::default()));
+    }
+
+    info!("Trimming reads of {}...", name);
+    let trimmed = file_path!(workdir, "read_trimmed", name);
+    let metrics = trim_reads(fastq, &trimmed, cfg)?;
+
+    Ok((trimmed, metrics))
+}
+
+/// Output of preprocessing, before the filtered files are uploaded.
+struct Preprocessed {
+    result: FastqPreprocessResult,
+    runtime: usize,
+    filtered: PathBuf,
+    mate_filtered: Option<PathBuf>,
+}
+
+/// Result of a run, with the urls of the uploaded files.
+pub struct PreprocessOutput {
+    pub result: FastqPreprocessResult,
+    pub runtime: usize,
+    pub url: String,
+    pub mate_url: Option<String>,
+    /// Set once the report is uploaded, which happens after the filtered files.
+    pub report_url: Option<String>,
+}
+
+/// Preprocess a sample in `workdir` and upload the filtered files under
+/// `key_prefix`, so that concurrent jobs never share a file or a key.
+///
+/// `mate` is R2 of paired-end samples, in which case `fastq` is R1.
+/// Filters and trims with the thresholds that the user chose for this sample.
 pub async fn handle_message(
+    workdir: &Path,
     fastq: &Path,
+    mate: Option<&Path>,
+    config: &FastqSampleConfig,
     minio_client: &Client,
-) -> Result<(FastqPreprocessResult, usize, String), FastqError> {
+    key_prefix: &str,
+    cancel: &CancellationToken,
+) -> Result<PreprocessOutput, FastqError> {
+    let cfg = FilterConfig::from(config);
+    info!("Filtering with {:?}", cfg);
+    let trim_cfg = TrimConfig::from(config);
+
+    // fastq_rs is CPU bound and blocks, so run it on the blocking thread pool.
+    // Otherwise it would stall other jobs and the heartbeats of this one.
+    let workdir = workdir.to_path_buf();
+    let fastq = fastq.to_path_buf();
+    let mate = mate.map(Path::to_path_buf);
+    let blocking_cancel = cancel.clone();
+    let preprocessed = spawn_blocking(move || match mate {
+        Some(mate) => preprocess_pair(&workdir, &fastq, &mate, &cfg, &trim_cfg, &blocking_cancel),
+        None => preprocess(&workdir, &fastq, &cfg, &trim_cfg, &blocking_cancel),
+    })
+    .await??;
+    check_cancelled(cancel)?;
+
+    // Upload filtered files to MinIO.
+    let key = format!(
+        "{}/{}",
+        key_prefix,
+        file_name(preprocessed.filtered.clone())
+    );
+    let minio_url =
+        minio_upload_file(minio_client, PROCESSED_BUCKET, &key, preprocessed.filtered).await?;
+
+    let mate_minio_url = match preprocessed.mate_filtered {
+        Some(mate_filtered) => {
+            let key = format!("{}/{}", key_prefix, file_name(mate_filtered.clone()));
+            Some(minio_upload_file(minio_client, PROCESSED_BUCKET, &key, mate_filtered).await?)
+        }
+        None => None,
+    };
+
+    Ok(PreprocessOutput {
+        result: preprocessed.result,
+        runtime: preprocessed.runtime,
+        url: minio_url,
+        mate_url: mate_minio_url,
+        report_url: None,
+    })
+}
+
+fn preprocess(
+    workdir: &Path,
+    fastq: &Path,
+    cfg: &FilterConfig,
+    trim_cfg: &TrimConfig,
+    cancel: &CancellationToken,
+) -> Result<Preprocessed, FastqError> {
     let start = time::Instant::now();
 
     // Stats for raw fastq.
     info!("Running stats on raw fastq...");
-    let json_raw = file_path!("/tmp", "raw", "stats.json");
+    let json_raw = file_path!(workdir, "raw", "stats.json");
     fastq_rs_stats(fastq, json_raw.clone())?;
+    check_cancelled(cancel)?;
+    let (qc_raw, duplication) = fastq_qc_with_duplication(fastq)?;
+
+    // Trim adapters, primers and low quality bases.
+    check_cancelled(cancel)?;
+    let (to_filter, trim_metrics) = trim_step(workdir, fastq, "R1.fastq.gz", trim_cfg)?;
 
     // Filter fastq.
+    check_cancelled(cancel)?;
     info!("Running fastq filter...");
-    let filtered_fastq = file_path!("/tmp", "trimmed", "trimmed.fastq.gz");
-    fastq_rs_filter(fastq, &filtered_fastq)?;
+    let filtered_fastq = file_path!(workdir, "trimmed", FILTERED_R1);
+    fastq_rs_filter(&to_filter, &filtered_fastq, cfg)?;
 
     // Stats for filtered fastq.
+    check_cancelled(cancel)?;
     info!("Running stats on filtered fastq...");
-    let json_trimmed = file_path!("/tmp", "trimmed", "stats.json");
+    let json_trimmed = file_path!(workdir, "trimmed", "stats.json");
     fastq_rs_stats(&filtered_fastq, json_trimmed.clone())?;
+    check_cancelled(cancel)?;
+    let qc_filtered = fastq_qc(&filtered_fastq)?;
 
     let elapsed = start.elapsed().as_secs();
 
-    // Upload file to MinIO
-    let key = file_name(filtered_fastq.clone());
-
-    let minio_url =
-        minio_upload_file(minio_client, "file-upload-processed", &key, filtered_fastq).await?;
-
     // Construct FastqResponse
     let fastq_preprocess_result = FastqPreprocessResult {
         metrics_raw: FastqMetrics::from_json(json_raw)?,
         metrics_filtered: FastqMetrics::from_json(json_trimmed)?,
+        mate_metrics_raw: None,
+        mate_metrics_filtered: None,
+        pair_metrics: None,
+        adapter_trim: trim_metrics.adapter,
+        mate_adapter_trim: None,
+        quality_trim: trim_metrics.quality,
+        mate_quality_trim: None,
+        qc_raw: Some(qc_raw),
+        qc_filtered: Some(qc_filtered),
+        mate_qc_raw: None,
+        mate_qc_filtered: None,
+        duplication: Some(duplication),
+        mate_duplication: None,
+    };
+
+    Ok(Preprocessed {
+        result: fastq_preprocess_result,
+        runtime: elapsed as usize,
+        filtered: filtered_fastq,
+        mate_filtered: None,
+    })
+}
+
+/// Filter both mates, then drop pairs where only one of the mates passed.
+fn preprocess_pair(
+    workdir: &Path,
+    r1: &Path,
+    r2: &Path,
+    cfg: &FilterConfig,
+    trim_cfg: &TrimConfig,
+    cancel: &CancellationToken,
+) -> Result<Preprocessed, FastqError> {
+    let start = time::Instant::now();
+
+    // Stats for raw mates.
+    info!("Running stats on raw mates...");
+    let json_raw_r1 = file_path!(workdir, "raw", "stats.json");
+    let json_raw_r2 = file_path!(workdir, "raw", "stats_R2.json");
+    fastq_rs_stats(r1, json_raw_r1.clone())?;
+    fastq_rs_stats(r2, json_raw_r2.clone())?;
+    check_cancelled(cancel)?;
+    let (qc_raw_r1, duplication_r1) = fastq_qc_with_duplication(r1)?;
+    let (qc_raw_r2, duplication_r2) = fastq_qc_with_duplication(r2)?;
+
+    // Trim adapters, primers and low quality bases, which keeps the order of the reads.
+    check_cancelled(cancel)?;
+    let (to_filter_r1, trim_metrics_r1) = trim_step(workdir, r1, "R1.fastq.gz", trim_cfg)?;
+    check_cancelled(cancel)?;
+    let (to_filter_r2, trim_metrics_r2) = trim_step(workdir, r2, "R2.fastq.gz", trim_cfg)?;
+
+    // Filter mates independently...
+    check_cancelled(cancel)?;
+    info!("Running fastq filter on mates...");
+    let unpaired_r1 = file_path!(workdir, "unpaired", "unpaired_R1.fastq.gz");
+    let unpaired_r2 = file_path!(workdir, "unpaired", "unpaired_R2.fastq.gz");
+    fastq_rs_filter(&to_filter_r1, &unpaired_r1, cfg)?;
+    check_cancelled(cancel)?;
+    fastq_rs_filter(&to_filter_r2, &unpaired_r2, cfg)?;
+
+    // ...and only keep pairs where both passed.
+    check_cancelled(cancel)?;
+    info!("Syncing filtered mates...");
+    let filtered_r1 = file_path!(workdir, "trimmed", FILTERED_R1);
+    let filtered_r2 = file_path!(workdir, "trimmed", FILTERED_R2);
+    let pair_metrics = sync_mates(r1, &unpaired_r1, &unpaired_r2, &filtered_r1, &filtered_r2)?;
+
+    // Stats for filtered mates.
+    check_cancelled(cancel)?;
+    info!("Running stats on filtered mates...");
+    let json_trimmed_r1 = file_path!(workdir, "trimmed", "stats.json");
+    let json_trimmed_r2 = file_path!(workdir, "trimmed", "stats_R2.json");
+    fastq_rs_stats(&filtered_r1, json_trimmed_r1.clone())?;
+    fastq_rs_stats(&filtered_r2, json_trimmed_r2.clone())?;
+    check_cancelled(cancel)?;
+    let qc_filtered_r1 = fastq_qc(&filtered_r1)?;
+    let qc_filtered_r2 = fastq_qc(&filtered_r2)?;
+
+    let elapsed = start.elapsed().as_secs();
+
+    let fastq_preprocess_result = FastqPreprocessResult {
+        metrics_raw: FastqMetrics::from_json(json_raw_r1)?,
+        metrics_filtered: FastqMetrics::from_json(json_trimmed_r1)?,
+        mate_metrics_raw: Some(FastqMetrics::from_json(json_raw_r2)?),
+        mate_metrics_filtered: Some(FastqMetrics::from_json(json_trimmed_r2)?),
+        pair_metrics: Some(pair_metrics),
+        adapter_trim: trim_metrics_r1.adapter,
+        mate_adapter_trim: trim_metrics_r2.adapter,
+        quality_trim: trim_metrics_r1.quality,
+        mate_quality_trim: trim_metrics_r2.quality,
+        qc_raw: Some(qc_raw_r1),
+        qc_filtered: Some(qc_filtered_r1),
+        mate_qc_raw: Some(qc_raw_r2),
+        mate_qc_filtered: Some(qc_filtered_r2),
+        duplication: Some(duplication_r1),
+        mate_duplication: Some(duplication_r2),
     };
 
-    Ok((fastq_preprocess_result, elapsed as usize, minio_url))
+    Ok(Preprocessed {
+        result: fastq_preprocess_result,
+        runtime: elapsed as usize,
+        filtered: filtered_r1,
+        mate_filtered: Some(filtered_r2),
+    })
 }
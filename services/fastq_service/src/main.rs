@@ -1,28 +1,33 @@
-use std::path::PathBuf;
-
-use async_nats::jetstream::AckKind;
-use futures::StreamExt;
-use log::{error, info};
+use log::info;
 use shared::database::connect_db;
-use shared::nats::schema::fastq_service::FastqMessage;
+use shared::nats::run_worker;
 use simple_logger::SimpleLogger;
 use tokio;
 
-use shared::minio::{connect_minio, minio_download};
+use shared::minio::connect_minio;
 use shared::nats::connect_nats;
-use shared::nats::streams::{config::StreamType, stream::get_consumer_from_stream_type};
 
 mod handle_message;
-use handle_message::handle_message;
 
-use crate::database::write_to_db;
+use crate::config::worker_config;
 use crate::errors::FastqError;
+use crate::worker::FastqWorker;
 
+mod adapters;
 mod config;
 mod database;
+mod duplication;
 mod errors;
-
-/// Entrypoint - check for messages that are put on the NATS consumer queue.
+mod pairs;
+mod qc;
+mod quality;
+mod records;
+mod report;
+mod svg;
+mod trim;
+mod worker;
+
+/// Entrypoint - process the messages that are put on the NATS consumer queue.
 #[tokio::main]
 async fn main() -> Result<(), FastqError> {
     info!("Inside fastq service.");
@@ -34,69 +39,15 @@ async fn main() -> Result<(), FastqError> {
     info!("Setting up connections...");
     let jetstream = connect_nats().await?;
     let db = connect_db(3).await?;
-    let consumer = get_consumer_from_stream_type(&jetstream, StreamType::FileUpload).await?;
     let minio_client = connect_minio().await?;
 
-    info!("Getting messages...");
-    let mut messages = consumer
-        .messages()
-        .await
-        .map_err(|err| FastqError::GetConsumerMessagesError(err.to_string()))?;
-
-    info!("Ready to accept messages...");
-    while let Some(message) = messages.next().await {
-        let message = match message {
-            Ok(message) => {
-                info!("Got message!");
-                message
-            }
-            Err(e) => {
-                error!("Got invalid message: {:?}", e);
-                continue;
-            }
-        };
-
-        // Parse payload bytes.
-        // Consider error handling with continue statement.
-        let nats_message = serde_json::from_slice::<FastqMessage>(&message.payload)?;
-        info!("{:?}", nats_message);
-
-        // Download file.
-        // We can change this later on to provide a directory and the function
-        // returns the file as outdir/<file_base_name>.
-        let file_path = PathBuf::from("test.fastq.gz");
-        minio_download(&minio_client, &nats_message.url, &file_path).await?;
-
-        // Do actual work...
-        // Later on, return filtered file so we can upload to MinIO.
-        info!("Running fastq_rs filter...");
-        let handle_result = handle_message(&file_path, &minio_client).await;
-
-        // Acknowledge message...
-        match handle_result {
-            Ok((fastq_preprocess_result, runtime, url)) => {
-                // Write to database.
+    let worker = FastqWorker {
+        db,
+        minio_client,
+        jetstream: jetstream.clone(),
+    };
 
-                write_to_db(
-                    fastq_preprocess_result,
-                    url,
-                    runtime,
-                    nats_message.fastq_sample_id,
-                    &db,
-                )
-                .await?;
-                message.ack().await.expect("Failed to ack message.")
-            }
-            // Something
-            Err(e) => {
-                error!("{:?}", e);
-                message
-                    .ack_with(AckKind::Nak(None))
-                    .await
-                    .map_err(|err| FastqError::MessageAckError(err.to_string()))?;
-            }
-        }
-    }
+    run_worker(jetstream, worker, worker_config()).await?;
 
     Ok(())
 }
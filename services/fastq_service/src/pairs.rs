@@ -0,0 +1,176 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use flate2::{Compression, write::GzEncoder};
+use log::info;
+use shared::database::schemas::fastq_preprocess::PairMetrics;
+
+use crate::errors::FastqError;
+use crate::records::{FastqRecord, next_record, open_fastq, write_record};
+
+/// Mates share a read name, except for an optional /1 or /2 suffix.
+fn read_name(record: &FastqRecord) -> &str {
+    let name = record[0]
+        .trim_start_matches('@')
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+
+    name.strip_suffix("/1")
+        .or(name.strip_suffix("/2"))
+        .unwrap_or(name)
+}
+
+/// R1 and R2 are filtered independently, so a read can pass in one mate
+/// and fail in the other. Keep only pairs where both mates passed, so that
+/// the filtered files stay in sync.
+///
+/// Filtering preserves the order of the reads, so we walk the raw R1 file
+/// and check if the next filtered read of each mate is the current read.
+/// This way, we never hold more than a single record per file in memory.
+pub fn sync_mates(
+    raw: &Path,
+    filtered_r1: &Path,
+    filtered_r2: &Path,
+    out_r1: &Path,
+    out_r2: &Path,
+) -> Result<PairMetrics, FastqError> {
+    let mut raw = open_fastq(raw)?;
+    let mut filtered_r1 = open_fastq(filtered_r1)?;
+    let mut filtered_r2 = open_fastq(filtered_r2)?;
+
+    let mut writer_r1 = GzEncoder::new(
+        BufWriter::new(File::create(out_r1)?),
+        Compression::default(),
+    );
+    let mut writer_r2 = GzEncoder::new(
+        BufWriter::new(File::create(out_r2)?),
+        Compression::default(),
+    );
+
+    let mut metrics = PairMetrics {
+        num_pairs_raw: 0,
+        num_pairs_filtered: 0,
+        r1_only: 0,
+        r2_only: 0,
+    };
+
+    let mut next_r1 = next_record(&mut filtered_r1)?;
+    let mut next_r2 = next_record(&mut filtered_r2)?;
+
+    while let Some(raw_record) = next_record(&mut raw)? {
+        metrics.num_pairs_raw += 1;
+        let name = read_name(&raw_record);
+
+        let passed_r1 = next_r1.as_ref().is_some_and(|r| read_name(r) == name);
+        let passed_r2 = next_r2.as_ref().is_some_and(|r| read_name(r) == name);
+
+        match (passed_r1, passed_r2) {
+            (true, true) => {
+                write_record(&mut writer_r1, next_r1.as_ref().unwrap())?;
+                write_record(&mut writer_r2, next_r2.as_ref().unwrap())?;
+                metrics.num_pairs_filtered += 1;
+            }
+            (true, false) => metrics.r1_only += 1,
+            (false, true) => metrics.r2_only += 1,
+            (false, false) => {}
+        }
+
+        if passed_r1 {
+            next_r1 = next_record(&mut filtered_r1)?;
+        }
+        if passed_r2 {
+            next_r2 = next_record(&mut filtered_r2)?;
+        }
+    }
+
+    // Leftovers mean that the mates are not in the same order as R1.
+    if let Some(record) = next_r1.or(next_r2) {
+        return Err(FastqError::MateMismatchError(format!(
+            "Read {} does not match any read in R1",
+            read_name(&record)
+        )));
+    }
+
+    writer_r1.finish()?.flush()?;
+    writer_r2.finish()?.flush()?;
+
+    info!("Pair metrics: {:?}", metrics);
+
+    Ok(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fastq(names: &[&str]) -> String {
+        names
+            .iter()
+            .map(|name| format!("@{}\nACGT\n+\nIIII\n", name))
+            .collect()
+    }
+
+    fn read_names(path: &Path) -> Vec<String> {
+        let mut reader = open_fastq(path).unwrap();
+        std::iter::from_fn(|| next_record(&mut reader).unwrap())
+            .map(|record| read_name(&record).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn keep_pairs_where_both_mates_passed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+
+        std::fs::write(
+            path("raw.fastq"),
+            fastq(&["read1/1", "read2/1", "read3/1", "read4/1", "read5/1"]),
+        )
+        .unwrap();
+        // read2 only passed in R1, read3 only in R2 and read5 in neither.
+        std::fs::write(path("r1.fastq"), fastq(&["read1/1", "read2/1", "read4/1"])).unwrap();
+        std::fs::write(
+            path("r2.fastq"),
+            fastq(&["read1 2:N:0:1", "read3 2:N:0:1", "read4 2:N:0:1"]),
+        )
+        .unwrap();
+
+        let metrics = sync_mates(
+            &path("raw.fastq"),
+            &path("r1.fastq"),
+            &path("r2.fastq"),
+            &path("out_r1.fastq.gz"),
+            &path("out_r2.fastq.gz"),
+        )
+        .unwrap();
+
+        assert_eq!(metrics.num_pairs_raw, 5);
+        assert_eq!(metrics.num_pairs_filtered, 2);
+        assert_eq!(metrics.r1_only, 1);
+        assert_eq!(metrics.r2_only, 1);
+        assert_eq!(read_names(&path("out_r1.fastq.gz")), vec!["read1", "read4"]);
+        assert_eq!(read_names(&path("out_r2.fastq.gz")), vec!["read1", "read4"]);
+    }
+
+    #[test]
+    fn mates_out_of_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+
+        std::fs::write(path("raw.fastq"), fastq(&["read1", "read2", "read3"])).unwrap();
+        std::fs::write(path("r1.fastq"), fastq(&["read2", "read1"])).unwrap();
+        std::fs::write(path("r2.fastq"), fastq(&["read1", "read2"])).unwrap();
+
+        let result = sync_mates(
+            &path("raw.fastq"),
+            &path("r1.fastq"),
+            &path("r2.fastq"),
+            &path("out_r1.fastq.gz"),
+            &path("out_r2.fastq.gz"),
+        );
+
+        assert!(matches!(result, Err(FastqError::MateMismatchError(_))));
+    }
+}
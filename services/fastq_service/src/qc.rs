@@ -0,0 +1,442 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use log::info;
+use shared::database::schemas::fastq_qc::{
+    BaseComposition, DuplicationMetrics, FastqQc, HistogramBin, NContent, PositionBin,
+    QualityDistribution,
+};
+
+use crate::duplication::DuplicationCounter;
+use crate::errors::FastqError;
+use crate::records::{FastqRecord, PHRED_OFFSET, next_record, open_fastq};
+
+const NUM_PHREDS: usize = 94;
+
+/// (first position, width) of every range of position bins, 0-based.
+/// See shared::database::schemas::fastq_qc.
+const POSITION_BINS: [(usize, usize); 4] = [(0, 1), (100, 10), (1_000, 100), (10_000, 1_000)];
+
+/// Number of bins in the i'th range of POSITION_BINS, the last one is open ended.
+fn bins_in_range(i: usize) -> usize {
+    let (from, width) = POSITION_BINS[i];
+    POSITION_BINS
+        .get(i + 1)
+        .map_or(usize::MAX, |(next, _)| (next - from) / width)
+}
+
+/// Bin of a 0-based position.
+fn bin_index(position: usize) -> usize {
+    let mut index = 0;
+    for (i, (from, width)) in POSITION_BINS.iter().enumerate() {
+        let bins = bins_in_range(i);
+        if position < from.saturating_add(bins.saturating_mul(*width)) {
+            return index + (position - from) / width;
+        }
+        index += bins;
+    }
+
+    index
+}
+
+/// 1-based positions of a bin, up to the longest read.
+fn bin_positions(index: usize, max_len: usize) -> PositionBin {
+    let mut index = index;
+    for (i, (from, width)) in POSITION_BINS.iter().enumerate() {
+        let bins = bins_in_range(i);
+        if index < bins {
+            let start = from + index * width;
+            return PositionBin {
+                start: start + 1,
+                end: (start + width).min(max_len),
+            };
+        }
+        index -= bins;
+    }
+
+    unreachable!("The last range of position bins is open ended")
+}
+
+struct PositionCounts {
+    /// Number of bases per phred.
+    qualities: [usize; NUM_PHREDS],
+    /// A, C, G, T and N.
+    bases: [usize; 5],
+}
+
+impl PositionCounts {
+    fn new() -> Self {
+        Self {
+            qualities: [0; NUM_PHREDS],
+            bases: [0; 5],
+        }
+    }
+
+    /// Smallest phred such that at least `fraction` of the bases have that phred or lower.
+    fn percentile(&self, total: usize, fraction: f64) -> u8 {
+        let target = ((fraction * total as f64).ceil() as usize).max(1);
+
+        let mut seen = 0;
+        for (phred, count) in self.qualities.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return phred as u8;
+            }
+        }
+
+        (NUM_PHREDS - 1) as u8
+    }
+
+    fn quality_distribution(&self, position: PositionBin) -> QualityDistribution {
+        let total: usize = self.qualities.iter().sum();
+        let sum: usize = self
+            .qualities
+            .iter()
+            .enumerate()
+            .map(|(phred, count)| phred * count)
+            .sum();
+
+        QualityDistribution {
+            position,
+            mean: match total {
+                0 => 0.0,
+                _ => sum as f64 / total as f64,
+            },
+            median: self.percentile(total, 0.5),
+            lower_quartile: self.percentile(total, 0.25),
+            upper_quartile: self.percentile(total, 0.75),
+            percentile_10: self.percentile(total, 0.1),
+            percentile_90: self.percentile(total, 0.9),
+        }
+    }
+
+    fn base_composition(&self, position: PositionBin) -> BaseComposition {
+        let called: usize = self.bases[..4].iter().sum();
+        let fraction = |count: usize| match called {
+            0 => 0.0,
+            _ => count as f64 / called as f64,
+        };
+
+        BaseComposition {
+            position,
+            a: fraction(self.bases[0]),
+            c: fraction(self.bases[1]),
+            g: fraction(self.bases[2]),
+            t: fraction(self.bases[3]),
+        }
+    }
+
+    fn n_content(&self, position: PositionBin) -> NContent {
+        let total: usize = self.bases.iter().sum();
+
+        NContent {
+            position,
+            n: match total {
+                0 => 0.0,
+                _ => self.bases[4] as f64 / total as f64,
+            },
+        }
+    }
+}
+
+/// Counts over every read of a file, turned into FastqQc at the end.
+struct QcCounter {
+    lengths: BTreeMap<usize, usize>,
+    mean_qualities: BTreeMap<usize, usize>,
+    gc: [usize; 101],
+    positions: Vec<PositionCounts>,
+    max_len: usize,
+}
+
+impl QcCounter {
+    fn new() -> Self {
+        Self {
+            lengths: BTreeMap::new(),
+            mean_qualities: BTreeMap::new(),
+            gc: [0; 101],
+            positions: Vec::new(),
+            max_len: 0,
+        }
+    }
+
+    fn add(&mut self, record: &FastqRecord) {
+        let sequence = record[1].as_bytes();
+        let qualities = record[3].as_bytes();
+
+        *self.lengths.entry(sequence.len()).or_default() += 1;
+        self.max_len = self.max_len.max(sequence.len());
+
+        let mut phred_sum = 0;
+        let mut gc: usize = 0;
+        let mut called = 0;
+        for (position, (base, quality)) in sequence.iter().zip(qualities).enumerate() {
+            let phred = (quality.saturating_sub(PHRED_OFFSET) as usize).min(NUM_PHREDS - 1);
+            phred_sum += phred;
+
+            let base = match base.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => 4,
+            };
+            if base < 4 {
+                called += 1;
+            }
+            if base == 1 || base == 2 {
+                gc += 1;
+            }
+
+            let bin = bin_index(position);
+            if self.positions.len() <= bin {
+                self.positions.resize_with(bin + 1, PositionCounts::new);
+            }
+            self.positions[bin].qualities[phred] += 1;
+            self.positions[bin].bases[base] += 1;
+        }
+
+        if !qualities.is_empty() {
+            *self
+                .mean_qualities
+                .entry(phred_sum / qualities.len())
+                .or_default() += 1;
+        }
+
+        // Reads with only N's have no GC content.
+        if let Some(percentage) = (gc * 100 + called / 2).checked_div(called) {
+            self.gc[percentage] += 1;
+        }
+    }
+
+    fn finish(self) -> FastqQc {
+        let histogram = |counts: BTreeMap<usize, usize>| {
+            counts
+                .into_iter()
+                .map(|(value, count)| HistogramBin { value, count })
+                .collect()
+        };
+
+        let mut qc = FastqQc {
+            length_histogram: histogram(self.lengths),
+            mean_quality_histogram: histogram(self.mean_qualities),
+            gc_histogram: self
+                .gc
+                .iter()
+                .enumerate()
+                .map(|(percentage, count)| HistogramBin {
+                    value: percentage,
+                    count: *count,
+                })
+                .collect(),
+            position_quality: Vec::new(),
+            base_composition: Vec::new(),
+            n_content: Vec::new(),
+        };
+
+        for (index, counts) in self.positions.iter().enumerate() {
+            let position = bin_positions(index, self.max_len);
+            qc.position_quality
+                .push(counts.quality_distribution(position.clone()));
+            qc.base_composition
+                .push(counts.base_composition(position.clone()));
+            qc.n_content.push(counts.n_content(position));
+        }
+
+        qc
+    }
+}
+
+/// Per-read metrics of a fastq file, gzipped or not, in a single pass.
+pub fn fastq_qc(fastq: &Path) -> Result<FastqQc, FastqError> {
+    let mut reader = open_fastq(fastq)?;
+    let mut counter = QcCounter::new();
+
+    while let Some(record) = next_record(&mut reader)? {
+        counter.add(&record);
+    }
+
+    info!(
+        "QC of {:?}: {} lengths, {} position bins",
+        fastq,
+        counter.lengths.len(),
+        counter.positions.len()
+    );
+
+    Ok(counter.finish())
+}
+
+/// Same as fastq_qc, and also how often sequences occur, in the same pass.
+pub fn fastq_qc_with_duplication(
+    fastq: &Path,
+) -> Result<(FastqQc, DuplicationMetrics), FastqError> {
+    let mut reader = open_fastq(fastq)?;
+    let mut counter = QcCounter::new();
+    let mut duplication = DuplicationCounter::new();
+
+    while let Some(record) = next_record(&mut reader)? {
+        counter.add(&record);
+        duplication.add(&record);
+    }
+
+    let duplication = duplication.finish();
+    info!(
+        "Duplication of {:?}: {:.1}% left if deduplicated, {} overrepresented sequences",
+        fastq,
+        duplication.percent_remaining_if_deduplicated,
+        duplication.overrepresented.len()
+    );
+
+    Ok((counter.finish(), duplication))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(sequence: &str, qualities: &str) -> FastqRecord {
+        [
+            "@read".to_string(),
+            sequence.to_string(),
+            "+".to_string(),
+            qualities.to_string(),
+        ]
+    }
+
+    fn bin(start: usize, end: usize) -> PositionBin {
+        PositionBin { start, end }
+    }
+
+    #[test]
+    fn position_bins() {
+        let expected = [
+            (0, 0),
+            (99, 99),
+            (100, 100),
+            (109, 100),
+            (110, 101),
+            (999, 189),
+            (1_000, 190),
+            (1_099, 190),
+            (9_999, 279),
+            (10_000, 280),
+            (10_999, 280),
+            (11_000, 281),
+            (1_000_000, 1_270),
+        ];
+        for (position, index) in expected {
+            assert_eq!(bin_index(position), index, "position {position}");
+        }
+
+        let max_len = 2_000_000;
+        assert_eq!(bin_positions(0, max_len), bin(1, 1));
+        assert_eq!(bin_positions(99, max_len), bin(100, 100));
+        assert_eq!(bin_positions(100, max_len), bin(101, 110));
+        assert_eq!(bin_positions(190, max_len), bin(1_001, 1_100));
+        assert_eq!(bin_positions(281, max_len), bin(11_001, 12_000));
+    }
+
+    #[test]
+    fn position_bins_round_trip() {
+        for position in (0..20_000).step_by(7) {
+            let PositionBin { start, end } = bin_positions(bin_index(position), usize::MAX);
+            assert!(
+                (start..=end).contains(&(position + 1)),
+                "position {position}"
+            );
+        }
+    }
+
+    #[test]
+    fn last_bin_ends_at_longest_read() {
+        assert_eq!(bin_positions(100, 105), bin(101, 105));
+        assert_eq!(bin_positions(5, 105), bin(6, 6));
+    }
+
+    #[test]
+    fn read_histograms() {
+        let mut counter = QcCounter::new();
+        // Mean phreds 40, 20 and 30, GC 50%, 75% and 50%.
+        counter.add(&record("ACGT", "IIII"));
+        counter.add(&record("GGCAN", "55555"));
+        counter.add(&record("ACGT", "II55"));
+        // No called bases, so no GC content.
+        counter.add(&record("NN", "!!"));
+        let qc = counter.finish();
+
+        assert_eq!(
+            qc.length_histogram,
+            vec![
+                HistogramBin { value: 2, count: 1 },
+                HistogramBin { value: 4, count: 2 },
+                HistogramBin { value: 5, count: 1 },
+            ]
+        );
+        assert_eq!(
+            qc.mean_quality_histogram,
+            vec![
+                HistogramBin { value: 0, count: 1 },
+                HistogramBin {
+                    value: 20,
+                    count: 1
+                },
+                HistogramBin {
+                    value: 30,
+                    count: 1
+                },
+                HistogramBin {
+                    value: 40,
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(qc.gc_histogram.len(), 101);
+        assert_eq!(qc.gc_histogram[50].count, 2);
+        assert_eq!(qc.gc_histogram[75].count, 1);
+        assert_eq!(
+            qc.gc_histogram.iter().map(|bin| bin.count).sum::<usize>(),
+            3
+        );
+    }
+
+    #[test]
+    fn per_position_metrics() {
+        let mut counter = QcCounter::new();
+        counter.add(&record("AC", "I5"));
+        counter.add(&record("AN", "5!"));
+        counter.add(&record("G", "+"));
+        let qc = counter.finish();
+
+        assert_eq!(qc.position_quality.len(), 2);
+        let first = &qc.position_quality[0];
+        assert_eq!(first.position, bin(1, 1));
+        assert_eq!(first.mean, (40.0 + 20.0 + 10.0) / 3.0);
+        assert_eq!(
+            (first.percentile_10, first.lower_quartile, first.median),
+            (10, 10, 20)
+        );
+        assert_eq!((first.upper_quartile, first.percentile_90), (40, 40));
+
+        let second = &qc.base_composition[1];
+        assert_eq!(second.position, bin(2, 2));
+        assert_eq!(
+            (second.a, second.c, second.g, second.t),
+            (0.0, 1.0, 0.0, 0.0)
+        );
+        assert_eq!(qc.n_content[1].n, 0.5);
+        assert_eq!(qc.base_composition[0].a, 2.0 / 3.0);
+    }
+
+    #[test]
+    fn binned_positions() {
+        let mut counter = QcCounter::new();
+        let sequence = "A".repeat(105);
+        let qualities = format!("{}{}", "I".repeat(100), "+".repeat(5));
+        counter.add(&record(&sequence, &qualities));
+        let qc = counter.finish();
+
+        assert_eq!(qc.position_quality.len(), 101);
+        let last = &qc.position_quality[100];
+        assert_eq!(last.position, bin(101, 105));
+        assert_eq!(last.mean, 10.0);
+    }
+}
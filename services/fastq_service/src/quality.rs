@@ -0,0 +1,132 @@
+use shared::schema::trimming::QualityTrimConfig;
+
+use crate::records::PHRED_OFFSET;
+
+/// Apply the steps of `cfg` to the qualities of a read. Returns the
+/// part of the read that is left, which is empty if start == end.
+pub fn quality_trim(qualities: &[u8], cfg: &QualityTrimConfig) -> (usize, usize) {
+    let phred = |i: usize| qualities[i].saturating_sub(PHRED_OFFSET) as usize;
+    let (mut start, mut end) = (0, qualities.len());
+
+    if let Some(head_crop) = cfg.head_crop {
+        start = head_crop.min(end);
+    }
+
+    if let Some(min_phred) = cfg.leading {
+        while start < end && phred(start) < min_phred {
+            start += 1;
+        }
+    }
+
+    if let Some(min_phred) = cfg.trailing {
+        while end > start && phred(end - 1) < min_phred {
+            end -= 1;
+        }
+    }
+
+    // Cut at the start of the first window with a low mean. Reads
+    // shorter than the window are judged as a whole.
+    if let Some(window) = &cfg.sliding_window {
+        let size = window.size.min(end - start);
+        let min_sum = window.min_phred * size;
+
+        let mut window_start = start;
+        let mut sum: usize = (start..start + size).map(phred).sum();
+        loop {
+            if sum < min_sum {
+                end = window_start;
+                break;
+            }
+            if window_start + size >= end {
+                break;
+            }
+            sum = sum + phred(window_start + size) - phred(window_start);
+            window_start += 1;
+        }
+    }
+
+    if let Some(crop) = cfg.crop {
+        end = end.min(start + crop);
+    }
+
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::schema::trimming::SlidingWindow;
+
+    // '+' is phred 10, '5' 20 and 'I' 40.
+
+    fn window(size: usize, min_phred: usize) -> QualityTrimConfig {
+        QualityTrimConfig {
+            sliding_window: Some(SlidingWindow { size, min_phred }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn disabled() {
+        let cfg = QualityTrimConfig::default();
+        assert_eq!(quality_trim(b"+++", &cfg), (0, 3));
+        assert_eq!(quality_trim(b"", &window(4, 20)), (0, 0));
+    }
+
+    #[test]
+    fn leading_and_trailing() {
+        let cfg = QualityTrimConfig {
+            leading: Some(20),
+            trailing: Some(20),
+            ..Default::default()
+        };
+
+        assert_eq!(quality_trim(b"++5II+5++", &cfg), (2, 7));
+        assert_eq!(quality_trim(b"IIII", &cfg), (0, 4));
+        assert_eq!(quality_trim(b"++++", &cfg), (4, 4));
+    }
+
+    #[test]
+    fn sliding_window() {
+        // The window at 4 has a mean of exactly 20, the one at 5 is below it.
+        assert_eq!(quality_trim(b"IIIII+++II", &window(3, 20)), (0, 5));
+        assert_eq!(quality_trim(b"IIIIIIII", &window(3, 20)), (0, 8));
+        assert_eq!(quality_trim(b"+++IIIII", &window(3, 20)), (0, 0));
+    }
+
+    #[test]
+    fn sliding_window_longer_than_read() {
+        assert_eq!(quality_trim(b"I+", &window(4, 20)), (0, 2));
+        assert_eq!(quality_trim(b"5+", &window(4, 20)), (0, 0));
+    }
+
+    #[test]
+    fn sliding_window_after_leading() {
+        let cfg = QualityTrimConfig {
+            leading: Some(20),
+            ..window(2, 30)
+        };
+
+        assert_eq!(quality_trim(b"++IIII5+I", &cfg), (2, 6));
+    }
+
+    #[test]
+    fn head_crop_and_crop() {
+        let cfg = QualityTrimConfig {
+            head_crop: Some(2),
+            crop: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(quality_trim(b"IIIIIIII", &cfg), (2, 5));
+        assert_eq!(quality_trim(b"III", &cfg), (2, 3));
+        assert_eq!(quality_trim(b"I", &cfg), (1, 1));
+
+        // Cropping happens after everything else.
+        let cfg = QualityTrimConfig {
+            trailing: Some(20),
+            crop: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(quality_trim(b"IIII++", &cfg), (0, 3));
+    }
+}
@@ -0,0 +1,52 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+
+use crate::errors::FastqError;
+
+/// Sanger/Illumina 1.8+ quality encoding.
+pub const PHRED_OFFSET: u8 = 33;
+
+/// Header, sequence, separator and quality line, without line endings.
+pub type FastqRecord = [String; 4];
+
+/// Open a fastq file, gzipped or not.
+pub fn open_fastq(path: &Path) -> Result<Box<dyn BufRead>, FastqError> {
+    let mut magic = [0u8; 2];
+    let is_gzip = File::open(path)?.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
+
+    let file = File::open(path)?;
+    match is_gzip {
+        true => Ok(Box::new(BufReader::new(MultiGzDecoder::new(file)))),
+        false => Ok(Box::new(BufReader::new(file))),
+    }
+}
+
+pub fn next_record(reader: &mut dyn BufRead) -> Result<Option<FastqRecord>, FastqError> {
+    let mut record: FastqRecord = Default::default();
+
+    for (i, line) in record.iter_mut().enumerate() {
+        if reader.read_line(line)? == 0 {
+            return match i {
+                0 => Ok(None),
+                _ => Err(FastqError::InvalidFastqError(
+                    "File ends in the middle of a record".into(),
+                )),
+            };
+        }
+        line.truncate(line.trim_end().len());
+    }
+
+    Ok(Some(record))
+}
+
+pub fn write_record(writer: &mut impl Write, record: &FastqRecord) -> Result<(), FastqError> {
+    for line in record {
+        writer.write_all(line.as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
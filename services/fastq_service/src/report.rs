@@ -0,0 +1,746 @@
+use std::fmt::Write;
+
+use bytes::Bytes;
+use minio::s3::{Client, segmented_bytes::SegmentedBytes};
+use shared::database::schemas::fastq_preprocess::{
+    AdapterTrimMetrics, FastqMetrics, FastqPreprocessResult, QualityTrimMetrics,
+};
+use shared::database::schemas::fastq_qc::{
+    BaseComposition, DuplicationMetrics, FastqQc, HistogramBin,
+};
+use shared::database::schemas::fastq_sample::FastqSampleConfig;
+use shared::minio::minio_upload_bytes_as;
+use shared::utils::time::time_now;
+
+use crate::errors::FastqError;
+use crate::handle_message::{PROCESSED_BUCKET, REPORT};
+use crate::svg::{Band, Chart, Series, escape};
+
+// A standalone HTML report of a preprocessing run, with inline SVG plots,
+// that can be opened without an account, e.g., from an email attachment.
+
+const RAW_COLOR: &str = "#d62728";
+const FILTERED_COLOR: &str = "#1f77b4";
+
+// Thresholds as in FastQC, for which a check fails.
+const MIN_LOWER_QUARTILE: u8 = 5;
+const MIN_MEDIAN: u8 = 20;
+const MIN_MODAL_MEAN_PHRED: usize = 20;
+const MAX_N_CONTENT: f64 = 0.2;
+const MIN_PERCENT_REMAINING: f64 = 50.0;
+const MAX_OVERREPRESENTED_PERCENT: f64 = 1.0;
+/// Not in FastQC, this is the share of reads that filtering may drop.
+const MIN_PERCENT_KEPT: f64 = 50.0;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 1000px; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: left; }
+th { background: #f3f3f3; }
+.pass { color: #2ca02c; font-weight: bold; }
+.fail { color: #d62728; font-weight: bold; }
+.plots { display: flex; flex-wrap: wrap; gap: 1em; }
+code { word-break: break-all; }
+";
+
+/// Name of a row of the metrics table, and how to get its value.
+type MetricRow = (&'static str, fn(&FastqMetrics) -> String);
+/// Title and x label of a histogram plot, and its bins.
+type HistogramPlot = (
+    &'static str,
+    &'static str,
+    fn(&FastqQc) -> &Vec<HistogramBin>,
+);
+/// Name and color of a base, and its share at a position.
+type BaseSeries = (&'static str, &'static str, fn(&BaseComposition) -> f64);
+
+/// Everything about one mate, or the reads of a single-end sample.
+struct Mate<'a> {
+    name: &'a str,
+    raw: &'a FastqMetrics,
+    filtered: &'a FastqMetrics,
+    qc_raw: Option<&'a FastqQc>,
+    qc_filtered: Option<&'a FastqQc>,
+    duplication: Option<&'a DuplicationMetrics>,
+    adapter_trim: Option<&'a AdapterTrimMetrics>,
+    quality_trim: Option<&'a QualityTrimMetrics>,
+}
+
+fn mates(result: &FastqPreprocessResult) -> Vec<Mate<'_>> {
+    let paired = result.mate_metrics_raw.is_some();
+
+    let mut mates = vec![Mate {
+        name: if paired { "R1" } else { "Reads" },
+        raw: &result.metrics_raw,
+        filtered: &result.metrics_filtered,
+        qc_raw: result.qc_raw.as_ref(),
+        qc_filtered: result.qc_filtered.as_ref(),
+        duplication: result.duplication.as_ref(),
+        adapter_trim: result.adapter_trim.as_ref(),
+        quality_trim: result.quality_trim.as_ref(),
+    }];
+
+    if let (Some(raw), Some(filtered)) = (&result.mate_metrics_raw, &result.mate_metrics_filtered) {
+        mates.push(Mate {
+            name: "R2",
+            raw,
+            filtered,
+            qc_raw: result.mate_qc_raw.as_ref(),
+            qc_filtered: result.mate_qc_filtered.as_ref(),
+            duplication: result.mate_duplication.as_ref(),
+            adapter_trim: result.mate_adapter_trim.as_ref(),
+            quality_trim: result.mate_quality_trim.as_ref(),
+        });
+    }
+
+    mates
+}
+
+struct Flag {
+    check: &'static str,
+    passed: bool,
+    detail: String,
+}
+
+fn percent(count: usize, total: usize) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => 100.0 * count as f64 / total as f64,
+    }
+}
+
+/// Checks on the filtered reads, except for duplication, which is on the raw reads.
+/// Checks without data, e.g., for runs from before QC was added, are left out.
+fn flags(mate: &Mate) -> Vec<Flag> {
+    let kept = percent(mate.filtered.num_reads, mate.raw.num_reads);
+    let mut flags = vec![Flag {
+        check: "Reads kept",
+        passed: kept >= MIN_PERCENT_KEPT,
+        detail: format!("{:.1}% of the reads passed", kept),
+    }];
+
+    if let Some(qc) = mate.qc_filtered {
+        let worst = qc.position_quality.iter().find(|position| {
+            position.lower_quartile < MIN_LOWER_QUARTILE || position.median < MIN_MEDIAN
+        });
+        flags.push(Flag {
+            check: "Per base quality",
+            passed: worst.is_none(),
+            detail: match worst {
+                Some(position) => format!(
+                    "Median {} and lower quartile {} at {}-{}",
+                    position.median,
+                    position.lower_quartile,
+                    position.position.start,
+                    position.position.end
+                ),
+                None => format!(
+                    "Median at least {} and lower quartile at least {} everywhere",
+                    MIN_MEDIAN, MIN_LOWER_QUARTILE
+                ),
+            },
+        });
+
+        let modal = qc
+            .mean_quality_histogram
+            .iter()
+            .max_by_key(|bin| bin.count)
+            .map(|bin| bin.value);
+        if let Some(modal) = modal {
+            flags.push(Flag {
+                check: "Per read quality",
+                passed: modal >= MIN_MODAL_MEAN_PHRED,
+                detail: format!("Most reads have a mean phred of {}", modal),
+            });
+        }
+
+        let max_n = qc
+            .n_content
+            .iter()
+            .map(|position| position.n)
+            .fold(0.0f64, f64::max);
+        flags.push(Flag {
+            check: "N content",
+            passed: max_n <= MAX_N_CONTENT,
+            detail: format!("At most {:.1}% N at any position", max_n * 100.0),
+        });
+    }
+
+    if let Some(duplication) = mate.duplication {
+        flags.push(Flag {
+            check: "Duplication",
+            passed: duplication.percent_remaining_if_deduplicated >= MIN_PERCENT_REMAINING,
+            detail: format!(
+                "{:.1}% of the reads left if deduplicated",
+                duplication.percent_remaining_if_deduplicated
+            ),
+        });
+
+        let top = duplication
+            .overrepresented
+            .first()
+            .map_or(0.0, |sequence| sequence.percentage);
+        flags.push(Flag {
+            check: "Overrepresented sequences",
+            passed: top <= MAX_OVERREPRESENTED_PERCENT,
+            detail: format!("The most frequent sequence is {:.2}% of the reads", top),
+        });
+    }
+
+    flags
+}
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map_or("-".into(), |value| value.to_string())
+}
+
+fn histogram_points(bins: &[HistogramBin], total: usize) -> Vec<(f64, f64)> {
+    bins.iter()
+        .map(|bin| (bin.value as f64, percent(bin.count, total)))
+        .collect()
+}
+
+fn summary_section(html: &mut String, mates: &[Mate]) {
+    html.push_str("<h2>Summary</h2><table><tr><th>Check</th>");
+    for mate in mates {
+        let _ = write!(html, "<th>{}</th>", mate.name);
+    }
+    html.push_str("</tr>");
+
+    let flags: Vec<Vec<Flag>> = mates.iter().map(flags).collect();
+    for (i, flag) in flags[0].iter().enumerate() {
+        let _ = write!(html, "<tr><td>{}</td>", flag.check);
+        for mate_flags in &flags {
+            match mate_flags.get(i) {
+                Some(flag) => {
+                    let (class, label) = match flag.passed {
+                        true => ("pass", "Pass"),
+                        false => ("fail", "Fail"),
+                    };
+                    let _ = write!(
+                        html,
+                        r#"<td><span class="{}">{}</span> {}</td>"#,
+                        class,
+                        label,
+                        escape(&flag.detail)
+                    );
+                }
+                None => html.push_str("<td>-</td>"),
+            }
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+}
+
+fn metrics_section(html: &mut String, mates: &[Mate], result: &FastqPreprocessResult) {
+    html.push_str("<h2>Metrics</h2><table><tr><th>Metric</th>");
+    for mate in mates {
+        let _ = write!(
+            html,
+            "<th>{} raw</th><th>{} filtered</th>",
+            mate.name, mate.name
+        );
+    }
+    html.push_str("</tr>");
+
+    let rows: [MetricRow; 5] = [
+        ("Reads", |metrics| metrics.num_reads.to_string()),
+        ("Bases", |metrics| metrics.num_bases.to_string()),
+        ("Mean length", |metrics| metrics.mean_len.to_string()),
+        ("Mean phred", |metrics| metrics.mean_phred.to_string()),
+        ("Mean error", |metrics| format!("{:.4}", metrics.mean_error)),
+    ];
+    for (name, value) in rows {
+        let _ = write!(html, "<tr><td>{}</td>", name);
+        for mate in mates {
+            let _ = write!(
+                html,
+                "<td>{}</td><td>{}</td>",
+                value(mate.raw),
+                value(mate.filtered)
+            );
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+
+    if let Some(pairs) = &result.pair_metrics {
+        let _ = write!(
+            html,
+            "<table><tr><th>Pairs raw</th><th>Pairs kept</th><th>Only R1 passed</th><th>Only R2 passed</th></tr>
+             <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr></table>",
+            pairs.num_pairs_raw, pairs.num_pairs_filtered, pairs.r1_only, pairs.r2_only
+        );
+    }
+
+    html.push_str(
+        "<table><tr><th>Trimming</th><th>Reads</th><th>Trimmed reads</th><th>Trimmed bases</th><th>Emptied reads</th></tr>",
+    );
+    for mate in mates {
+        let steps = [
+            (
+                "adapters and primers",
+                mate.adapter_trim.map(|metrics| {
+                    (
+                        metrics.num_reads,
+                        metrics.trimmed_reads,
+                        metrics.trimmed_bases,
+                        metrics.empty_reads,
+                    )
+                }),
+            ),
+            (
+                "quality",
+                mate.quality_trim.map(|metrics| {
+                    (
+                        metrics.num_reads,
+                        metrics.trimmed_reads,
+                        metrics.trimmed_bases,
+                        metrics.empty_reads,
+                    )
+                }),
+            ),
+        ];
+        for (step, metrics) in steps {
+            let Some((reads, trimmed_reads, trimmed_bases, empty_reads)) = metrics else {
+                continue;
+            };
+            let _ = write!(
+                html,
+                "<tr><td>{} {}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                mate.name, step, reads, trimmed_reads, trimmed_bases, empty_reads
+            );
+        }
+    }
+    html.push_str("</table>");
+}
+
+fn config_section(html: &mut String, config: &FastqSampleConfig) {
+    let adapter_trim = &config.adapter_trim;
+    let quality_trim = &config.quality_trim;
+
+    let rows =
+        [
+            ("Min length", config.min_len.to_string()),
+            ("Max length", or_dash(config.max_len)),
+            ("Min phred", config.min_phred.to_string()),
+            ("Adapters", format!("{:?}", adapter_trim.adapters)),
+            ("Primers", adapter_trim.primers.join(", ")),
+            (
+                "Max adapter error rate",
+                adapter_trim.max_error_rate.to_string(),
+            ),
+            ("Min adapter overlap", adapter_trim.min_overlap.to_string()),
+            ("Head crop", or_dash(quality_trim.head_crop)),
+            ("Leading min phred", or_dash(quality_trim.leading)),
+            ("Trailing min phred", or_dash(quality_trim.trailing)),
+            (
+                "Sliding window",
+                or_dash(quality_trim.sliding_window.as_ref().map(|window| {
+                    format!("{} bases, min phred {}", window.size, window.min_phred)
+                })),
+            ),
+            ("Crop", or_dash(quality_trim.crop)),
+        ];
+
+    html.push_str("<h2>Configuration</h2><table>");
+    for (name, value) in rows {
+        let _ = write!(
+            html,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            name,
+            escape(&value)
+        );
+    }
+    html.push_str("</table>");
+}
+
+fn plots_section(html: &mut String, mate: &Mate) {
+    let _ = write!(html, r#"<h2>{}</h2><div class="plots">"#, mate.name);
+
+    let qcs: Vec<(&str, &str, &FastqQc)> = [
+        ("raw", RAW_COLOR, mate.qc_raw),
+        ("filtered", FILTERED_COLOR, mate.qc_filtered),
+    ]
+    .into_iter()
+    .filter_map(|(name, color, qc)| qc.map(|qc| (name, color, qc)))
+    .collect();
+
+    // The lower quartile in a lighter shade of the median.
+    let quality_series = [
+        ("median raw", RAW_COLOR, mate.qc_raw, false),
+        ("lower quartile raw", "#ff9896", mate.qc_raw, true),
+        ("median filtered", FILTERED_COLOR, mate.qc_filtered, false),
+        ("lower quartile filtered", "#aec7e8", mate.qc_filtered, true),
+    ];
+
+    let mut chart = Chart::new("Quality per position", "Position (bp)", "Phred");
+    chart.y_range = Some((0.0, 41.0));
+    chart.bands = vec![
+        Band {
+            y0: 0.0,
+            y1: 20.0,
+            color: "#f7d9d9",
+        },
+        Band {
+            y0: 20.0,
+            y1: 28.0,
+            color: "#f7edd9",
+        },
+        Band {
+            y0: 28.0,
+            y1: 41.0,
+            color: "#dff2df",
+        },
+    ];
+    let series: Vec<Series> = quality_series
+        .into_iter()
+        .filter_map(|(name, color, qc, lower_quartile)| {
+            qc.map(|qc| Series {
+                name,
+                color,
+                points: qc
+                    .position_quality
+                    .iter()
+                    .map(|position| {
+                        let phred = match lower_quartile {
+                            true => position.lower_quartile,
+                            false => position.median,
+                        };
+                        (position.position.start as f64, phred as f64)
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+    html.push_str(&chart.render(&series));
+
+    let histograms: [HistogramPlot; 3] = [
+        ("Read length", "Length (bp)", |qc| &qc.length_histogram),
+        ("Mean quality per read", "Mean phred", |qc| {
+            &qc.mean_quality_histogram
+        }),
+        ("GC content per read", "GC (%)", |qc| &qc.gc_histogram),
+    ];
+    for (title, x_label, bins) in histograms {
+        let series: Vec<Series> = qcs
+            .iter()
+            .map(|(name, color, qc)| {
+                let total = bins(qc).iter().map(|bin| bin.count).sum();
+                Series {
+                    name,
+                    color,
+                    points: histogram_points(bins(qc), total),
+                }
+            })
+            .collect();
+        html.push_str(&Chart::new(title, x_label, "Reads (%)").render(&series));
+    }
+
+    let series: Vec<Series> = qcs
+        .iter()
+        .map(|(name, color, qc)| Series {
+            name,
+            color,
+            points: qc
+                .n_content
+                .iter()
+                .map(|position| (position.position.start as f64, position.n * 100.0))
+                .collect(),
+        })
+        .collect();
+    let mut chart = Chart::new("N content per position", "Position (bp)", "N (%)");
+    chart.y_range = Some((0.0, 100.0));
+    html.push_str(&chart.render(&series));
+
+    if let Some((_, _, qc)) = qcs.last() {
+        let bases: [BaseSeries; 4] = [
+            ("A", "#2ca02c", |position| position.a),
+            ("C", "#1f77b4", |position| position.c),
+            ("G", "#7f7f7f", |position| position.g),
+            ("T", "#d62728", |position| position.t),
+        ];
+        let series: Vec<Series> = bases
+            .into_iter()
+            .map(|(name, color, base)| Series {
+                name,
+                color,
+                points: qc
+                    .base_composition
+                    .iter()
+                    .map(|position| (position.position.start as f64, 100.0 * base(position)))
+                    .collect(),
+            })
+            .collect();
+        let mut chart = Chart::new("Base composition per position", "Position (bp)", "%");
+        chart.y_range = Some((0.0, 100.0));
+        html.push_str(&chart.render(&series));
+    }
+
+    if let Some(duplication) = mate.duplication {
+        let levels = &duplication.duplication_levels;
+        let mut chart = Chart::new("Duplication levels (raw)", "Occurrences", "%");
+        chart.y_range = Some((0.0, 100.0));
+        chart.x_labels = Some(levels.iter().map(|level| level.level.clone()).collect());
+        let series = [
+            Series {
+                name: "of reads",
+                color: RAW_COLOR,
+                points: levels
+                    .iter()
+                    .enumerate()
+                    .map(|(i, level)| (i as f64, level.percent_total))
+                    .collect(),
+            },
+            Series {
+                name: "of sequences",
+                color: FILTERED_COLOR,
+                points: levels
+                    .iter()
+                    .enumerate()
+                    .map(|(i, level)| (i as f64, level.percent_deduplicated))
+                    .collect(),
+            },
+        ];
+        html.push_str(&chart.render(&series));
+    }
+    html.push_str("</div>");
+
+    let overrepresented = mate
+        .duplication
+        .map(|duplication| &duplication.overrepresented)
+        .filter(|overrepresented| !overrepresented.is_empty());
+    if let Some(overrepresented) = overrepresented {
+        html.push_str(
+                "<h3>Overrepresented sequences (raw)</h3><table><tr><th>Sequence</th><th>Count</th><th>%</th><th>Possible source</th></tr>",
+            );
+        for sequence in overrepresented {
+            let _ = write!(
+                html,
+                "<tr><td><code>{}</code></td><td>{}</td><td>{:.2}</td><td>{}</td></tr>",
+                escape(&sequence.sequence),
+                sequence.count,
+                sequence.percentage,
+                escape(&or_dash(sequence.possible_source.as_ref()))
+            );
+        }
+        html.push_str("</table>");
+    }
+}
+
+/// The report of a run, as a single HTML document.
+pub fn html_report(
+    sample_name: &str,
+    sample_id: &str,
+    result: &FastqPreprocessResult,
+    config: &FastqSampleConfig,
+    runtime: usize,
+) -> String {
+    let mates = mates(result);
+    let title = format!("QC report {}", escape(sample_name));
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html><html lang="en"><head><meta charset="utf-8"><title>{title}</title><style>{STYLE}</style></head><body>"#
+    );
+    let _ = write!(
+        html,
+        "<h1>{}</h1><p>Sample {}, preprocessed in {} s, report generated at {} UTC.</p>",
+        title,
+        escape(sample_id),
+        runtime,
+        time_now()
+    );
+
+    summary_section(&mut html, &mates);
+    metrics_section(&mut html, &mates, result);
+    config_section(&mut html, config);
+    for mate in &mates {
+        plots_section(&mut html, mate);
+    }
+
+    html.push_str("</body></html>");
+    html
+}
+
+/// Upload the report of a run next to its filtered files, as HTML,
+/// so that it opens in the browser. Returns its url.
+pub async fn upload_report(
+    minio_client: &Client,
+    key_prefix: &str,
+    html: String,
+) -> Result<String, FastqError> {
+    let key = format!("{}/{}", key_prefix, REPORT);
+    let bytes = SegmentedBytes::from(Bytes::from(html));
+
+    Ok(minio_upload_bytes_as(minio_client, PROCESSED_BUCKET, &key, bytes, "text/html").await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::database::schemas::fastq_qc::{
+        NContent, OverrepresentedSequence, PositionBin, QualityDistribution,
+    };
+
+    fn metrics(num_reads: usize) -> FastqMetrics {
+        FastqMetrics {
+            num_reads,
+            num_bases: num_reads * 100,
+            mean_error: 0.001,
+            mean_phred: 30,
+            mean_len: 100,
+            shortest: None,
+            longest: None,
+        }
+    }
+
+    fn position(start: usize) -> PositionBin {
+        PositionBin { start, end: start }
+    }
+
+    fn qc(median: u8, n: f64) -> FastqQc {
+        FastqQc {
+            mean_quality_histogram: vec![
+                HistogramBin {
+                    value: 12,
+                    count: 10,
+                },
+                HistogramBin {
+                    value: median as usize,
+                    count: 90,
+                },
+            ],
+            position_quality: vec![QualityDistribution {
+                position: position(1),
+                mean: median as f64,
+                median,
+                lower_quartile: median - 2,
+                upper_quartile: median + 2,
+                percentile_10: median - 4,
+                percentile_90: median + 4,
+            }],
+            n_content: vec![NContent {
+                position: position(1),
+                n,
+            }],
+            ..FastqQc::mock()
+        }
+    }
+
+    fn duplication(remaining: f64, top: f64) -> DuplicationMetrics {
+        DuplicationMetrics {
+            tracked_reads: 100,
+            distinct_sequences: 80,
+            percent_remaining_if_deduplicated: remaining,
+            duplication_levels: Vec::new(),
+            overrepresented: vec![OverrepresentedSequence {
+                sequence: "ACGT".into(),
+                count: 2,
+                percentage: top,
+                possible_source: None,
+            }],
+        }
+    }
+
+    fn passed(flags: &[Flag]) -> Vec<(&str, bool)> {
+        flags.iter().map(|flag| (flag.check, flag.passed)).collect()
+    }
+
+    #[test]
+    fn flags_pass() {
+        let (raw, filtered) = (metrics(100), metrics(90));
+        let (qc, duplication) = (qc(35, 0.01), duplication(80.0, 0.5));
+        let mate = Mate {
+            name: "Reads",
+            raw: &raw,
+            filtered: &filtered,
+            qc_raw: None,
+            qc_filtered: Some(&qc),
+            duplication: Some(&duplication),
+            adapter_trim: None,
+            quality_trim: None,
+        };
+
+        assert_eq!(
+            passed(&flags(&mate)),
+            vec![
+                ("Reads kept", true),
+                ("Per base quality", true),
+                ("Per read quality", true),
+                ("N content", true),
+                ("Duplication", true),
+                ("Overrepresented sequences", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_fail() {
+        let (raw, filtered) = (metrics(100), metrics(40));
+        let (qc, duplication) = (qc(15, 0.3), duplication(40.0, 2.0));
+        let mate = Mate {
+            name: "Reads",
+            raw: &raw,
+            filtered: &filtered,
+            qc_raw: None,
+            qc_filtered: Some(&qc),
+            duplication: Some(&duplication),
+            adapter_trim: None,
+            quality_trim: None,
+        };
+
+        let flags = flags(&mate);
+        assert!(flags.iter().all(|flag| !flag.passed));
+        assert_eq!(flags[0].detail, "40.0% of the reads passed");
+        assert_eq!(flags[1].detail, "Median 15 and lower quartile 13 at 1-1");
+    }
+
+    #[test]
+    fn flags_without_qc() {
+        // Runs from before QC was added only have the read counts.
+        let (raw, filtered) = (metrics(0), metrics(0));
+        let mate = Mate {
+            name: "Reads",
+            raw: &raw,
+            filtered: &filtered,
+            qc_raw: None,
+            qc_filtered: None,
+            duplication: None,
+            adapter_trim: None,
+            quality_trim: None,
+        };
+
+        assert_eq!(passed(&flags(&mate)), vec![("Reads kept", false)]);
+    }
+
+    #[test]
+    fn report_of_paired_sample() {
+        let mut result = FastqPreprocessResult::mock();
+        result.metrics_raw = metrics(100);
+        result.metrics_filtered = metrics(90);
+        result.mate_metrics_raw = Some(metrics(100));
+        result.mate_metrics_filtered = Some(metrics(90));
+        result.qc_filtered = Some(qc(35, 0.01));
+
+        let html = html_report(
+            "<script>S1</script>",
+            "abc123",
+            &result,
+            &FastqSampleConfig::mock(),
+            42,
+        );
+
+        assert!(html.starts_with("<!DOCTYPE html>") && html.ends_with("</body></html>"));
+        assert!(html.contains("QC report &lt;script&gt;S1&lt;/script&gt;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<th>R1 raw</th><th>R1 filtered</th>"));
+        assert!(html.contains("<th>R2 raw</th><th>R2 filtered</th>"));
+        assert!(html.contains("preprocessed in 42 s"));
+        // R2 has no QC, so its column has no per base quality check.
+        assert!(html.contains("<tr><td>Per base quality</td>"));
+        assert!(html.contains("<td>-</td>"));
+    }
+}
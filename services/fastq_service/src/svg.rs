@@ -0,0 +1,259 @@
+use std::fmt::Write;
+
+// Just enough SVG for the plots of the QC report, so that
+// the report works offline, without any JavaScript.
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 320.0;
+const LEFT: f64 = 60.0;
+const RIGHT: f64 = 150.0;
+const TOP: f64 = 30.0;
+const BOTTOM: f64 = 45.0;
+const NUM_TICKS: usize = 5;
+
+/// Escape text for HTML and SVG.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub struct Series<'a> {
+    pub name: &'a str,
+    pub color: &'a str,
+    pub points: Vec<(f64, f64)>,
+}
+
+/// A background band from y0 to y1, e.g., the good, okay and bad phred ranges.
+pub struct Band<'a> {
+    pub y0: f64,
+    pub y1: f64,
+    pub color: &'a str,
+}
+
+pub struct Chart<'a> {
+    pub title: &'a str,
+    pub x_label: &'a str,
+    pub y_label: &'a str,
+    /// Fixed y range, e.g., 0 to 100 for percentages. Otherwise 0 up to the highest value.
+    pub y_range: Option<(f64, f64)>,
+    /// Labels for the x values 0, 1, 2..., for categories. Otherwise the numbers are shown.
+    pub x_labels: Option<Vec<String>>,
+    pub bands: Vec<Band<'a>>,
+}
+
+impl<'a> Chart<'a> {
+    pub fn new(title: &'a str, x_label: &'a str, y_label: &'a str) -> Self {
+        Self {
+            title,
+            x_label,
+            y_label,
+            y_range: None,
+            x_labels: None,
+            bands: Vec::new(),
+        }
+    }
+
+    /// A line per series, as an inline <svg>.
+    pub fn render(&self, series: &[Series]) -> String {
+        let points = series.iter().flat_map(|series| series.points.iter());
+        let (x_min, x_max) = points
+            .clone()
+            .fold((f64::MAX, f64::MIN), |(min, max), (x, _)| {
+                (min.min(*x), max.max(*x))
+            });
+        let (x_min, x_max) = match x_min <= x_max {
+            true => (x_min, x_max.max(x_min + 1.0)),
+            false => (0.0, 1.0),
+        };
+        let (y_min, y_max) = self.y_range.unwrap_or_else(|| {
+            let max = points.fold(0.0f64, |max, (_, y)| max.max(*y));
+            (0.0, if max > 0.0 { max * 1.05 } else { 1.0 })
+        });
+
+        let plot_width = WIDTH - LEFT - RIGHT;
+        let plot_height = HEIGHT - TOP - BOTTOM;
+        let x_pos = |x: f64| LEFT + (x - x_min) / (x_max - x_min) * plot_width;
+        let y_pos = |y: f64| {
+            let y = y.clamp(y_min, y_max);
+            TOP + plot_height - (y - y_min) / (y_max - y_min) * plot_height
+        };
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="11">"#
+        );
+        let _ = write!(
+            svg,
+            r#"<text x="{}" y="18" font-size="13" font-weight="bold">{}</text>"#,
+            LEFT,
+            escape(self.title)
+        );
+
+        for band in &self.bands {
+            let (top, bottom) = (y_pos(band.y1), y_pos(band.y0));
+            let _ = write!(
+                svg,
+                r#"<rect x="{LEFT}" y="{top:.1}" width="{plot_width}" height="{:.1}" fill="{}"/>"#,
+                bottom - top,
+                band.color
+            );
+        }
+
+        // Axes and ticks.
+        let _ = write!(
+            svg,
+            r#"<path d="M{LEFT} {TOP} V{} H{}" fill="none" stroke="black"/>"#,
+            TOP + plot_height,
+            LEFT + plot_width
+        );
+        for i in 0..=NUM_TICKS {
+            let y = y_min + (y_max - y_min) * i as f64 / NUM_TICKS as f64;
+            let _ = write!(
+                svg,
+                r#"<text x="{}" y="{:.1}" text-anchor="end">{}</text>"#,
+                LEFT - 5.0,
+                y_pos(y) + 4.0,
+                format_number(y)
+            );
+        }
+        match &self.x_labels {
+            Some(labels) => {
+                for (i, label) in labels.iter().enumerate() {
+                    let _ = write!(
+                        svg,
+                        r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#,
+                        x_pos(i as f64),
+                        TOP + plot_height + 15.0,
+                        escape(label)
+                    );
+                }
+            }
+            None => {
+                for i in 0..=NUM_TICKS {
+                    let x = x_min + (x_max - x_min) * i as f64 / NUM_TICKS as f64;
+                    let _ = write!(
+                        svg,
+                        r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#,
+                        x_pos(x),
+                        TOP + plot_height + 15.0,
+                        format_number(x)
+                    );
+                }
+            }
+        }
+        let _ = write!(
+            svg,
+            r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#,
+            LEFT + plot_width / 2.0,
+            HEIGHT - 8.0,
+            escape(self.x_label)
+        );
+        let _ = write!(
+            svg,
+            r#"<text transform="translate(14 {:.1}) rotate(-90)" text-anchor="middle">{}</text>"#,
+            TOP + plot_height / 2.0,
+            escape(self.y_label)
+        );
+
+        // Lines and the legend.
+        for (i, series) in series.iter().enumerate() {
+            let line: Vec<String> = series
+                .points
+                .iter()
+                .map(|(x, y)| format!("{:.1},{:.1}", x_pos(*x), y_pos(*y)))
+                .collect();
+            let _ = write!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+                line.join(" "),
+                series.color
+            );
+
+            let legend_y = TOP + 10.0 + i as f64 * 16.0;
+            let legend_x = LEFT + plot_width + 10.0;
+            let _ = write!(
+                svg,
+                r#"<rect x="{legend_x}" y="{:.1}" width="12" height="3" fill="{}"/><text x="{}" y="{:.1}">{}</text>"#,
+                legend_y - 4.0,
+                series.color,
+                legend_x + 16.0,
+                legend_y,
+                escape(series.name)
+            );
+        }
+
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+/// Short numbers for tick labels, e.g., 0.25, 12 or 1.5k.
+fn format_number(value: f64) -> String {
+    match value.abs() {
+        v if v >= 1_000_000.0 => format!("{:.1}M", value / 1_000_000.0),
+        v if v >= 1_000.0 => format!("{:.1}k", value / 1_000.0),
+        v if v >= 10.0 || v == 0.0 => format!("{:.0}", value),
+        _ => format!("{:.2}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_markup() {
+        assert_eq!(
+            escape(r#"<a href="x">R&D</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;R&amp;D&lt;/a&gt;"
+        );
+        // Already escaped text is escaped again, not left alone.
+        assert_eq!(escape("&amp;"), "&amp;amp;");
+        assert_eq!(escape("sample_1"), "sample_1");
+    }
+
+    #[test]
+    fn format_tick_labels() {
+        assert_eq!(format_number(0.0), "0");
+        assert_eq!(format_number(0.25), "0.25");
+        assert_eq!(format_number(12.4), "12");
+        assert_eq!(format_number(1_500.0), "1.5k");
+        assert_eq!(format_number(2_500_000.0), "2.5M");
+    }
+
+    #[test]
+    fn render_series() {
+        let mut chart = Chart::new("GC <content>", "Position", "%");
+        chart.y_range = Some((0.0, 100.0));
+        let svg = chart.render(&[Series {
+            name: "R1 & R2",
+            color: "#1f77b4",
+            points: vec![(0.0, 0.0), (10.0, 100.0)],
+        }]);
+
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+        assert!(svg.contains("GC &lt;content&gt;"));
+        assert!(svg.contains("R1 &amp; R2"));
+        // From the bottom left to the top right corner of the plot.
+        let (plot_right, plot_bottom) = (WIDTH - RIGHT, HEIGHT - BOTTOM);
+        assert!(svg.contains(&format!(
+            r#"<polyline points="{LEFT:.1},{plot_bottom:.1} {plot_right:.1},{TOP:.1}""#
+        )));
+    }
+
+    #[test]
+    fn render_without_points() {
+        // E.g., a run without reads, which still gets its (empty) plots.
+        let svg = Chart::new("Empty", "x", "y").render(&[Series {
+            name: "raw",
+            color: "#d62728",
+            points: Vec::new(),
+        }]);
+
+        assert!(!svg.contains("NaN"));
+        assert!(!svg.contains("inf"));
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use flate2::{Compression, write::GzEncoder};
+use log::info;
+use shared::database::schemas::fastq_preprocess::{AdapterTrimMetrics, QualityTrimMetrics};
+
+use crate::adapters::trim_read;
+use crate::config::TrimConfig;
+use crate::errors::FastqError;
+use crate::quality::quality_trim;
+use crate::records::{FastqRecord, next_record, open_fastq, write_record};
+
+/// Metrics of the trimming steps that are enabled.
+#[derive(Debug, Default)]
+pub struct TrimMetrics {
+    pub adapter: Option<AdapterTrimMetrics>,
+    pub quality: Option<QualityTrimMetrics>,
+}
+
+/// Trim adapters and primers, and then low quality bases, off every read of
+/// `fastq` into `outfile`. Reads with nothing left are dropped, the order
+/// of the others is kept.
+pub fn trim_reads(
+    fastq: &Path,
+    outfile: &Path,
+    cfg: &TrimConfig,
+) -> Result<TrimMetrics, FastqError> {
+    let mut reader = open_fastq(fastq)?;
+    let mut writer = GzEncoder::new(BufWriter::new(File::create(outfile)?), Compression::fast());
+
+    let mut adapter_metrics = AdapterTrimMetrics {
+        num_reads: 0,
+        trimmed_reads: 0,
+        trimmed_bases: 0,
+        empty_reads: 0,
+        adapter_hits: BTreeMap::new(),
+    };
+    let mut quality_metrics = QualityTrimMetrics {
+        num_reads: 0,
+        trimmed_reads: 0,
+        trimmed_bases: 0,
+        empty_reads: 0,
+    };
+
+    while let Some(record) = next_record(&mut reader)? {
+        let len = record[1].len();
+        let (mut start, mut end) = (0, len);
+
+        if cfg.has_adapters() {
+            let (adapter_start, adapter_end, found) = trim_read(record[1].as_bytes(), cfg);
+            (start, end) = (adapter_start, adapter_end);
+
+            adapter_metrics.num_reads += 1;
+            for name in &found {
+                *adapter_metrics
+                    .adapter_hits
+                    .entry(name.to_string())
+                    .or_default() += 1;
+            }
+            if !found.is_empty() {
+                adapter_metrics.trimmed_reads += 1;
+                adapter_metrics.trimmed_bases += len - (end - start);
+                if start >= end {
+                    adapter_metrics.empty_reads += 1;
+                    continue;
+                }
+            }
+        }
+
+        if cfg.quality.is_enabled() {
+            let qualities = record[3].as_bytes().get(start..end).unwrap_or_default();
+            let (quality_start, quality_end) = quality_trim(qualities, &cfg.quality);
+            let trimmed = (end - start) - (quality_end - quality_start);
+            (start, end) = (start + quality_start, start + quality_end);
+
+            quality_metrics.num_reads += 1;
+            if trimmed > 0 {
+                quality_metrics.trimmed_reads += 1;
+                quality_metrics.trimmed_bases += trimmed;
+                if start >= end {
+                    quality_metrics.empty_reads += 1;
+                    continue;
+                }
+            }
+        }
+
+        if (start, end) == (0, len) {
+            write_record(&mut writer, &record)?;
+            continue;
+        }
+
+        let trimmed: FastqRecord = [
+            record[0].clone(),
+            record[1].get(start..end).unwrap_or_default().to_string(),
+            record[2].clone(),
+            record[3].get(start..end).unwrap_or_default().to_string(),
+        ];
+        write_record(&mut writer, &trimmed)?;
+    }
+
+    writer.finish()?.flush()?;
+
+    let metrics = TrimMetrics {
+        adapter: cfg.has_adapters().then_some(adapter_metrics),
+        quality: cfg.quality.is_enabled().then_some(quality_metrics),
+    };
+    info!("Trim metrics: {:?}", metrics);
+
+    Ok(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::schema::trimming::QualityTrimConfig;
+
+    use crate::config::{Adapter, AdapterEnd};
+
+    const INSERT: &str = "ACGTTGCAACGTTGCAACGT";
+    const TRUSEQ: &str = "AGATCGGAAGAGC";
+
+    fn read_all(path: &Path) -> Vec<FastqRecord> {
+        let mut reader = open_fastq(path).unwrap();
+        std::iter::from_fn(|| next_record(&mut reader).unwrap()).collect()
+    }
+
+    #[test]
+    fn trim_adapters_then_quality() {
+        let dir = tempfile::tempdir().unwrap();
+        let fastq = dir.path().join("reads.fastq");
+        let outfile = dir.path().join("trimmed.fastq.gz");
+
+        let records = [
+            format!("@adapter\n{INSERT}{TRUSEQ}\n+\n{}\n", "I".repeat(33)),
+            format!("@quality\n{INSERT}\n+\n{}+++\n", "I".repeat(17)),
+            format!(
+                "@both\n{INSERT}{TRUSEQ}\n+\n{}+++{}\n",
+                "I".repeat(17),
+                "I".repeat(13)
+            ),
+            format!("@only_adapter\n{TRUSEQ}\n+\n{}\n", "I".repeat(13)),
+            format!("@only_low_quality\n{INSERT}\n+\n{}\n", "+".repeat(20)),
+            format!("@clean\n{INSERT}\n+\n{}\n", "I".repeat(20)),
+        ];
+        std::fs::write(&fastq, records.concat()).unwrap();
+
+        let cfg = TrimConfig {
+            adapters: vec![Adapter {
+                name: "TruSeq".to_string(),
+                sequence: TRUSEQ.as_bytes().to_vec(),
+                end: AdapterEnd::End,
+            }],
+            max_error_rate: 0.1,
+            min_overlap: 3,
+            quality: QualityTrimConfig {
+                trailing: Some(20),
+                ..Default::default()
+            },
+        };
+        let metrics = trim_reads(&fastq, &outfile, &cfg).unwrap();
+
+        let trimmed = read_all(&outfile);
+        let summary: Vec<(&str, &str, usize)> = trimmed
+            .iter()
+            .map(|record| (record[0].as_str(), record[1].as_str(), record[3].len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("@adapter", INSERT, 20),
+                ("@quality", &INSERT[..17], 17),
+                ("@both", &INSERT[..17], 17),
+                ("@clean", INSERT, 20),
+            ]
+        );
+
+        let adapter = metrics.adapter.unwrap();
+        assert_eq!(adapter.num_reads, 6);
+        assert_eq!(adapter.trimmed_reads, 3);
+        assert_eq!(adapter.trimmed_bases, 3 * TRUSEQ.len());
+        assert_eq!(adapter.empty_reads, 1);
+        assert_eq!(adapter.adapter_hits.get("TruSeq"), Some(&3));
+
+        // The read that was only adapter never gets here.
+        let quality = metrics.quality.unwrap();
+        assert_eq!(quality.num_reads, 5);
+        assert_eq!(quality.trimmed_reads, 3);
+        assert_eq!(quality.trimmed_bases, 3 + 3 + 20);
+        assert_eq!(quality.empty_reads, 1);
+    }
+
+    #[test]
+    fn trim_disabled_steps() {
+        let dir = tempfile::tempdir().unwrap();
+        let fastq = dir.path().join("reads.fastq");
+        let outfile = dir.path().join("trimmed.fastq.gz");
+        std::fs::write(&fastq, format!("@read\n{INSERT}\n+\n{}\n", "+".repeat(20))).unwrap();
+
+        let cfg = TrimConfig {
+            adapters: Vec::new(),
+            max_error_rate: 0.1,
+            min_overlap: 3,
+            quality: QualityTrimConfig {
+                crop: Some(5),
+                ..Default::default()
+            },
+        };
+        let metrics = trim_reads(&fastq, &outfile, &cfg).unwrap();
+
+        assert!(metrics.adapter.is_none());
+        assert_eq!(metrics.quality.unwrap().trimmed_bases, 15);
+        assert_eq!(read_all(&outfile)[0][1], &INSERT[..5]);
+    }
+}
@@ -0,0 +1,240 @@
+use async_nats::jetstream::Context as NatsContext;
+use log::{info, warn};
+use minio::s3::Client as MinioClient;
+use shared::file_path;
+use shared::minio::{minio_delete_object, minio_download};
+use shared::nats::Worker;
+use shared::nats::schema::envelope::Envelope;
+use shared::nats::schema::fastq_service::FastqMessage;
+use shared::nats::status::publish_sample_status;
+use shared::nats::streams::config::StreamType;
+use shared::schema::schema::Status;
+use surrealdb::{Surreal, engine::remote::ws::Client};
+use tokio_util::sync::CancellationToken;
+
+use crate::database::{sample_name, transition_sample_status, write_to_db};
+use crate::errors::FastqError;
+use crate::handle_message::{PROCESSED_BUCKET, PreprocessOutput, handle_message, processed_keys};
+use crate::report::{html_report, upload_report};
+
+/// Filters uploaded fastq files and stores the metrics.
+pub struct FastqWorker {
+    pub db: Surreal<Client>,
+    pub minio_client: MinioClient,
+    pub jetstream: NatsContext,
+}
+
+impl FastqWorker {
+    /// Where the filtered files of a run go. Every publish of a job, e.g., a
+    /// reprocess, has its own correlation id, so runs don't overwrite each
+    /// other, while redeliveries of the same message do.
+    fn key_prefix(envelope: &Envelope<FastqMessage>) -> String {
+        format!(
+            "{}/{}",
+            envelope.payload.fastq_sample_id.key(),
+            envelope.correlation_id
+        )
+    }
+
+    /// Let the user know that the sample changed status. This is only a
+    /// notification, so failing to publish it does not fail the job.
+    async fn publish_status(
+        &self,
+        envelope: &Envelope<FastqMessage>,
+        status: Status,
+        error: Option<String>,
+    ) {
+        let sample_id = &envelope.payload.fastq_sample_id;
+
+        if let Err(e) = publish_sample_status(
+            &self.jetstream,
+            &envelope.user_id,
+            sample_id.key(),
+            status,
+            error,
+        )
+        .await
+        {
+            warn!(
+                "Failed to publish status of {}: {:?}",
+                sample_id.formatted_id(),
+                e
+            );
+        }
+    }
+
+    /// Render the HTML report of a run and upload it next to the filtered files.
+    async fn report(
+        &self,
+        envelope: &Envelope<FastqMessage>,
+        output: &PreprocessOutput,
+        key_prefix: &str,
+    ) -> Result<String, FastqError> {
+        let sample_id = &envelope.payload.fastq_sample_id;
+        let name = sample_name(sample_id, &self.db)
+            .await?
+            .unwrap_or_else(|| sample_id.key().to_string());
+
+        let html = html_report(
+            &name,
+            sample_id.key(),
+            &output.result,
+            &envelope.payload.config,
+            output.runtime,
+        );
+
+        upload_report(&self.minio_client, key_prefix, html).await
+    }
+}
+
+impl Worker for FastqWorker {
+    type Message = FastqMessage;
+    type Output = PreprocessOutput;
+    type Error = FastqError;
+
+    fn stream_type(&self) -> StreamType {
+        StreamType::FileUpload
+    }
+
+    fn job_id(&self, envelope: &Envelope<FastqMessage>) -> String {
+        envelope.payload.fastq_sample_id.key().to_string()
+    }
+
+    async fn start(&self, envelope: &Envelope<FastqMessage>) -> Result<bool, FastqError> {
+        // A redelivery of a message for a sample that is already done,
+        // e.g., after the ack got lost, must not process it again. Neither
+        // must a retry of a run that was replaced by a reprocess.
+        let sample_id = &envelope.payload.fastq_sample_id;
+        if !transition_sample_status(
+            sample_id,
+            Status::Pending,
+            None,
+            &envelope.correlation_id,
+            &self.db,
+        )
+        .await?
+        {
+            info!(
+                "Sample {} is already done, or on another run, skipping",
+                sample_id.formatted_id()
+            );
+            return Ok(false);
+        }
+        self.publish_status(envelope, Status::Pending, None).await;
+
+        Ok(true)
+    }
+
+    async fn process(
+        &self,
+        envelope: &Envelope<FastqMessage>,
+        cancel: &CancellationToken,
+    ) -> Result<Self::Output, FastqError> {
+        // Every job gets its own directory, removed once the job is done,
+        // so that jobs (and replicas on the same host) can run side by side.
+        let workdir = tempfile::Builder::new()
+            .prefix("fastq_service-")
+            .tempdir()?;
+
+        // Download file.
+        let file_path = file_path!(workdir.path(), "raw", "R1.fastq.gz");
+        minio_download(&self.minio_client, &envelope.payload.url, &file_path).await?;
+
+        // Paired-end samples also have an R2.
+        let mate_path = file_path!(workdir.path(), "raw", "R2.fastq.gz");
+        if let Some(mate_url) = &envelope.payload.mate_url {
+            minio_download(&self.minio_client, mate_url, &mate_path).await?;
+        }
+        let mate = envelope
+            .payload
+            .mate_url
+            .as_ref()
+            .map(|_| mate_path.as_path());
+
+        // Do actual work...
+        info!("Running fastq_rs filter...");
+        let key_prefix = Self::key_prefix(envelope);
+        let mut output = handle_message(
+            workdir.path(),
+            &file_path,
+            mate,
+            &envelope.payload.config,
+            &self.minio_client,
+            &key_prefix,
+            cancel,
+        )
+        .await?;
+
+        // The report is a nice to have, so failing to make it does not fail the job.
+        match self.report(envelope, &output, &key_prefix).await {
+            Ok(report_url) => output.report_url = Some(report_url),
+            Err(e) => warn!(
+                "Failed to upload report of {}: {:?}",
+                envelope.payload.fastq_sample_id.formatted_id(),
+                e
+            ),
+        }
+
+        Ok(output)
+    }
+
+    async fn finish(
+        &self,
+        envelope: &Envelope<FastqMessage>,
+        output: Self::Output,
+    ) -> Result<(), FastqError> {
+        let sample_id = &envelope.payload.fastq_sample_id;
+
+        // Write to database.
+        let written = write_to_db(
+            output,
+            envelope.payload.config.clone(),
+            sample_id.clone(),
+            &envelope.correlation_id,
+            &self.db,
+        )
+        .await?;
+
+        match written {
+            true => self.publish_status(envelope, Status::Done, None).await,
+            false => info!(
+                "Sample {} was finished by another delivery, discarding result",
+                sample_id.formatted_id()
+            ),
+        }
+
+        Ok(())
+    }
+
+    async fn fail(
+        &self,
+        envelope: &Envelope<FastqMessage>,
+        error: &FastqError,
+    ) -> Result<(), FastqError> {
+        let error = format!("{:?}", error);
+        if transition_sample_status(
+            &envelope.payload.fastq_sample_id,
+            Status::Error,
+            Some(error.clone()),
+            &envelope.correlation_id,
+            &self.db,
+        )
+        .await?
+        {
+            self.publish_status(envelope, Status::Error, Some(error))
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// The temp dir went with the job, but an upload might have finished
+    /// already. The sample was marked as cancelled by whoever cancelled it.
+    async fn cancelled(&self, envelope: &Envelope<FastqMessage>) -> Result<(), FastqError> {
+        for key in processed_keys(&Self::key_prefix(envelope)) {
+            minio_delete_object(&self.minio_client, PROCESSED_BUCKET, &key).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::schemas::common::SimpleRecordId;
+use crate::schema::sample_sheet::SampleSheetRow;
+use crate::schema::schema::{Pipeline, Status};
+use crate::schema::upload::UploadConfig;
+
+/// A file of the batch that has finished uploading.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchUpload {
+    pub file_name: String,
+    pub url: String,
+}
+
+/// A sample sheet driven upload, e.g., a 96-sample plate. Files are uploaded
+/// one by one, and a FastqSample is created for a row of the sheet once all
+/// of its files are in. Samples are related to the batch with
+/// batch->contains->fastq_sample.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatchData {
+    /// Id of the users record that owns the batch, e.g., "users:abc123".
+    #[serde(default)]
+    pub user_id: String,
+    pub name: String,
+    /// Used for rows that don't specify a pipeline.
+    pub pipeline: Pipeline,
+    /// Used for rows that don't specify thresholds.
+    #[serde(default)]
+    pub config: UploadConfig,
+    pub rows: Vec<SampleSheetRow>,
+    #[serde(default)]
+    pub uploads: Vec<BatchUpload>,
+    pub status: Status,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl BatchData {
+    pub fn url_of(&self, file_name: &str) -> Option<&str> {
+        self.uploads
+            .iter()
+            .find(|upload| upload.file_name == file_name)
+            .map(|upload| upload.url.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Batch {
+    pub id: Option<SimpleRecordId>,
+    #[serde(flatten)]
+    pub data: BatchData,
+}
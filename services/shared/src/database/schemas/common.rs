@@ -8,13 +8,27 @@ use crate::database::DatabaseError;
 /// what we get is a RecordId, which contains a bunch of stuff we don't
 /// necessarily want. Instead, we implement a custom deserializer that
 /// extracts what we want, which is the record_id and table_name only.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct SimpleRecordId {
     record_id: String,
     table_name: String,
 }
 
 impl SimpleRecordId {
+    /// An id that we pick ourselves, e.g., to refer to a record
+    /// in the same transaction that creates it.
+    pub fn new(table_name: &str, record_id: &str) -> Self {
+        Self {
+            record_id: record_id.to_string(),
+            table_name: table_name.to_string(),
+        }
+    }
+
+    /// The id without the table name.
+    pub fn key(&self) -> &str {
+        &self.record_id
+    }
+
     pub fn formatted_id(&self) -> String {
         format!("{}:{}", self.table_name, self.record_id)
     }
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::io::BufReader;
 use std::path::PathBuf;
 
@@ -5,6 +6,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::database::DatabaseError;
 use crate::database::schemas::common::SimpleRecordId;
+use crate::database::schemas::fastq_qc::{DuplicationMetrics, FastqQc};
+use crate::database::schemas::fastq_sample::FastqSampleConfig;
 use crate::utils::time::time_now;
 
 use crate::schema::schema::Status;
@@ -47,10 +50,76 @@ impl FastqMetrics {
     }
 }
 
+/// How many read pairs survived filtering. A pair is only kept if
+/// both mates pass, so the mates stay in sync.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PairMetrics {
+    pub num_pairs_raw: usize,
+    pub num_pairs_filtered: usize,
+    /// Pairs dropped because only R1 passed.
+    pub r1_only: usize,
+    /// Pairs dropped because only R2 passed.
+    pub r2_only: usize,
+}
+
+/// What adapter and primer trimming took off, before filtering.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdapterTrimMetrics {
+    pub num_reads: usize,
+    /// Reads that had at least one adapter or primer trimmed off.
+    pub trimmed_reads: usize,
+    pub trimmed_bases: usize,
+    /// Reads with nothing left after trimming, which are dropped.
+    pub empty_reads: usize,
+    /// Number of reads that each adapter or primer was found in.
+    pub adapter_hits: BTreeMap<String, usize>,
+}
+
+/// What quality trimming took off, after adapter trimming and before filtering.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QualityTrimMetrics {
+    pub num_reads: usize,
+    pub trimmed_reads: usize,
+    pub trimmed_bases: usize,
+    /// Reads with nothing left after trimming, which are dropped.
+    pub empty_reads: usize,
+}
+
+/// For paired-end samples, metrics_* are for R1 and mate_metrics_* for R2.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FastqPreprocessResult {
     pub metrics_raw: FastqMetrics,
     pub metrics_filtered: FastqMetrics,
+    #[serde(default)]
+    pub mate_metrics_raw: Option<FastqMetrics>,
+    #[serde(default)]
+    pub mate_metrics_filtered: Option<FastqMetrics>,
+    #[serde(default)]
+    pub pair_metrics: Option<PairMetrics>,
+    /// Only set if the sample has adapters or primers to trim.
+    #[serde(default)]
+    pub adapter_trim: Option<AdapterTrimMetrics>,
+    #[serde(default)]
+    pub mate_adapter_trim: Option<AdapterTrimMetrics>,
+    /// Only set if the sample has quality trimming steps.
+    #[serde(default)]
+    pub quality_trim: Option<QualityTrimMetrics>,
+    #[serde(default)]
+    pub mate_quality_trim: Option<QualityTrimMetrics>,
+    /// Per-read metrics, not set for runs from before they were added.
+    #[serde(default)]
+    pub qc_raw: Option<FastqQc>,
+    #[serde(default)]
+    pub qc_filtered: Option<FastqQc>,
+    #[serde(default)]
+    pub mate_qc_raw: Option<FastqQc>,
+    #[serde(default)]
+    pub mate_qc_filtered: Option<FastqQc>,
+    /// Duplication and overrepresented sequences of the raw reads.
+    #[serde(default)]
+    pub duplication: Option<DuplicationMetrics>,
+    #[serde(default)]
+    pub mate_duplication: Option<DuplicationMetrics>,
 }
 
 impl FastqPreprocessResult {
@@ -58,6 +127,19 @@ impl FastqPreprocessResult {
         Self {
             metrics_raw: FastqMetrics::mock(),
             metrics_filtered: FastqMetrics::mock(),
+            mate_metrics_raw: None,
+            mate_metrics_filtered: None,
+            pair_metrics: None,
+            adapter_trim: None,
+            mate_adapter_trim: None,
+            quality_trim: None,
+            mate_quality_trim: None,
+            qc_raw: None,
+            qc_filtered: None,
+            mate_qc_raw: None,
+            mate_qc_filtered: None,
+            duplication: None,
+            mate_duplication: None,
         }
     }
 }
@@ -68,8 +150,17 @@ impl FastqPreprocessResult {
 pub struct FastqPreprocessData {
     pub status: Status,
     pub url: String,
+    #[serde(default)]
+    pub mate_url: Option<String>,
     pub runtime: usize,
     pub result: FastqPreprocessResult,
+    /// Thresholds of this run. A sample can be preprocessed several
+    /// times, with different thresholds, and every run is kept.
+    #[serde(default)]
+    pub config: FastqSampleConfig,
+    /// The HTML report of this run, if uploading it succeeded.
+    #[serde(default)]
+    pub report_url: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -79,8 +170,11 @@ impl FastqPreprocessData {
         Self {
             status: Status::Created,
             url: "http://minio:9000/bucket/preprocessed_key".into(),
+            mate_url: None,
             runtime: 0,
             result: FastqPreprocessResult::mock(),
+            config: FastqSampleConfig::mock(),
+            report_url: None,
             created_at: time_now(),
             updated_at: time_now(),
         }
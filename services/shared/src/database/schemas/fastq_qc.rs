@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+// FastQC-style per-read metrics of a fastq file, for plotting.
+//
+// Long reads would give one entry per base, so positions are binned:
+// every position up to 100, then bins of 10 up to 1000, of 100 up to
+// 10 000 and of 1000 beyond that. Every per-position metric uses the same bins.
+
+/// Number of reads with a value, e.g., a length or a mean phred.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistogramBin {
+    pub value: usize,
+    pub count: usize,
+}
+
+/// Positions `start` up to and including `end`, 1-based.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PositionBin {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Phred scores of every base in a position bin.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QualityDistribution {
+    #[serde(flatten)]
+    pub position: PositionBin,
+    pub mean: f64,
+    pub median: u8,
+    pub lower_quartile: u8,
+    pub upper_quartile: u8,
+    pub percentile_10: u8,
+    pub percentile_90: u8,
+}
+
+/// Fraction of the bases in a position bin, N excluded, see n_content.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BaseComposition {
+    #[serde(flatten)]
+    pub position: PositionBin,
+    pub a: f64,
+    pub c: f64,
+    pub g: f64,
+    pub t: f64,
+}
+
+/// Fraction of N calls in a position bin.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NContent {
+    #[serde(flatten)]
+    pub position: PositionBin,
+    pub n: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FastqQc {
+    /// Reads per length, only lengths that occur.
+    pub length_histogram: Vec<HistogramBin>,
+    /// Reads per mean phred, rounded down, only phreds that occur.
+    pub mean_quality_histogram: Vec<HistogramBin>,
+    /// Reads per GC percentage of their called bases, 0 to 100.
+    pub gc_histogram: Vec<HistogramBin>,
+    pub position_quality: Vec<QualityDistribution>,
+    pub base_composition: Vec<BaseComposition>,
+    pub n_content: Vec<NContent>,
+}
+
+impl FastqQc {
+    pub fn mock() -> Self {
+        Self {
+            length_histogram: Vec::new(),
+            mean_quality_histogram: Vec::new(),
+            gc_histogram: Vec::new(),
+            position_quality: Vec::new(),
+            base_composition: Vec::new(),
+            n_content: Vec::new(),
+        }
+    }
+}
+
+/// Distinct sequences that occur `level` times, e.g., "2" or ">10".
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DuplicationLevel {
+    pub level: String,
+    /// Percentage of the distinct sequences.
+    pub percent_deduplicated: f64,
+    /// Percentage of the reads.
+    pub percent_total: f64,
+}
+
+/// A sequence that makes up a large part of the reads.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OverrepresentedSequence {
+    pub sequence: String,
+    pub count: usize,
+    /// Percentage of all reads, tracked or not.
+    pub percentage: f64,
+    /// The known contaminant or adapter that it matches, if any.
+    pub possible_source: Option<String>,
+}
+
+/// How often sequences occur. Only the first distinct sequences are tracked,
+/// and long reads are cut short, so for large files this is an estimate.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DuplicationMetrics {
+    /// Reads that were tracked, which the percentages are relative to.
+    pub tracked_reads: usize,
+    pub distinct_sequences: usize,
+    /// Percentage of the reads that would be left after deduplication.
+    pub percent_remaining_if_deduplicated: f64,
+    pub duplication_levels: Vec<DuplicationLevel>,
+    /// Most frequent first.
+    pub overrepresented: Vec<OverrepresentedSequence>,
+}
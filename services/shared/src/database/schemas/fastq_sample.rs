@@ -1,23 +1,67 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 use crate::database::schemas::common::SimpleRecordId;
 use crate::utils::time::time_now;
 
 use crate::schema::schema::{Pipeline, Status};
+use crate::schema::trimming::{AdapterTrimConfig, QualityTrimConfig};
+use crate::schema::upload::UploadConfig;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Thresholds that the fastq preprocessor filters the sample with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FastqSampleConfig {
     pub min_len: usize,
     pub max_len: Option<usize>,
     pub min_phred: usize,
+    /// Adapters and primers to trim off, before filtering.
+    #[serde(default)]
+    pub adapter_trim: AdapterTrimConfig,
+    /// Trimming on base quality and position, after adapters and before filtering.
+    #[serde(default)]
+    pub quality_trim: QualityTrimConfig,
 }
 
 impl FastqSampleConfig {
     pub fn mock() -> Self {
+        Self::default()
+    }
+
+    /// Same rules as for the thresholds of the upload form.
+    pub fn validate(&self) -> Result<(), String> {
+        UploadConfig {
+            min_len: Some(self.min_len),
+            max_len: self.max_len,
+            min_phred: Some(self.min_phred),
+            adapter_trim: Some(self.adapter_trim.clone()),
+            quality_trim: Some(self.quality_trim.clone()),
+            ..Default::default()
+        }
+        .validate()
+    }
+
+    /// Use the thresholds from the upload form, with defaults for whatever was left empty.
+    pub fn from_upload_config(config: &UploadConfig) -> Self {
+        let default = Self::default();
+
+        Self {
+            min_len: config.min_len.unwrap_or(default.min_len),
+            max_len: config.max_len.or(default.max_len),
+            min_phred: config.min_phred.unwrap_or(default.min_phred),
+            adapter_trim: config.adapter_trim.clone().unwrap_or(default.adapter_trim),
+            quality_trim: config.quality_trim.clone().unwrap_or(default.quality_trim),
+        }
+    }
+}
+
+impl Default for FastqSampleConfig {
+    fn default() -> Self {
         Self {
             min_len: 200,
             max_len: None,
             min_phred: 15,
+            adapter_trim: AdapterTrimConfig::default(),
+            quality_trim: QualityTrimConfig::default(),
         }
     }
 }
@@ -26,9 +70,26 @@ impl FastqSampleConfig {
 pub struct FastqSampleData {
     pub name: String,
     pub status: Status,
+    /// Why preprocessing failed, only set while the status is error.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// How many times preprocessing has been started.
+    #[serde(default)]
+    pub attempts: usize,
+    /// Correlation id of the message of the current run. Messages of earlier
+    /// runs, e.g., retries still on the stream after a reprocess, are skipped.
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// R1 for paired-end samples.
     pub url: String,
+    /// R2, only set for paired-end samples.
+    #[serde(default)]
+    pub mate_url: Option<String>,
     pub pipeline: Pipeline,
     pub config: FastqSampleConfig,
+    /// Free form metadata, e.g., extra columns of a sample sheet.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -38,15 +99,40 @@ impl FastqSampleData {
         Self {
             name: "sample_name".into(),
             status: Status::Created,
+            error: None,
+            attempts: 0,
+            run_id: None,
             url: "http://minio:9000/bucket/key".into(),
+            mate_url: None,
             pipeline: Pipeline::AmpliconMetgenome,
             config: FastqSampleConfig::mock(),
+            metadata: BTreeMap::new(),
             created_at: time_now(),
             updated_at: time_now(),
         }
     }
 }
 
+impl FastqSampleData {
+    /// Apply the thresholds from the upload form. Identifier
+    /// and comment are stored as metadata.
+    pub fn apply_upload_config(&mut self, config: &UploadConfig) {
+        self.config = FastqSampleConfig::from_upload_config(config);
+
+        if let Some(identifier) = &config.identifier {
+            self.metadata
+                .insert("identifier".into(), identifier.clone());
+        }
+        if let Some(comment) = &config.comment {
+            self.metadata.insert("comment".into(), comment.clone());
+        }
+    }
+
+    pub fn is_paired(&self) -> bool {
+        self.mate_url.is_some()
+    }
+}
+
 /// Practically, we'd relate a user to this database record.
 /// something like person->uploaded->sample.
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,10 +1,14 @@
 pub mod user;
 pub use user::User;
 
+pub mod batch;
 pub mod common;
 pub mod fastq_preprocess;
+pub mod fastq_qc;
 pub mod fastq_sample;
+pub mod outbox;
 pub mod pipelines;
+pub mod upload_session;
 
 // We have a general pattern for structs that are written to our surrealdb database tables.
 //
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::schemas::common::SimpleRecordId;
+use crate::utils::time::time_now;
+
+/// A NATS message that waits to be published. It is written in the same
+/// transaction as the records it is about, so that either both exist or
+/// neither does, and published (then removed) by the outbox relay of the API.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxData {
+    pub subject: String,
+    /// The serialized envelope.
+    pub payload: String,
+    /// Sent as the Nats-Msg-Id header, so that JetStream drops the message
+    /// if the relay publishes it again, e.g., because it died before removing it.
+    pub msg_id: String,
+    /// Failed attempts to publish it, with the last error.
+    #[serde(default)]
+    pub attempts: usize,
+    #[serde(default)]
+    pub error: Option<String>,
+    pub created_at: String,
+}
+
+impl OutboxData {
+    pub fn mock() -> Self {
+        Self {
+            subject: "file-uploaded.process".into(),
+            payload: "{}".into(),
+            msg_id: "0199a1b2-0000-7000-8000-000000000000".into(),
+            attempts: 0,
+            error: None,
+            created_at: time_now(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Outbox {
+    pub id: Option<SimpleRecordId>,
+    #[serde(flatten)]
+    pub data: OutboxData,
+}
+
+impl Outbox {
+    pub fn mock() -> Self {
+        Self {
+            id: None,
+            data: OutboxData::mock(),
+        }
+    }
+}
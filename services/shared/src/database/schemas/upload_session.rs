@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::database::schemas::common::SimpleRecordId;
+
+use crate::schema::schema::{Pipeline, Status};
+use crate::schema::upload::UploadConfig;
+
+/// A part that has been uploaded to MinIO through the API.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadedPart {
+    pub number: u16,
+    pub etag: String,
+    pub size: u64,
+}
+
+/// Keeps track of a MinIO multipart upload. Parts are either uploaded
+/// by the client directly (presigned) or through the API in chunks
+/// (resumable), in which case we keep track of the offset and parts here.
+/// Once all parts are uploaded, the status is set to done and a
+/// FastqSample is created from it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadSessionData {
+    /// Id of the users record that owns the session, e.g., "users:abc123".
+    #[serde(default)]
+    pub user_id: String,
+    pub file_name: String,
+    pub bucket: String,
+    pub key: String,
+    pub upload_id: String,
+    pub size: u64,
+    pub part_size: usize,
+    pub num_parts: usize,
+    pub pipeline: Pipeline,
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default)]
+    pub parts: Vec<UploadedPart>,
+    /// Files of a batch are registered through the batch, not one by one.
+    #[serde(default)]
+    pub batch_id: Option<String>,
+    #[serde(default)]
+    pub config: UploadConfig,
+    pub status: Status,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadSession {
+    pub id: Option<SimpleRecordId>,
+    #[serde(flatten)]
+    pub data: UploadSessionData,
+}
@@ -0,0 +1,50 @@
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum FastqValidationErrorKind {
+    #[error("Corrupt gzip stream")]
+    CorruptGzip(String),
+
+    #[error("File contains no records")]
+    Empty,
+
+    #[error("File ends in the middle of a record")]
+    TruncatedRecord,
+
+    #[error("Line is too long")]
+    LineTooLong,
+
+    #[error("Header line does not start with '@'")]
+    InvalidHeader,
+
+    #[error("Separator line does not start with '+'")]
+    InvalidSeparator,
+
+    #[error("Sequence is empty")]
+    EmptySequence,
+
+    #[error("Sequence contains a non-nucleotide character")]
+    InvalidSequenceCharacter(char),
+
+    #[error("Sequence and quality lengths differ")]
+    LengthMismatch { sequence: usize, quality: usize },
+
+    #[error("Quality character is outside of the valid phred range")]
+    InvalidPhred(char),
+}
+
+/// Describes the first offending record in a file, so that
+/// the user can go and look at it.
+#[derive(Error, Debug, Serialize, Clone, PartialEq)]
+#[error("Invalid FASTQ record {record} (line {line}): {kind}")]
+pub struct FastqValidationError {
+    /// 1-based index of the record.
+    pub record: usize,
+    /// 1-based line number in the decompressed file.
+    pub line: usize,
+    /// Header of the offending record, if we got that far.
+    pub header: Option<String>,
+    pub kind: FastqValidationErrorKind,
+}
@@ -0,0 +1,5 @@
+pub mod validate;
+pub use validate::{FastqValidator, ValidationMode, ValidationSummary};
+
+pub mod errors;
+pub use errors::{FastqValidationError, FastqValidationErrorKind};
@@ -0,0 +1,550 @@
+use flate2::write::MultiGzDecoder;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::fastq::errors::{FastqValidationError, FastqValidationErrorKind};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Sanger/Illumina 1.8+ quality encoding, i.e., '!' (Q0) to '~' (Q93).
+pub const PHRED_OFFSET: u8 = 33;
+pub const MAX_PHRED: u8 = 93;
+
+/// Long (ONT) reads can be a few Mb, but a line longer than this
+/// is almost certainly a binary file without newlines.
+pub const MAX_LINE_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Number of records we check when only validating the start of a file.
+pub const DEFAULT_PREFIX_RECORDS: usize = 10_000;
+
+/// We only keep this much of the header around for error messages.
+const MAX_HEADER_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationMode {
+    /// Only check the first n records.
+    Prefix(usize),
+    /// Check every record, and that the file (and gzip stream) is complete.
+    Full,
+}
+
+impl ValidationMode {
+    /// FASTQ_VALIDATION_MODE="full" validates entire files. Anything
+    /// else (or nothing) only validates the first records.
+    pub fn from_env() -> Self {
+        match std::env::var("FASTQ_VALIDATION_MODE").as_deref() {
+            Ok("full") => Self::Full,
+            _ => Self::Prefix(DEFAULT_PREFIX_RECORDS),
+        }
+    }
+
+    fn max_records(&self) -> Option<usize> {
+        match self {
+            Self::Prefix(n) => Some(*n),
+            Self::Full => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ValidationSummary {
+    pub records: usize,
+    pub bases: u64,
+}
+
+/// Checks decompressed FASTQ, line by line, as it is written to it.
+struct RecordChecker {
+    max_records: Option<usize>,
+    line_buf: Vec<u8>,
+    line_number: usize,
+    // Which of the four lines in a record we expect next.
+    field: usize,
+    records: usize,
+    bases: u64,
+    header: Option<String>,
+    seq_len: usize,
+    error: Option<FastqValidationError>,
+}
+
+impl RecordChecker {
+    fn new(max_records: Option<usize>) -> Self {
+        Self {
+            max_records,
+            line_buf: Vec::new(),
+            line_number: 0,
+            field: 0,
+            records: 0,
+            bases: 0,
+            header: None,
+            seq_len: 0,
+            error: None,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.max_records.is_some_and(|max| self.records >= max)
+    }
+
+    fn error(&self, kind: FastqValidationErrorKind) -> FastqValidationError {
+        FastqValidationError {
+            record: self.records + 1,
+            line: self.line_number,
+            header: self.header.clone(),
+            kind,
+        }
+    }
+
+    fn feed(&mut self, data: &[u8]) -> Result<(), FastqValidationError> {
+        let mut rest = data;
+
+        while !self.is_done() {
+            match rest.iter().position(|&b| b == b'\n') {
+                Some(i) => {
+                    let (line, tail) = rest.split_at(i);
+                    rest = &tail[1..];
+
+                    if self.line_buf.is_empty() {
+                        self.check_line(line)?;
+                    } else {
+                        // Line started in a previous chunk. Take the buffer
+                        // to satisfy the borrow checker, then give it back
+                        // so that we reuse the allocation.
+                        let mut full_line = std::mem::take(&mut self.line_buf);
+                        full_line.extend_from_slice(line);
+                        self.check_line(&full_line)?;
+                        full_line.clear();
+                        self.line_buf = full_line;
+                    }
+                }
+                None => {
+                    if self.line_buf.len() + rest.len() > MAX_LINE_LENGTH {
+                        self.line_number += 1;
+                        return Err(self.error(FastqValidationErrorKind::LineTooLong));
+                    }
+                    self.line_buf.extend_from_slice(rest);
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_line(&mut self, line: &[u8]) -> Result<(), FastqValidationError> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        self.line_number += 1;
+
+        if line.len() > MAX_LINE_LENGTH {
+            return Err(self.error(FastqValidationErrorKind::LineTooLong));
+        }
+
+        match self.field {
+            0 => {
+                // Be lenient with blank lines between records (e.g., at the end of the file).
+                if line.is_empty() {
+                    return Ok(());
+                }
+                if line[0] != b'@' {
+                    return Err(self.error(FastqValidationErrorKind::InvalidHeader));
+                }
+                let header = &line[..line.len().min(MAX_HEADER_LENGTH)];
+                self.header = Some(String::from_utf8_lossy(header).to_string());
+            }
+            1 => {
+                if line.is_empty() {
+                    return Err(self.error(FastqValidationErrorKind::EmptySequence));
+                }
+                if let Some(&c) = line.iter().find(|&&c| !is_nucleotide(c)) {
+                    return Err(
+                        self.error(FastqValidationErrorKind::InvalidSequenceCharacter(
+                            c as char,
+                        )),
+                    );
+                }
+                self.seq_len = line.len();
+            }
+            2 => {
+                if line.first() != Some(&b'+') {
+                    return Err(self.error(FastqValidationErrorKind::InvalidSeparator));
+                }
+            }
+            _ => {
+                if line.len() != self.seq_len {
+                    return Err(self.error(FastqValidationErrorKind::LengthMismatch {
+                        sequence: self.seq_len,
+                        quality: line.len(),
+                    }));
+                }
+                let phred_range = PHRED_OFFSET..=PHRED_OFFSET + MAX_PHRED;
+                if let Some(&c) = line.iter().find(|c| !phred_range.contains(c)) {
+                    return Err(self.error(FastqValidationErrorKind::InvalidPhred(c as char)));
+                }
+                self.records += 1;
+                self.bases += self.seq_len as u64;
+                self.header = None;
+            }
+        }
+
+        self.field = (self.field + 1) % 4;
+        Ok(())
+    }
+
+    /// `complete` means that the entire file has been fed, so a
+    /// partial record at the end is an error rather than a cut-off prefix.
+    fn finish(&mut self, complete: bool) -> Result<ValidationSummary, FastqValidationError> {
+        if complete && !self.is_done() {
+            // The last line might not end with a newline.
+            if !self.line_buf.is_empty() {
+                let last_line = std::mem::take(&mut self.line_buf);
+                self.check_line(&last_line)?;
+            }
+            if self.field != 0 {
+                return Err(self.error(FastqValidationErrorKind::TruncatedRecord));
+            }
+            if self.records == 0 {
+                return Err(self.error(FastqValidationErrorKind::Empty));
+            }
+        }
+
+        Ok(ValidationSummary {
+            records: self.records,
+            bases: self.bases,
+        })
+    }
+}
+
+impl Write for RecordChecker {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Err(e) = self.feed(buf) {
+            self.error = Some(e);
+            return Err(std::io::Error::other("Invalid FASTQ record"));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// IUPAC nucleotide codes, upper or lower (softmasked) case.
+fn is_nucleotide(c: u8) -> bool {
+    matches!(
+        c.to_ascii_uppercase(),
+        b'A' | b'C'
+            | b'G'
+            | b'T'
+            | b'U'
+            | b'N'
+            | b'R'
+            | b'Y'
+            | b'K'
+            | b'M'
+            | b'S'
+            | b'W'
+            | b'B'
+            | b'D'
+            | b'H'
+            | b'V'
+    )
+}
+
+enum Decoder {
+    Gzip(Box<MultiGzDecoder<RecordChecker>>),
+    Plain(RecordChecker),
+}
+
+/// Validates a (possibly gzipped) FASTQ file that arrives in chunks, without
+/// ever holding more than a single line in memory. Checks:
+/// * gzip integrity (full mode only for the trailer/crc).
+/// * four-line record structure ('@' header, '+' separator).
+/// * valid nucleotides in the sequence.
+/// * sequence and quality lengths agree.
+/// * quality characters within the phred range.
+///
+/// Feed the raw file with `push`, then call `finish`. In prefix mode, `is_done`
+/// returns true once enough records have been checked and the rest of the
+/// file can be skipped.
+pub struct FastqValidator {
+    mode: ValidationMode,
+    // Gzip or not is decided from the first two bytes.
+    magic: Vec<u8>,
+    decoder: Option<Decoder>,
+}
+
+impl FastqValidator {
+    pub fn new(mode: ValidationMode) -> Self {
+        Self {
+            mode,
+            magic: Vec::with_capacity(GZIP_MAGIC.len()),
+            decoder: None,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        match &self.decoder {
+            Some(Decoder::Gzip(decoder)) => decoder.get_ref().is_done(),
+            Some(Decoder::Plain(checker)) => checker.is_done(),
+            None => false,
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<(), FastqValidationError> {
+        if self.decoder.is_none() {
+            self.magic.extend_from_slice(chunk);
+            if self.magic.len() < GZIP_MAGIC.len() {
+                return Ok(());
+            }
+
+            let checker = RecordChecker::new(self.mode.max_records());
+            self.decoder = Some(match self.magic.starts_with(&GZIP_MAGIC) {
+                true => Decoder::Gzip(Box::new(MultiGzDecoder::new(checker))),
+                false => Decoder::Plain(checker),
+            });
+
+            let magic = std::mem::take(&mut self.magic);
+            return self.write(&magic);
+        }
+
+        self.write(chunk)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), FastqValidationError> {
+        if self.is_done() {
+            return Ok(());
+        }
+
+        match self.decoder.as_mut() {
+            Some(Decoder::Gzip(decoder)) => match decoder.write_all(data) {
+                Ok(()) => Ok(()),
+                Err(e) => Err(gzip_error(decoder, e)),
+            },
+            Some(Decoder::Plain(checker)) => checker.feed(data),
+            None => Ok(()),
+        }
+    }
+
+    /// `complete` should be true if the entire file was pushed,
+    /// and false if we stopped early (e.g., only have the first chunk).
+    pub fn finish(mut self, complete: bool) -> Result<ValidationSummary, FastqValidationError> {
+        // Fewer than two bytes in total, which can't be gzip.
+        if self.decoder.is_none() {
+            let mut checker = RecordChecker::new(self.mode.max_records());
+            checker.feed(&self.magic)?;
+            return checker.finish(complete);
+        }
+
+        match self.decoder.as_mut() {
+            Some(Decoder::Gzip(decoder)) => {
+                if complete
+                    && !decoder.get_ref().is_done()
+                    && let Err(e) = decoder.try_finish()
+                {
+                    return Err(gzip_error(decoder, e));
+                }
+                decoder.get_mut().finish(complete)
+            }
+            Some(Decoder::Plain(checker)) => checker.finish(complete),
+            None => unreachable!(),
+        }
+    }
+}
+
+/// The decoder fails either because the gzip stream is corrupt, or because
+/// the FASTQ inside it is invalid, in which case the checker has the details.
+fn gzip_error(
+    decoder: &mut MultiGzDecoder<RecordChecker>,
+    err: std::io::Error,
+) -> FastqValidationError {
+    let checker = decoder.get_mut();
+    match checker.error.take() {
+        Some(e) => e,
+        None => checker.error(FastqValidationErrorKind::CorruptGzip(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+
+    const RECORDS: &[u8] = b"@read1\nACGT\n+\nIIII\n@read2\nACGTN\n+read2\n!!~~#\n";
+
+    fn validate(
+        data: &[u8],
+        mode: ValidationMode,
+        complete: bool,
+    ) -> Result<ValidationSummary, FastqValidationError> {
+        let mut validator = FastqValidator::new(mode);
+        validator.push(data)?;
+        validator.finish(complete)
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn error_kind(data: &[u8]) -> FastqValidationErrorKind {
+        validate(data, ValidationMode::Full, true).unwrap_err().kind
+    }
+
+    #[test]
+    fn valid_records() {
+        let summary = validate(RECORDS, ValidationMode::Full, true).unwrap();
+        assert_eq!(summary.records, 2);
+        assert_eq!(summary.bases, 9);
+    }
+
+    #[test]
+    fn valid_records_in_single_byte_chunks() {
+        let mut validator = FastqValidator::new(ValidationMode::Full);
+        for byte in RECORDS {
+            validator.push(&[*byte]).unwrap();
+        }
+        assert_eq!(validator.finish(true).unwrap().records, 2);
+    }
+
+    #[test]
+    fn crlf_and_missing_final_newline() {
+        let summary = validate(b"@r\r\nAC\r\n+\r\nII", ValidationMode::Full, true).unwrap();
+        assert_eq!(summary.records, 1);
+    }
+
+    #[test]
+    fn malformed_records() {
+        assert_eq!(
+            error_kind(b"read1\nACGT\n+\nIIII\n"),
+            FastqValidationErrorKind::InvalidHeader
+        );
+        assert_eq!(
+            error_kind(b"@read1\nACGT\n-\nIIII\n"),
+            FastqValidationErrorKind::InvalidSeparator
+        );
+        assert_eq!(
+            error_kind(b"@read1\n\n+\n\n"),
+            FastqValidationErrorKind::EmptySequence
+        );
+        assert_eq!(
+            error_kind(b"@read1\nACXT\n+\nIIII\n"),
+            FastqValidationErrorKind::InvalidSequenceCharacter('X')
+        );
+        assert_eq!(
+            error_kind(b"@read1\nACGT\n+\nIII\n"),
+            FastqValidationErrorKind::LengthMismatch {
+                sequence: 4,
+                quality: 3
+            }
+        );
+    }
+
+    #[test]
+    fn error_points_at_the_offending_record() {
+        let error = validate(
+            b"@read1\nACGT\n+\nIIII\n@read2\nACGT\n+\nII\n",
+            ValidationMode::Full,
+            true,
+        )
+        .unwrap_err();
+        assert_eq!(error.record, 2);
+        assert_eq!(error.line, 8);
+        assert_eq!(error.header.as_deref(), Some("@read2"));
+    }
+
+    #[test]
+    fn phred_range() {
+        // '!' (Q0) and '~' (Q93) are the bounds.
+        assert!(validate(b"@r\nAC\n+\n!~\n", ValidationMode::Full, true).is_ok());
+        assert_eq!(
+            error_kind(b"@r\nAC\n+\nI \n"),
+            FastqValidationErrorKind::InvalidPhred(' ')
+        );
+        assert_eq!(
+            error_kind(b"@r\nAC\n+\nI\x7f\n"),
+            FastqValidationErrorKind::InvalidPhred('\x7f')
+        );
+    }
+
+    #[test]
+    fn truncated_and_empty_files() {
+        assert_eq!(
+            error_kind(b"@read1\nACGT\n+\nIIII\n@read2\nACGT\n"),
+            FastqValidationErrorKind::TruncatedRecord
+        );
+        assert_eq!(error_kind(b""), FastqValidationErrorKind::Empty);
+        assert_eq!(error_kind(b"\n"), FastqValidationErrorKind::Empty);
+
+        // Only the start of the file, so a partial record is expected.
+        assert!(
+            validate(
+                b"@read1\nACGT\n+\nIIII\n@read2\nAC",
+                ValidationMode::Full,
+                false
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn prefix_mode_stops_after_enough_records() {
+        let mut data = b"@read1\nACGT\n+\nIIII\n".to_vec();
+        data.extend_from_slice(b"not fastq at all");
+
+        let mut validator = FastqValidator::new(ValidationMode::Prefix(1));
+        validator.push(&data).unwrap();
+        assert!(validator.is_done());
+        assert_eq!(validator.finish(true).unwrap().records, 1);
+    }
+
+    #[test]
+    fn gzipped_records() {
+        let summary = validate(&gzip(RECORDS), ValidationMode::Full, true).unwrap();
+        assert_eq!(summary.records, 2);
+
+        let error = validate(&gzip(b"@r\nAC\n+\nI\n"), ValidationMode::Full, true).unwrap_err();
+        assert_eq!(
+            error.kind,
+            FastqValidationErrorKind::LengthMismatch {
+                sequence: 2,
+                quality: 1
+            }
+        );
+    }
+
+    #[test]
+    fn gzip_truncation() {
+        let data: Vec<u8> = (0..2000)
+            .flat_map(|i: u32| {
+                let sequence: String = (0..16)
+                    .map(|b| ['A', 'C', 'G', 'T'][(i >> b) as usize % 4])
+                    .collect();
+                format!("@read{i}\n{sequence}\n+\n{}\n", "I".repeat(16)).into_bytes()
+            })
+            .collect();
+        let compressed = gzip(&data);
+        let truncated = &compressed[..compressed.len() / 2];
+
+        let error = validate(truncated, ValidationMode::Full, true).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            FastqValidationErrorKind::CorruptGzip(_)
+        ));
+
+        // The first chunk of an upload is cut off as well.
+        let summary = validate(truncated, ValidationMode::Prefix(1), false).unwrap();
+        assert_eq!(summary.records, 1);
+    }
+
+    #[test]
+    fn corrupt_gzip() {
+        let mut compressed = gzip(RECORDS);
+        compressed[3] = 0xff;
+
+        let error = validate(&compressed, ValidationMode::Full, true).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            FastqValidationErrorKind::CorruptGzip(_)
+        ));
+    }
+}
@@ -1,6 +1,9 @@
 #[cfg(feature = "database")]
 pub mod database;
 
+#[cfg(feature = "fastq")]
+pub mod fastq;
+
 #[cfg(feature = "minio")]
 pub mod minio;
 
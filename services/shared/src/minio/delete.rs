@@ -0,0 +1,15 @@
+use crate::minio::errors::MinIoError;
+use log::info;
+use minio::s3::Client;
+use minio::s3::types::S3Api;
+
+pub async fn minio_delete_object(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<(), MinIoError> {
+    client.delete_object(bucket, key).send().await?;
+    info!("Deleted {}/{}", bucket, key);
+
+    Ok(())
+}
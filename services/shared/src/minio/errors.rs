@@ -17,6 +17,9 @@ pub enum MinIoError {
 
     #[error("Failed to write contenst to file")]
     FileWriteError(String),
+
+    #[error("Multipart upload part size is below the 5Mb minimum")]
+    PartSizeError(usize),
 }
 
 impl From<minio::s3::error::Error> for MinIoError {
@@ -0,0 +1,41 @@
+use crate::minio::errors::MinIoError;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use minio::s3::Client;
+use minio::s3::types::{S3Api, ToStream};
+
+#[derive(Debug, Clone)]
+pub struct MinioObject {
+    pub key: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Every object in `bucket`, or none if the bucket doesn't exist.
+pub async fn minio_list_objects(
+    client: &Client,
+    bucket: &str,
+) -> Result<Vec<MinioObject>, MinIoError> {
+    if !client.bucket_exists(bucket).send().await?.exists {
+        return Ok(Vec::new());
+    }
+
+    let mut objects: Vec<MinioObject> = Vec::new();
+    let mut pages = client
+        .list_objects(bucket)
+        .recursive(true)
+        .to_stream()
+        .await;
+    while let Some(page) = pages.next().await {
+        for entry in page?.contents {
+            if entry.is_prefix {
+                continue;
+            }
+            objects.push(MinioObject {
+                key: entry.name,
+                last_modified: entry.last_modified,
+            });
+        }
+    }
+
+    Ok(objects)
+}
@@ -2,10 +2,22 @@ pub mod connection;
 pub use connection::connect_minio;
 
 pub mod upload;
-pub use upload::{minio_upload_bytes, minio_upload_file};
+pub use upload::{minio_upload_bytes, minio_upload_bytes_as, minio_upload_file};
+
+pub mod multipart;
+pub use multipart::MultipartUpload;
+
+pub mod presign;
+pub use presign::Presigner;
 
 pub mod download;
 pub use download::minio_download;
 
+pub mod delete;
+pub use delete::minio_delete_object;
+
+pub mod list;
+pub use list::{MinioObject, minio_list_objects};
+
 pub mod errors;
 pub use errors::MinIoError;
@@ -0,0 +1,222 @@
+use crate::minio::errors::MinIoError;
+use crate::minio::upload::{create_bucket_if_not_exists, object_url};
+use bytes::{Bytes, BytesMut};
+use log::{error, info};
+use minio::s3::{Client, segmented_bytes::SegmentedBytes, types::PartInfo, types::S3Api};
+
+/// MinIO (S3) requires every part except the last one to be at least 5Mb.
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// We buffer a bit more than the minimum before flushing a part, to keep
+/// the number of parts (max 10 000) reasonable for very large files.
+pub const DEFAULT_PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// S3 does not allow more parts than this for a single upload.
+pub const MAX_PARTS: usize = 10_000;
+
+/// Streams data into a MinIO multipart upload, one chunk at a time.
+///
+/// Chunks are buffered until at least `part_size` bytes are available,
+/// at which point a part is uploaded and the buffer is cleared. This means
+/// memory usage is bounded by roughly `part_size` regardless of file size.
+///
+/// Either `complete` or `abort` should be called when done, otherwise
+/// MinIO keeps the uploaded parts around.
+pub struct MultipartUpload<'a> {
+    client: &'a Client,
+    bucket: String,
+    key: String,
+    upload_id: String,
+    part_size: usize,
+    buffer: BytesMut,
+    parts: Vec<PartInfo>,
+    total_bytes: u64,
+}
+
+impl<'a> MultipartUpload<'a> {
+    pub async fn new(client: &'a Client, bucket: &str, key: &str) -> Result<Self, MinIoError> {
+        Self::with_part_size(client, bucket, key, DEFAULT_PART_SIZE).await
+    }
+
+    pub async fn with_part_size(
+        client: &'a Client,
+        bucket: &str,
+        key: &str,
+        part_size: usize,
+    ) -> Result<Self, MinIoError> {
+        if part_size < MIN_PART_SIZE {
+            return Err(MinIoError::PartSizeError(part_size));
+        }
+
+        let upload_id = create_multipart_upload(client, bucket, key).await?;
+
+        Ok(Self {
+            client,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            upload_id,
+            part_size,
+            buffer: BytesMut::with_capacity(part_size),
+            parts: Vec::new(),
+            total_bytes: 0,
+        })
+    }
+
+    /// Number of bytes received so far (uploaded or buffered).
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    pub async fn write(&mut self, chunk: Bytes) -> Result<(), MinIoError> {
+        self.total_bytes += chunk.len() as u64;
+        self.buffer.extend_from_slice(&chunk);
+
+        while self.buffer.len() >= self.part_size {
+            let part = self.buffer.split_to(self.part_size).freeze();
+            self.upload_part(part).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn upload_part(&mut self, part: Bytes) -> Result<(), MinIoError> {
+        // Part numbers start at 1.
+        let part_number = (self.parts.len() + 1) as u16;
+
+        let part_info = upload_part(
+            self.client,
+            &self.bucket,
+            &self.key,
+            &self.upload_id,
+            part_number,
+            part,
+        )
+        .await?;
+
+        self.parts.push(part_info);
+
+        Ok(())
+    }
+
+    /// Flush whatever is left in the buffer as the last part and
+    /// complete the upload. Returns the url of the uploaded object.
+    pub async fn complete(mut self) -> Result<String, MinIoError> {
+        // The last part is allowed to be smaller than MIN_PART_SIZE. We also
+        // need at least one part, even for empty files.
+        if !self.buffer.is_empty() || self.parts.is_empty() {
+            let part = self.buffer.split().freeze();
+            self.upload_part(part).await?;
+        }
+
+        info!(
+            "Completing multipart upload of {} ({} parts, {} bytes)",
+            self.key,
+            self.parts.len(),
+            self.total_bytes
+        );
+
+        complete_multipart_upload(
+            self.client,
+            &self.bucket,
+            &self.key,
+            &self.upload_id,
+            self.parts,
+        )
+        .await
+    }
+
+    /// Discard all uploaded parts.
+    pub async fn abort(self) -> Result<(), MinIoError> {
+        abort_multipart_upload(self.client, &self.bucket, &self.key, &self.upload_id).await
+    }
+}
+
+// The functions below are the building blocks of a multipart upload. They are
+// used directly when parts are uploaded across several requests (or by the
+// client itself through presigned urls) and we only keep track of the upload id.
+
+/// Pick a part size so that a file of `size` bytes fits within the
+/// 10 000 part limit, while never going below DEFAULT_PART_SIZE.
+pub fn part_size_for(size: u64) -> usize {
+    let min_for_size = size.div_ceil(MAX_PARTS as u64) as usize;
+    min_for_size.max(DEFAULT_PART_SIZE)
+}
+
+pub async fn create_multipart_upload(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<String, MinIoError> {
+    create_bucket_if_not_exists(bucket, client).await?;
+
+    info!("Creating multipart upload for {} in bucket {}", key, bucket);
+    let response = client.create_multipart_upload(bucket, key).send().await?;
+
+    Ok(response.upload_id)
+}
+
+pub async fn upload_part(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    part_number: u16,
+    part: Bytes,
+) -> Result<PartInfo, MinIoError> {
+    let size = part.len() as u64;
+
+    let segbuf: SegmentedBytes = part.into();
+    let response = client
+        .upload_part(bucket, key, upload_id, part_number, segbuf)
+        .send()
+        .await?;
+
+    Ok(PartInfo {
+        number: part_number,
+        etag: response.etag,
+        size,
+    })
+}
+
+/// Returns the url of the uploaded object.
+pub async fn complete_multipart_upload(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+    parts: Vec<PartInfo>,
+) -> Result<String, MinIoError> {
+    client
+        .complete_multipart_upload(bucket, key, upload_id, parts)
+        .send()
+        .await?;
+
+    object_url(bucket, key)
+}
+
+pub async fn abort_multipart_upload(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+) -> Result<(), MinIoError> {
+    info!("Aborting multipart upload of {}", key);
+
+    match client
+        .abort_multipart_upload(bucket, key, upload_id)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("Failed to abort multipart upload: {:?}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Size in bytes of an existing object.
+pub async fn object_size(client: &Client, bucket: &str, key: &str) -> Result<u64, MinIoError> {
+    let response = client.stat_object(bucket, key).send().await?;
+    Ok(response.size)
+}
@@ -1,7 +1,12 @@
 use crate::minio::errors::MinIoError;
 use bytes::Bytes;
 use log::{error, info};
-use minio::s3::{Client, segmented_bytes::SegmentedBytes, types::S3Api};
+use minio::s3::{
+    Client,
+    multimap::{Multimap, MultimapExt},
+    segmented_bytes::SegmentedBytes,
+    types::S3Api,
+};
 use std::io::Read;
 use std::path::PathBuf;
 
@@ -41,9 +46,41 @@ pub async fn minio_upload_bytes(
     let response = client.put_object(bucket, key, file_contents).send().await?;
     info!("MinIO response: {:?}", response);
 
-    // Does not seem like minio really returns a url, so we construct it here.
-    let url = format!("http://{}/{bucket}/{key}", std::env::var("MINIO_ENDPOINT")?);
-    Ok(url)
+    object_url(bucket, key)
+}
+
+/// Like minio_upload_bytes, but stored with a Content-Type other than
+/// application/octet-stream, e.g., so that a browser shows an HTML
+/// report instead of downloading it.
+pub async fn minio_upload_bytes_as(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    file_contents: SegmentedBytes,
+    content_type: &str,
+) -> Result<String, MinIoError> {
+    create_bucket_if_not_exists(bucket, client).await?;
+
+    let mut headers = Multimap::new();
+    headers.add("Content-Type", content_type);
+
+    info!("Uploading {} ({}) to bucket {}", key, content_type, bucket);
+    let response = client
+        .put_object(bucket, key, file_contents)
+        .extra_headers(Some(headers))
+        .send()
+        .await?;
+    info!("MinIO response: {:?}", response);
+
+    object_url(bucket, key)
+}
+
+/// Does not seem like minio really returns a url, so we construct it here.
+pub fn object_url(bucket: &str, key: &str) -> Result<String, MinIoError> {
+    Ok(format!(
+        "http://{}/{bucket}/{key}",
+        std::env::var("MINIO_ENDPOINT")?
+    ))
 }
 
 /// This essentially is just some boilerplate for converting
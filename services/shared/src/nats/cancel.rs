@@ -0,0 +1,29 @@
+use async_nats::jetstream::Context as NatsContext;
+
+use crate::nats::NatsError;
+use crate::nats::schema::cancel::CancelMessage;
+use crate::nats::schema::envelope::Envelope;
+
+/// Matches the job-cancel.* subjects of the JobCancel stream.
+pub const JOB_CANCEL_SUBJECT: &str = "job-cancel.requested";
+
+/// Tell the workers to stop the job `job_id`, if one of them is running it.
+pub async fn publish_job_cancel(
+    jetstream: &NatsContext,
+    user_id: &str,
+    job_id: &str,
+) -> Result<(), NatsError> {
+    let envelope = Envelope::new(
+        user_id,
+        CancelMessage {
+            job_id: job_id.to_string(),
+        },
+    );
+
+    let ack = jetstream
+        .publish(JOB_CANCEL_SUBJECT, envelope.to_bytes()?.into())
+        .await?;
+    ack.await?;
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use async_nats::jetstream::Context as NatsContext;
+use async_nats::jetstream::stream::{RawMessageErrorKind, Stream};
+
+use crate::nats::NatsError;
+use crate::nats::schema::dead_letter::{DeadLetter, DeadLetterMessage};
+use crate::nats::streams::config::{StreamConsumerConfig, StreamType};
+use crate::nats::streams::stream::read_stream_messages;
+use crate::utils::time::time_now;
+
+fn dead_letter_stream_name() -> String {
+    StreamConsumerConfig::from(StreamType::DeadLetter)
+        .stream
+        .name
+}
+
+/// Move a message that failed on every delivery to the dead-letter stream,
+/// under dead-letter.<stream>, together with why it failed.
+pub async fn publish_dead_letter(
+    jetstream: &NatsContext,
+    stream_type: StreamType,
+    subject: &str,
+    payload: &[u8],
+    error: String,
+    deliveries: i64,
+) -> Result<(), NatsError> {
+    let stream_name = StreamConsumerConfig::from(stream_type).stream.name;
+
+    let message = DeadLetterMessage {
+        subject: subject.to_string(),
+        payload: String::from_utf8_lossy(payload).to_string(),
+        error,
+        deliveries,
+        failed_at: time_now(),
+    };
+
+    let ack = jetstream
+        .publish(
+            format!("{}.{}", dead_letter_stream_name(), stream_name),
+            serde_json::to_string(&message)?.into(),
+        )
+        .await?;
+    ack.await?;
+
+    Ok(())
+}
+
+/// Up to `limit` dead letters, oldest first, starting at sequence `start`.
+/// Also returns the sequence to continue from, if there are more.
+pub async fn list_dead_letters(
+    jetstream: &NatsContext,
+    start: Option<u64>,
+    limit: usize,
+) -> Result<(Vec<DeadLetter>, Option<u64>), NatsError> {
+    let stream = jetstream.get_stream(dead_letter_stream_name()).await?;
+    let state = stream
+        .get_info()
+        .await
+        .map_err(|err| NatsError::StreamMessageError(err.to_string()))?
+        .state;
+
+    // Replayed messages are deleted, which leaves gaps in the sequence.
+    let start = start
+        .unwrap_or(state.first_sequence)
+        .max(state.first_sequence);
+
+    let mut dead_letters: Vec<DeadLetter> = Vec::new();
+    if state.messages == 0 || start > state.last_sequence {
+        return Ok((dead_letters, None));
+    }
+
+    for message in read_stream_messages(&stream, start, limit).await? {
+        let sequence = message
+            .info()
+            .map_err(|err| NatsError::StreamMessageError(err.to_string()))?
+            .stream_sequence;
+        let message: DeadLetterMessage = serde_json::from_slice(&message.payload)?;
+        dead_letters.push(DeadLetter { sequence, message });
+    }
+
+    // Fewer than `limit` means that we got to the end.
+    let next = match dead_letters.last() {
+        Some(last) if dead_letters.len() == limit && last.sequence < state.last_sequence => {
+            Some(last.sequence + 1)
+        }
+        _ => None,
+    };
+
+    Ok((dead_letters, next))
+}
+
+/// The dead letter at `sequence`, if there is one.
+pub async fn get_dead_letter(
+    jetstream: &NatsContext,
+    sequence: u64,
+) -> Result<Option<DeadLetter>, NatsError> {
+    let stream = jetstream.get_stream(dead_letter_stream_name()).await?;
+
+    read_dead_letter(&stream, sequence).await
+}
+
+async fn read_dead_letter(stream: &Stream, sequence: u64) -> Result<Option<DeadLetter>, NatsError> {
+    let raw = match stream.get_raw_message(sequence).await {
+        Ok(raw) => raw,
+        // Deleted, or never existed.
+        Err(err) if err.kind() == RawMessageErrorKind::NoMessageFound => return Ok(None),
+        Err(err) => return Err(NatsError::StreamMessageError(err.to_string())),
+    };
+
+    let message: DeadLetterMessage = serde_json::from_slice(&raw.payload)?;
+
+    Ok(Some(DeadLetter { sequence, message }))
+}
+
+/// Publish a dead letter to where it originally came from, so that it is
+/// processed again, and remove it from the dead-letter stream.
+pub async fn replay_dead_letter(
+    jetstream: &NatsContext,
+    dead_letter: &DeadLetter,
+) -> Result<(), NatsError> {
+    let ack = jetstream
+        .publish(
+            dead_letter.message.subject.clone(),
+            dead_letter.message.payload.clone().into(),
+        )
+        .await?;
+    ack.await?;
+
+    let stream = jetstream.get_stream(dead_letter_stream_name()).await?;
+    stream
+        .delete_message(dead_letter.sequence)
+        .await
+        .map_err(|err| NatsError::StreamMessageError(err.to_string()))?;
+
+    Ok(())
+}
@@ -29,6 +29,18 @@ pub enum NatsError {
     #[error("NATS publish error")]
     NatsPublishError(String),
 
+    #[error("Failed to acknowledge message")]
+    MessageAckError(String),
+
+    #[error("Unexpected message type")]
+    MessageTypeError(String),
+
+    #[error("Unsupported message version")]
+    MessageVersionError(String),
+
+    #[error("Failed to read stream message")]
+    StreamMessageError(String),
+
     #[error("IO error")]
     IoError(String),
 
@@ -4,5 +4,11 @@ pub use connection::connect_nats;
 pub mod errors;
 pub use errors::NatsError;
 
+pub mod cancel;
+pub mod dead_letter;
+pub mod pending;
 pub mod schema;
+pub mod status;
 pub mod streams;
+pub mod worker;
+pub use worker::{Worker, WorkerConfig, run_worker};
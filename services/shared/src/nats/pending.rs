@@ -0,0 +1,49 @@
+use async_nats::jetstream::Context as NatsContext;
+
+use crate::nats::NatsError;
+use crate::nats::schema::envelope::{Envelope, Payload};
+use crate::nats::streams::config::{StreamConsumerConfig, StreamType};
+use crate::nats::streams::stream::{get_consumer_from_stream_type, read_stream_messages};
+
+/// Messages on the stream that its consumer hasn't acknowledged yet, i.e.,
+/// jobs that are queued, running or waiting for a retry.
+/// Messages that don't parse are left out, they end up as dead letters anyway.
+pub async fn unacked_messages<T: Payload>(
+    jetstream: &NatsContext,
+    stream_type: StreamType,
+) -> Result<Vec<Envelope<T>>, NatsError> {
+    let stream_name = StreamConsumerConfig::from(stream_type).stream.name;
+
+    let mut consumer = get_consumer_from_stream_type(jetstream, stream_type).await?;
+    let ack_floor = consumer
+        .info()
+        .await
+        .map_err(|err| NatsError::GetConsumerError(err.to_string()))?
+        .ack_floor
+        .stream_sequence;
+
+    let stream = jetstream.get_stream(stream_name).await?;
+    let state = stream
+        .get_info()
+        .await
+        .map_err(|err| NatsError::StreamMessageError(err.to_string()))?
+        .state;
+
+    let mut messages: Vec<Envelope<T>> = Vec::new();
+    if state.messages == 0 || ack_floor >= state.last_sequence {
+        return Ok(messages);
+    }
+
+    // Everything up to the ack floor is acknowledged. Above it, some messages
+    // may be acknowledged already, which we can't tell apart from
+    // unacknowledged ones here, so this errs on the side of too many.
+    let start = (ack_floor + 1).max(state.first_sequence);
+    let limit = (state.last_sequence - start + 1) as usize;
+    for message in read_stream_messages(&stream, start, limit).await? {
+        if let Ok(envelope) = Envelope::<T>::from_slice(&message.payload) {
+            messages.push(envelope);
+        }
+    }
+
+    Ok(messages)
+}
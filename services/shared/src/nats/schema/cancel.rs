@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+use crate::nats::schema::envelope::Payload;
+
+/// Asks the workers to stop a job, inside an Envelope. Jobs that
+/// have not started yet are skipped by the workers on their own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelMessage {
+    /// What Worker::job_id returns for the job, e.g., the id of the sample
+    /// without the table name.
+    pub job_id: String,
+}
+
+impl Payload for CancelMessage {
+    const MESSAGE_TYPE: &'static str = "job.cancel";
+    const VERSION: u32 = 1;
+}
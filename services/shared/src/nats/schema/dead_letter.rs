@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// A message that failed on every delivery, as stored in the dead-letter stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetterMessage {
+    /// Where the message was originally published, and is replayed to.
+    pub subject: String,
+    /// The original message, e.g., an Envelope with a FastqMessage.
+    pub payload: String,
+    /// Why the last delivery failed.
+    pub error: String,
+    pub deliveries: i64,
+    pub failed_at: String,
+}
+
+/// A dead-lettered message and its sequence in the dead-letter stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub message: DeadLetterMessage,
+}
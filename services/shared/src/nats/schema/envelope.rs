@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::nats::NatsError;
+use crate::utils::time::time_now;
+
+/// A message type that is published on NATS, wrapped in an Envelope.
+///
+/// Bump VERSION whenever the payload changes in a way that older
+/// messages no longer deserialize, and teach upcast how to convert them.
+/// Messages are kept on the stream (and in the dead-letter stream) for a
+/// while, so consumers must keep reading every version they might still find.
+pub trait Payload: Serialize + DeserializeOwned {
+    /// e.g., "fastq.preprocess".
+    const MESSAGE_TYPE: &'static str;
+    const VERSION: u32;
+
+    /// Convert the payload of a message of `version` to the current version.
+    /// Version 0 is the bare payload, from before messages had an envelope.
+    fn upcast(version: u32, payload: Value) -> Result<Value, NatsError> {
+        let _ = version;
+        Ok(payload)
+    }
+}
+
+/// What we actually publish on NATS, e.g.,
+///
+/// {
+///     "message_type": "fastq.preprocess",
+///     "version": 1,
+///     "correlation_id": "0199a1b2-...",
+///     "user_id": "users:abc123",
+///     "created_at": "2025-01-31 12:00:00",
+///     "payload": {...}
+/// }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    pub message_type: String,
+    pub version: u32,
+    /// Follows a job through every service and retry, for tracing it in the logs.
+    pub correlation_id: String,
+    /// Id of the users record the message is on behalf of, e.g., "users:abc123".
+    pub user_id: String,
+    pub created_at: String,
+    pub payload: T,
+}
+
+impl<T: Payload> Envelope<T> {
+    pub fn new(user_id: &str, payload: T) -> Self {
+        Self {
+            message_type: T::MESSAGE_TYPE.to_string(),
+            version: T::VERSION,
+            correlation_id: uuid::Uuid::now_v7().to_string(),
+            user_id: user_id.to_string(),
+            created_at: time_now(),
+            payload,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, NatsError> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Parse a message of any version, upcast to the current one.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, NatsError> {
+        let raw: Value = serde_json::from_slice(bytes)?;
+        let envelope = match raw.get("message_type") {
+            Some(_) => serde_json::from_value::<Envelope<Value>>(raw)?,
+            None => Self::legacy(raw, bytes),
+        };
+
+        if envelope.message_type != T::MESSAGE_TYPE {
+            return Err(NatsError::MessageTypeError(format!(
+                "Expected {}, got {}",
+                T::MESSAGE_TYPE,
+                envelope.message_type
+            )));
+        }
+
+        if envelope.version > T::VERSION {
+            return Err(NatsError::MessageVersionError(format!(
+                "{} version {} is newer than the supported version {}",
+                envelope.message_type,
+                envelope.version,
+                T::VERSION
+            )));
+        }
+
+        let payload = T::upcast(envelope.version, envelope.payload)?;
+
+        Ok(Self {
+            message_type: envelope.message_type,
+            version: T::VERSION,
+            correlation_id: envelope.correlation_id,
+            user_id: envelope.user_id,
+            created_at: envelope.created_at,
+            payload: serde_json::from_value(payload)?,
+        })
+    }
+
+    /// A bare payload, published before messages had an envelope. They had
+    /// no user id, and no correlation id, so they get one now. The id is
+    /// derived from the message, so that every redelivery gets the same.
+    fn legacy(payload: Value, bytes: &[u8]) -> Envelope<Value> {
+        Envelope {
+            message_type: T::MESSAGE_TYPE.to_string(),
+            version: 0,
+            correlation_id: legacy_correlation_id(bytes),
+            user_id: String::new(),
+            created_at: time_now(),
+            payload,
+        }
+    }
+}
+
+/// 128 bit FNV-1a of the message, as a uuid. Unlike the hashers of std,
+/// it is the same across builds, so replicas agree on it.
+fn legacy_correlation_id(bytes: &[u8]) -> String {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    let hash = bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u128).wrapping_mul(PRIME)
+    });
+
+    uuid::Uuid::from_u128(hash).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Version 0 and 1 called the name `who`, version 2 added `loud`.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Greeting {
+        name: String,
+        #[serde(default)]
+        loud: bool,
+    }
+
+    impl Payload for Greeting {
+        const MESSAGE_TYPE: &'static str = "test.greeting";
+        const VERSION: u32 = 2;
+
+        fn upcast(version: u32, mut payload: Value) -> Result<Value, NatsError> {
+            if version < 2
+                && let Some(who) = payload.as_object_mut().and_then(|p| p.remove("who"))
+            {
+                payload["name"] = who;
+            }
+            Ok(payload)
+        }
+    }
+
+    fn envelope(message_type: &str, version: u32, payload: Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "message_type": message_type,
+            "version": version,
+            "correlation_id": "0199a1b2-0000-7000-8000-000000000000",
+            "user_id": "users:abc123",
+            "created_at": "2025-01-31 12:00:00",
+            "payload": payload,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let sent = Envelope::new(
+            "users:abc123",
+            Greeting {
+                name: "Ada".into(),
+                loud: true,
+            },
+        );
+        let received = Envelope::<Greeting>::from_slice(&sent.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(received.payload, sent.payload);
+        assert_eq!(received.version, 2);
+        assert_eq!(received.correlation_id, sent.correlation_id);
+        assert_eq!(received.user_id, "users:abc123");
+    }
+
+    #[test]
+    fn upcast_older_version() {
+        let bytes = envelope("test.greeting", 1, json!({"who": "Ada"}));
+        let received = Envelope::<Greeting>::from_slice(&bytes).unwrap();
+
+        assert_eq!(
+            received.payload,
+            Greeting {
+                name: "Ada".into(),
+                loud: false
+            }
+        );
+        assert_eq!(received.version, 2);
+        assert_eq!(
+            received.correlation_id,
+            "0199a1b2-0000-7000-8000-000000000000"
+        );
+    }
+
+    #[test]
+    fn legacy_message() {
+        let bytes = serde_json::to_vec(&json!({"who": "Ada"})).unwrap();
+        let received = Envelope::<Greeting>::from_slice(&bytes).unwrap();
+
+        assert_eq!(received.payload.name, "Ada");
+        assert_eq!(received.version, 2);
+        assert_eq!(received.user_id, "");
+
+        // Every redelivery is traced under the same id, but other messages are not.
+        let again = Envelope::<Greeting>::from_slice(&bytes).unwrap();
+        assert_eq!(again.correlation_id, received.correlation_id);
+        let other = serde_json::to_vec(&json!({"who": "Grace"})).unwrap();
+        let other = Envelope::<Greeting>::from_slice(&other).unwrap();
+        assert_ne!(other.correlation_id, received.correlation_id);
+    }
+
+    #[test]
+    fn reject_other_message_type() {
+        let bytes = envelope("test.farewell", 2, json!({"name": "Ada"}));
+
+        assert!(matches!(
+            Envelope::<Greeting>::from_slice(&bytes),
+            Err(NatsError::MessageTypeError(_))
+        ));
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let bytes = envelope("test.greeting", 3, json!({"name": "Ada"}));
+
+        assert!(matches!(
+            Envelope::<Greeting>::from_slice(&bytes),
+            Err(NatsError::MessageVersionError(_))
+        ));
+    }
+}
@@ -1,10 +1,29 @@
 use serde::{Deserialize, Serialize};
 
 use crate::database::schemas::common::SimpleRecordId;
+use crate::database::schemas::fastq_sample::FastqSampleConfig;
+use crate::nats::schema::envelope::Payload;
 
-// What we publish/consume from NATS.
+// What we publish/consume from NATS, inside an Envelope.
+//
+// Versions:
+// 0 - bare {url, fastq_sample_id}, from before messages had an envelope.
+// 1 - in an envelope, which carries the user id, and with mate_url and config.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FastqMessage {
     pub url: String,
+    /// R2 of paired-end samples, which are processed together with R1.
+    #[serde(default)]
+    pub mate_url: Option<String>,
     pub fastq_sample_id: SimpleRecordId,
+    /// Thresholds to filter the sample with.
+    #[serde(default)]
+    pub config: FastqSampleConfig,
+}
+
+impl Payload for FastqMessage {
+    const MESSAGE_TYPE: &'static str = "fastq.preprocess";
+    const VERSION: u32 = 1;
+
+    // Version 0 lacks mate_url and config, which both have a default.
 }
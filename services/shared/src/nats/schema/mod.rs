@@ -1 +1,5 @@
+pub mod cancel;
+pub mod dead_letter;
+pub mod envelope;
 pub mod fastq_service;
+pub mod sample_status;
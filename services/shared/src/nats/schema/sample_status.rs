@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+use crate::schema::sample::SampleStatusEvent;
+
+/// Published whenever a sample changes status. The API relays
+/// the event to the browser of the user that owns the sample.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleStatusMessage {
+    /// Id of the users record that uploaded the sample, e.g., "users:abc123".
+    pub user_id: String,
+    #[serde(flatten)]
+    pub event: SampleStatusEvent,
+}
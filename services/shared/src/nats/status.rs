@@ -0,0 +1,39 @@
+use async_nats::jetstream::Context as NatsContext;
+
+use crate::nats::NatsError;
+use crate::nats::schema::sample_status::SampleStatusMessage;
+use crate::schema::sample::SampleStatusEvent;
+use crate::schema::schema::Status;
+use crate::utils::time::time_now;
+
+/// Matches the sample-status.* subjects of the SampleStatus stream.
+pub const SAMPLE_STATUS_SUBJECT: &str = "sample-status.changed";
+
+/// Let the API know that a sample changed status, so that it can tell the user.
+pub async fn publish_sample_status(
+    jetstream: &NatsContext,
+    user_id: &str,
+    sample_id: &str,
+    status: Status,
+    error: Option<String>,
+) -> Result<(), NatsError> {
+    let message = SampleStatusMessage {
+        user_id: user_id.to_string(),
+        event: SampleStatusEvent {
+            sample_id: sample_id.to_string(),
+            status,
+            error,
+            updated_at: time_now(),
+        },
+    };
+
+    let ack = jetstream
+        .publish(
+            SAMPLE_STATUS_SUBJECT,
+            serde_json::to_string(&message)?.into(),
+        )
+        .await?;
+    ack.await?;
+
+    Ok(())
+}
@@ -1,37 +1,170 @@
+use async_nats::jetstream::consumer::pull::Config as PullConsumerConfig;
 use async_nats::jetstream::consumer::push::Config as PushConsumerConfig;
 use async_nats::jetstream::consumer::{AckPolicy, DeliverPolicy};
 use async_nats::jetstream::stream::DiscardPolicy;
 use async_nats::{self, jetstream::stream::Config as StreamConfig};
+use std::time::Duration;
 
+#[derive(Clone, Copy)]
 pub enum StreamType {
     FileUpload,
+    SampleStatus,
+    DeadLetter,
+    JobCancel,
+}
+
+/// How a consumer retries messages that failed. Every failed delivery is
+/// NAKed with an exponentially growing delay, and once a message has been
+/// delivered max_deliveries times it goes to the dead-letter stream instead.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_deliveries: i64,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_deliveries: 5,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the next delivery, after `delivered` failed ones,
+    /// i.e., base_delay, 2 * base_delay, 4 * base_delay, ... up to max_delay.
+    pub fn delay(&self, delivered: i64) -> Duration {
+        let exponent = delivered.saturating_sub(1).clamp(0, 16) as u32;
+        self.base_delay
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.max_delay)
+    }
+
+    pub fn is_exhausted(&self, delivered: i64) -> bool {
+        delivered >= self.max_deliveries
+    }
+}
+
+/// Max number of file upload messages that are delivered but not yet acked,
+/// over all fastq_service replicas, unless FILE_UPLOAD_MAX_ACK_PENDING is set.
+const DEFAULT_MAX_ACK_PENDING: i64 = 100;
+
+pub enum ConsumerConfig {
+    /// Durable consumer that workers pull messages from. Every message goes to
+    /// one of the workers, so adding replicas spreads the work between them.
+    Pull(PullConsumerConfig),
+    /// Consumer that pushes every message to whoever subscribes to its deliver subject.
+    Push(PushConsumerConfig),
+    /// Stream that is only read directly.
+    None,
+}
+
+impl ConsumerConfig {
+    /// The subject the consumer reads, which is also where we publish to.
+    pub fn filter_subject(&self) -> Option<&str> {
+        match self {
+            ConsumerConfig::Pull(cfg) => Some(&cfg.filter_subject),
+            ConsumerConfig::Push(cfg) => Some(&cfg.filter_subject),
+            ConsumerConfig::None => None,
+        }
+    }
 }
 
 pub struct StreamConsumerConfig {
     pub stream: StreamConfig,
-    pub consumer: PushConsumerConfig,
+    pub consumer: ConsumerConfig,
+    pub retry: RetryPolicy,
 }
 
 impl From<StreamType> for StreamConsumerConfig {
     fn from(stream_type: StreamType) -> Self {
         match stream_type {
-            StreamType::FileUpload => Self {
+            StreamType::FileUpload => {
+                let retry = RetryPolicy::default();
+                Self {
+                    stream: StreamConfig {
+                        name: "file-uploaded".into(),
+                        max_messages: 1_000,
+                        subjects: vec!["file-uploaded.*".into()],
+                        discard: DiscardPolicy::Old,
+                        ..Default::default()
+                    },
+                    consumer: ConsumerConfig::Pull(PullConsumerConfig {
+                        name: Some("file-uploaded-workers".into()),
+                        durable_name: Some("file-uploaded-workers".into()),
+                        filter_subject: "file-uploaded.process".into(),
+                        ack_policy: AckPolicy::Explicit,
+                        // Also picks up what was published before the consumer existed.
+                        // Samples that are already done are skipped by the worker.
+                        deliver_policy: DeliverPolicy::All,
+                        // We dead-letter the message on its last delivery ourselves,
+                        // this is only a backstop so that nothing is retried forever.
+                        max_deliver: retry.max_deliveries,
+                        // Caps the work in progress over all replicas.
+                        max_ack_pending: std::env::var("FILE_UPLOAD_MAX_ACK_PENDING")
+                            .ok()
+                            .and_then(|value| value.parse::<i64>().ok())
+                            .unwrap_or(DEFAULT_MAX_ACK_PENDING),
+                        ..Default::default()
+                    }),
+                    retry,
+                }
+            }
+            // Every API instance relays every status event to its own browsers,
+            // so the consumer is ephemeral (no durable name), only gets new events
+            // and each instance attaches its own with a unique deliver subject.
+            StreamType::SampleStatus => Self {
+                stream: StreamConfig {
+                    name: "sample-status".into(),
+                    max_messages: 10_000,
+                    subjects: vec!["sample-status.*".into()],
+                    discard: DiscardPolicy::Old,
+                    ..Default::default()
+                },
+                consumer: ConsumerConfig::Push(PushConsumerConfig {
+                    filter_subject: "sample-status.*".into(),
+                    deliver_subject: "sample-status-deliver".into(),
+                    ack_policy: AckPolicy::None,
+                    deliver_policy: DeliverPolicy::New,
+                    ..Default::default()
+                }),
+                retry: RetryPolicy::default(),
+            },
+            // Messages that failed too many times, with the reason why. They
+            // are only read (and replayed) through the API, so there is no consumer.
+            StreamType::DeadLetter => Self {
                 stream: StreamConfig {
-                    name: "file-uploaded".into(),
-                    max_messages: 1_000,
-                    subjects: vec!["file-uploaded.*".into()],
+                    name: "dead-letter".into(),
+                    max_messages: 10_000,
+                    subjects: vec!["dead-letter.*".into()],
                     discard: DiscardPolicy::Old,
                     ..Default::default()
                 },
-                consumer: PushConsumerConfig {
-                    name: Some("file-uploaded-process".into()),
-                    durable_name: Some("file-uploaded-process".into()),
-                    filter_subject: "file-uploaded.process".into(),
-                    deliver_subject: "file-uploaded.process.deliver".into(),
-                    ack_policy: AckPolicy::Explicit,
-                    deliver_policy: DeliverPolicy::All,
+                consumer: ConsumerConfig::None,
+                retry: RetryPolicy::default(),
+            },
+            // Every worker needs to hear about every cancellation, since any
+            // of them might be running the job. Like the status events, each
+            // worker attaches its own ephemeral consumer for new messages.
+            StreamType::JobCancel => Self {
+                stream: StreamConfig {
+                    name: "job-cancel".into(),
+                    max_messages: 10_000,
+                    subjects: vec!["job-cancel.*".into()],
+                    discard: DiscardPolicy::Old,
                     ..Default::default()
                 },
+                consumer: ConsumerConfig::Push(PushConsumerConfig {
+                    filter_subject: "job-cancel.*".into(),
+                    deliver_subject: "job-cancel-deliver".into(),
+                    ack_policy: AckPolicy::None,
+                    deliver_policy: DeliverPolicy::New,
+                    ..Default::default()
+                }),
+                retry: RetryPolicy::default(),
             },
         }
     }
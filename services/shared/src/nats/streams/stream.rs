@@ -1,10 +1,23 @@
 use async_nats::jetstream::Context as NatsContext;
+use async_nats::jetstream::Message as NatsMessage;
 use async_nats::jetstream::consumer::Consumer as NatsConsumer;
+use async_nats::jetstream::consumer::pull::Config as PullConsumerConfig;
 use async_nats::jetstream::consumer::push::Config as PushConsumerConfig;
-use log::info;
+use async_nats::jetstream::consumer::{AckPolicy, DeliverPolicy};
+use async_nats::jetstream::stream::Stream;
+use futures::StreamExt;
+use log::{info, warn};
+use std::time::Duration;
 
 use crate::nats::NatsError;
-use crate::nats::streams::config::{StreamConsumerConfig, StreamType};
+use crate::nats::streams::config::{ConsumerConfig, StreamConsumerConfig, StreamType};
+
+/// How many messages read_stream_messages fetches per request.
+const READ_BATCH_SIZE: usize = 256;
+
+/// Consumers that have been replaced, e.g., by a consumer of another kind,
+/// which can't be done in place. Removed when the streams are created.
+const RETIRED_CONSUMERS: [(&str, &str); 1] = [("file-uploaded", "file-uploaded-process")];
 
 /// Basics of NATS:
 /// * Stream - stores messages. We can define subjects
@@ -24,10 +37,14 @@ use crate::nats::streams::config::{StreamConsumerConfig, StreamType};
 ///     to the consumer subject. This means the consumer deliver subject and the
 ///     client subscription subject should match.
 ///
+/// * Pull consumer - Instead of being pushed to a subject, messages are
+///     fetched (in batches) by the clients. Several clients can pull from the
+///     same consumer, each message goes to one of them.
+///
 /// Basically, for this function we:
 /// * Extract out pre-defined stream and consumer setup based on the stream type.
 /// * Create the stream itself.
-/// * Attach a push consumer.
+/// * Attach a durable pull or push consumer.
 pub async fn create_stream_with_consumer(
     jetstream: &NatsContext,
     stream_type: StreamType,
@@ -39,39 +56,165 @@ pub async fn create_stream_with_consumer(
     info!("Creating {} stream...", stream_name);
     jetstream.get_or_create_stream(&cfg.stream).await?;
 
-    info!("Attaching durable push consumer...");
-    let mut push_consumer: NatsConsumer<PushConsumerConfig> = jetstream
-        .create_consumer_on_stream(cfg.consumer, stream_name)
-        .await
-        .expect("Failed to create consumer for stream.");
+    let consumer_info = match cfg.consumer {
+        ConsumerConfig::Pull(consumer) => {
+            info!("Attaching durable pull consumer...");
+            let mut pull_consumer: NatsConsumer<PullConsumerConfig> = jetstream
+                .create_consumer_on_stream(consumer, stream_name)
+                .await
+                .expect("Failed to create consumer for stream.");
+            pull_consumer.info().await.cloned()
+        }
+        ConsumerConfig::Push(consumer) => {
+            info!("Attaching durable push consumer...");
+            let mut push_consumer: NatsConsumer<PushConsumerConfig> = jetstream
+                .create_consumer_on_stream(consumer, stream_name)
+                .await
+                .expect("Failed to create consumer for stream.");
+            push_consumer.info().await.cloned()
+        }
+        ConsumerConfig::None => return Ok(()),
+    };
 
     info!("Getting consumer info...");
-    let consumer_info = push_consumer
-        .info()
-        .await
-        .expect("Failed to get consumer info.");
+    let consumer_info = consumer_info.expect("Failed to get consumer info.");
     info!("Succeeded:\n{:?}", consumer_info);
 
     Ok(())
 }
 
-// Get an already existing stream and consumer.
-pub async fn get_consumer_from_stream_type(
+/// Remove consumers that are no longer used, so that they don't keep
+/// messages around, and don't process them next to their replacement.
+pub async fn remove_retired_consumers(jetstream: &NatsContext) {
+    for (stream_name, consumer_name) in RETIRED_CONSUMERS {
+        let Ok(stream) = jetstream.get_stream(stream_name).await else {
+            continue;
+        };
+
+        if stream.consumer_info(consumer_name).await.is_err() {
+            continue;
+        }
+
+        info!("Removing retired consumer {}...", consumer_name);
+        if let Err(e) = stream.delete_consumer(consumer_name).await {
+            warn!("Failed to remove consumer {}: {:?}", consumer_name, e);
+        }
+    }
+}
+
+/// Create a stream without a consumer, for streams where
+/// every subscriber attaches its own ephemeral consumer.
+pub async fn create_stream(
+    jetstream: &NatsContext,
+    stream_type: StreamType,
+) -> Result<(), NatsError> {
+    let cfg = StreamConsumerConfig::from(stream_type);
+
+    info!("Creating {} stream...", cfg.stream.name);
+    jetstream.get_or_create_stream(&cfg.stream).await?;
+
+    Ok(())
+}
+
+/// Attach an ephemeral push consumer to an existing stream, which delivers to
+/// the stream's deliver subject suffixed with `id`. The consumer is removed by
+/// the server once nobody is subscribed to it anymore.
+pub async fn create_ephemeral_consumer(
     jetstream: &NatsContext,
     stream_type: StreamType,
+    id: &str,
 ) -> Result<NatsConsumer<PushConsumerConfig>, NatsError> {
     let cfg = StreamConsumerConfig::from(stream_type);
 
     let stream_name = cfg.stream.name.as_str();
+    let ConsumerConfig::Push(consumer) = cfg.consumer else {
+        return Err(NatsError::GetConsumerError(format!(
+            "{} has no push consumer",
+            stream_name
+        )));
+    };
+
+    info!("Attaching ephemeral consumer to {} stream...", stream_name);
+    let consumer: NatsConsumer<PushConsumerConfig> = jetstream
+        .create_consumer_on_stream(
+            PushConsumerConfig {
+                deliver_subject: format!("{}.{}", consumer.deliver_subject, id),
+                ..consumer
+            },
+            stream_name,
+        )
+        .await
+        .map_err(|err| NatsError::GetConsumerError(err.to_string()))?;
+
+    Ok(consumer)
+}
+
+// Get an already existing stream and its pull consumer.
+pub async fn get_consumer_from_stream_type(
+    jetstream: &NatsContext,
+    stream_type: StreamType,
+) -> Result<NatsConsumer<PullConsumerConfig>, NatsError> {
+    let cfg = StreamConsumerConfig::from(stream_type);
+
+    let stream_name = cfg.stream.name.as_str();
+    let ConsumerConfig::Pull(consumer) = cfg.consumer else {
+        return Err(NatsError::GetConsumerError(format!(
+            "{} has no pull consumer",
+            stream_name
+        )));
+    };
 
     // Get stream by name.
     info!("Getting stream {}", &stream_name);
     let stream = jetstream.get_stream(&stream_name).await?;
 
-    info!("Getting consumer {:?}...", &cfg.consumer.name);
-    let consumer: NatsConsumer<PushConsumerConfig> = stream
-        .get_consumer(&cfg.consumer.name.expect("Consumer name does not exist."))
+    info!("Getting consumer {:?}...", &consumer.name);
+    let consumer: NatsConsumer<PullConsumerConfig> = stream
+        .get_consumer(&consumer.name.expect("Consumer name does not exist."))
         .await?;
 
     Ok(consumer)
 }
+
+/// Read up to `limit` messages of a stream, oldest first, starting at
+/// sequence `start`. This goes through an ephemeral consumer that acknowledges
+/// nothing, which skips deleted messages and takes one round trip per batch,
+/// instead of one per sequence. The server removes the consumer after a minute.
+pub async fn read_stream_messages(
+    stream: &Stream,
+    start: u64,
+    limit: usize,
+) -> Result<Vec<NatsMessage>, NatsError> {
+    let consumer: NatsConsumer<PullConsumerConfig> = stream
+        .create_consumer(PullConsumerConfig {
+            deliver_policy: DeliverPolicy::ByStartSequence {
+                start_sequence: start,
+            },
+            ack_policy: AckPolicy::None,
+            inactive_threshold: Duration::from_secs(60),
+            ..Default::default()
+        })
+        .await
+        .map_err(|err| NatsError::GetConsumerError(err.to_string()))?;
+
+    let mut messages: Vec<NatsMessage> = Vec::new();
+    while messages.len() < limit {
+        // Only what is there already, without waiting for new messages.
+        let mut batch = consumer
+            .fetch()
+            .max_messages(READ_BATCH_SIZE.min(limit - messages.len()))
+            .messages()
+            .await
+            .map_err(|err| NatsError::StreamMessageError(err.to_string()))?;
+
+        let read = messages.len();
+        while let Some(message) = batch.next().await {
+            messages.push(message.map_err(|err| NatsError::StreamMessageError(err.to_string()))?);
+        }
+        if messages.len() == read {
+            break;
+        }
+    }
+
+    Ok(messages)
+}
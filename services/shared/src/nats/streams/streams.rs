@@ -1,8 +1,17 @@
 use crate::nats::NatsError;
-use crate::nats::streams::{config::StreamType, stream::create_stream_with_consumer};
+use crate::nats::streams::{
+    config::StreamType,
+    stream::{create_stream, create_stream_with_consumer, remove_retired_consumers},
+};
 use async_nats::jetstream::Context as NatsContext;
 
 pub async fn create_streams(jetstream: &NatsContext) -> Result<(), NatsError> {
     create_stream_with_consumer(jetstream, StreamType::FileUpload).await?;
+    remove_retired_consumers(jetstream).await;
+    // Consumers are attached by each API instance.
+    create_stream(jetstream, StreamType::SampleStatus).await?;
+    create_stream(jetstream, StreamType::DeadLetter).await?;
+    // Consumers are attached by each worker.
+    create_stream(jetstream, StreamType::JobCancel).await?;
     Ok(())
 }
@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_nats::jetstream::Context as NatsContext;
+use async_nats::jetstream::{AckKind, Message};
+use futures::StreamExt;
+use log::{error, info, warn};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::nats::NatsError;
+use crate::nats::dead_letter::publish_dead_letter;
+use crate::nats::schema::cancel::CancelMessage;
+use crate::nats::schema::envelope::{Envelope, Payload};
+use crate::nats::streams::config::{RetryPolicy, StreamConsumerConfig, StreamType};
+use crate::nats::streams::stream::{create_ephemeral_consumer, get_consumer_from_stream_type};
+
+/// How long to wait before attaching a new cancel consumer after losing the old one.
+const CANCEL_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Cancellation tokens of the jobs in progress, by job id. Several messages
+/// can be in progress for the same job, e.g., a redelivery, so each has its own.
+type RunningJobs = Arc<Mutex<HashMap<String, HashMap<String, CancellationToken>>>>;
+
+/// A service that processes the messages of a stream, e.g., the fastq preprocessor.
+///
+/// The runtime (run_worker) takes care of everything around it, i.e.,
+/// consuming, deserializing, concurrency, heartbeats, retries, dead-lettering
+/// and shutdown, so that a worker only implements the steps of a job:
+///
+/// start -> process -> finish
+///             |
+///             +-----> fail
+///             |
+///             +-----> cancelled
+///
+/// A failed step is retried with the retry policy of the stream. Every step
+/// gets the whole envelope, i.e., the message and who it is for.
+///
+/// A job is cancelled through publish_job_cancel with its job_id. The process
+/// future still runs until it returns, so that blocking work, e.g., on
+/// spawn_blocking, keeps its files and its slot until it stops, which it should
+/// do early by checking the token now and then. Its result is thrown away.
+pub trait Worker: Send + Sync + 'static {
+    type Message: Payload + Debug + Send + Sync + 'static;
+    type Output: Send + 'static;
+    type Error: Debug + Send + Sync + 'static;
+
+    /// The stream (and its consumer) to process messages from.
+    fn stream_type(&self) -> StreamType;
+
+    /// Called before processing. Returns false if the message should be
+    /// skipped, e.g., because it is a redelivery of a finished job.
+    fn start(
+        &self,
+        message: &Envelope<Self::Message>,
+    ) -> impl Future<Output = Result<bool, Self::Error>> + Send;
+
+    /// Identifies the job of a message for cancellation, e.g., the sample id.
+    fn job_id(&self, message: &Envelope<Self::Message>) -> String;
+
+    /// The actual work.
+    fn process(
+        &self,
+        message: &Envelope<Self::Message>,
+        cancel: &CancellationToken,
+    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send;
+
+    /// Store the output of a successful job.
+    fn finish(
+        &self,
+        message: &Envelope<Self::Message>,
+        output: Self::Output,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Record that processing failed for good, i.e., on the last delivery,
+    /// before the message is dead-lettered. Earlier failures are only retried.
+    fn fail(
+        &self,
+        message: &Envelope<Self::Message>,
+        error: &Self::Error,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Clean up after a job that was cancelled while processing, e.g., remove
+    /// what it already uploaded. The message is acked afterwards either way.
+    fn cancelled(
+        &self,
+        message: &Envelope<Self::Message>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    /// How many messages are processed at the same time.
+    pub concurrency: usize,
+    /// How many messages are pulled from the consumer at once. Pulled messages
+    /// count as in progress, so keep it close to the concurrency, otherwise
+    /// they wait in this worker while other replicas might be idle.
+    pub batch_size: usize,
+    /// How often a job in progress tells NATS that it is still alive, which
+    /// must be well below the ack wait of the consumer (30s by default).
+    pub heartbeat: Duration,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 1,
+            batch_size: 1,
+            heartbeat: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Process messages with `worker` until SIGTERM (or Ctrl-C), after which
+/// jobs in progress are allowed to finish.
+pub async fn run_worker<W: Worker>(
+    jetstream: NatsContext,
+    worker: W,
+    config: WorkerConfig,
+) -> Result<(), NatsError> {
+    let stream_type = worker.stream_type();
+    let retry = StreamConsumerConfig::from(worker.stream_type()).retry;
+
+    let consumer = get_consumer_from_stream_type(&jetstream, stream_type).await?;
+    let mut messages = consumer
+        .stream()
+        .max_messages_per_batch(config.batch_size.max(1))
+        .messages()
+        .await
+        .map_err(|err| NatsError::GetConsumerError(err.to_string()))?;
+
+    let concurrency = config.concurrency.max(1);
+    let worker = Arc::new(worker);
+    let permits = Arc::new(Semaphore::new(concurrency));
+
+    let jobs: RunningJobs = Arc::new(Mutex::new(HashMap::new()));
+    let cancel_listener = tokio::spawn(listen_for_cancel(jetstream.clone(), jobs.clone()));
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    info!("Ready to accept messages ({} at a time)...", concurrency);
+    loop {
+        // Wait for a free slot first, so that we don't take messages we can't start.
+        let permit = tokio::select! {
+            permit = permits.clone().acquire_owned() => permit.expect("Semaphore closed."),
+            _ = &mut shutdown => break,
+        };
+
+        let message = tokio::select! {
+            message = messages.next() => message,
+            _ = &mut shutdown => break,
+        };
+
+        let message = match message {
+            Some(Ok(message)) => message,
+            Some(Err(e)) => {
+                error!("Got invalid message: {:?}", e);
+                continue;
+            }
+            None => break,
+        };
+
+        let worker = worker.clone();
+        let jetstream = jetstream.clone();
+        let retry = retry.clone();
+        let config = config.clone();
+        let jobs = jobs.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(&*worker, &message, &retry, &config, &jobs, &jetstream).await {
+                error!("Failed to handle message: {:?}", e);
+            }
+            drop(permit);
+        });
+    }
+
+    // Unacked messages are redelivered anyway, but finishing
+    // what we started saves doing the work twice.
+    info!("Shutting down, waiting for jobs in progress...");
+    let _ = permits.acquire_many(concurrency as u32).await;
+    cancel_listener.abort();
+    info!("Shut down.");
+
+    Ok(())
+}
+
+/// Cancel running jobs when asked to, for as long as the worker runs.
+async fn listen_for_cancel(jetstream: NatsContext, jobs: RunningJobs) {
+    let id = uuid::Uuid::now_v7().simple().to_string();
+
+    loop {
+        if let Err(e) = cancel_jobs(&jetstream, &jobs, &id).await {
+            error!("Cancel listener failed: {:?}", e);
+        }
+
+        warn!(
+            "Cancel listener stopped, restarting in {:?}",
+            CANCEL_RECONNECT_DELAY
+        );
+        tokio::time::sleep(CANCEL_RECONNECT_DELAY).await;
+    }
+}
+
+async fn cancel_jobs(
+    jetstream: &NatsContext,
+    jobs: &RunningJobs,
+    id: &str,
+) -> Result<(), NatsError> {
+    let consumer = create_ephemeral_consumer(jetstream, StreamType::JobCancel, id).await?;
+    let mut messages = consumer
+        .messages()
+        .await
+        .map_err(|err| NatsError::GetConsumerError(err.to_string()))?;
+
+    while let Some(message) = messages.next().await {
+        let message = message.map_err(|err| NatsError::GetConsumerError(err.to_string()))?;
+
+        let cancel = match Envelope::<CancelMessage>::from_slice(&message.payload) {
+            Ok(cancel) => cancel.payload,
+            Err(e) => {
+                error!("Got invalid cancel message: {:?}", e);
+                continue;
+            }
+        };
+
+        // Most cancellations are for jobs that some other worker runs, or that
+        // have not started yet.
+        if let Some(tokens) = jobs.lock().unwrap().get(&cancel.job_id) {
+            info!("Cancelling job {}...", cancel.job_id);
+            for token in tokens.values() {
+                token.cancel();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM.");
+        tokio::select! {
+            _ = sigterm.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// One message, from deserializing to ack.
+async fn handle<W: Worker>(
+    worker: &W,
+    message: &Message,
+    retry: &RetryPolicy,
+    config: &WorkerConfig,
+    jobs: &RunningJobs,
+    jetstream: &NatsContext,
+) -> Result<(), NatsError> {
+    // A message that can't be parsed never will be, so there is no point in retrying it.
+    let parsed = match Envelope::<W::Message>::from_slice(&message.payload) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let delivered = message.info().map(|info| info.delivered).unwrap_or(1);
+            return dead_letter(
+                worker,
+                message,
+                format!("Invalid message: {:?}", e),
+                delivered,
+                jetstream,
+            )
+            .await;
+        }
+    };
+    info!(
+        "Got {} (correlation id {}): {:?}",
+        parsed.message_type, parsed.correlation_id, parsed.payload
+    );
+
+    // Registered before starting, so that a cancel that comes in while
+    // the job starts is not missed.
+    let job_id = worker.job_id(&parsed);
+    let key = uuid::Uuid::now_v7().simple().to_string();
+    let cancel = CancellationToken::new();
+    jobs.lock()
+        .unwrap()
+        .entry(job_id.clone())
+        .or_default()
+        .insert(key.clone(), cancel.clone());
+
+    let result = run_job(worker, message, &parsed, retry, config, &cancel, jetstream).await;
+
+    {
+        let mut running = jobs.lock().unwrap();
+        if let Some(tokens) = running.get_mut(&job_id) {
+            tokens.remove(&key);
+            if tokens.is_empty() {
+                running.remove(&job_id);
+            }
+        }
+    }
+
+    result
+}
+
+async fn run_job<W: Worker>(
+    worker: &W,
+    message: &Message,
+    parsed: &Envelope<W::Message>,
+    retry: &RetryPolicy,
+    config: &WorkerConfig,
+    cancel: &CancellationToken,
+    jetstream: &NatsContext,
+) -> Result<(), NatsError> {
+    match worker.start(parsed).await {
+        Ok(true) => {}
+        Ok(false) => {
+            info!("Skipping message.");
+            return ack(message).await;
+        }
+        Err(e) => {
+            return retry_or_dead_letter(worker, message, retry, format!("{:?}", e), jetstream)
+                .await;
+        }
+    }
+
+    // Long jobs would otherwise be redelivered once the ack wait runs out.
+    // A cancelled job is waited for as well, since dropping the work would not
+    // stop blocking tasks, but it would remove their temp dir and free their slot.
+    let mut work = Box::pin(worker.process(parsed, cancel));
+    let mut heartbeat = tokio::time::interval(config.heartbeat);
+    heartbeat.tick().await;
+    let result = loop {
+        tokio::select! {
+            biased;
+            result = &mut work => break result,
+            _ = heartbeat.tick() => {
+                if let Err(e) = message.ack_with(AckKind::Progress).await {
+                    warn!("Failed to send heartbeat: {:?}", e);
+                }
+            }
+        }
+    };
+
+    // Whatever the work got to, what is left is to clean up after it.
+    if cancel.is_cancelled() {
+        info!("Job {} cancelled.", worker.job_id(parsed));
+        if let Err(e) = worker.cancelled(parsed).await {
+            error!("Failed to clean up cancelled job: {:?}", e);
+        }
+        return ack(message).await;
+    }
+
+    match result {
+        Ok(output) => match worker.finish(parsed, output).await {
+            Ok(()) => ack(message).await,
+            Err(e) => {
+                retry_or_dead_letter(worker, message, retry, format!("{:?}", e), jetstream).await
+            }
+        },
+        Err(e) => {
+            error!("{:?}", e);
+            if retry.is_exhausted(deliveries(message))
+                && let Err(fail_error) = worker.fail(parsed, &e).await
+            {
+                error!("Failed to record failure: {:?}", fail_error);
+            }
+            retry_or_dead_letter(worker, message, retry, format!("{:?}", e), jetstream).await
+        }
+    }
+}
+
+async fn ack(message: &Message) -> Result<(), NatsError> {
+    message
+        .ack()
+        .await
+        .map_err(|err| NatsError::MessageAckError(err.to_string()))
+}
+
+/// How often the message has been delivered, including this time.
+fn deliveries(message: &Message) -> i64 {
+    message.info().map(|info| info.delivered).unwrap_or(1)
+}
+
+/// Retry a failed message later, with a growing delay, or move it to the
+/// dead-letter stream once it has used up its deliveries.
+async fn retry_or_dead_letter<W: Worker>(
+    worker: &W,
+    message: &Message,
+    retry: &RetryPolicy,
+    error: String,
+    jetstream: &NatsContext,
+) -> Result<(), NatsError> {
+    let delivered = deliveries(message);
+
+    if !retry.is_exhausted(delivered) {
+        let delay = retry.delay(delivered);
+        info!(
+            "Retrying message in {:?} (delivery {}/{})",
+            delay, delivered, retry.max_deliveries
+        );
+        return message
+            .ack_with(AckKind::Nak(Some(delay)))
+            .await
+            .map_err(|err| NatsError::MessageAckError(err.to_string()));
+    }
+
+    dead_letter(worker, message, error, delivered, jetstream).await
+}
+
+/// Give up on a message, i.e., move it to the dead-letter stream and ack it.
+async fn dead_letter<W: Worker>(
+    worker: &W,
+    message: &Message,
+    error: String,
+    delivered: i64,
+    jetstream: &NatsContext,
+) -> Result<(), NatsError> {
+    error!(
+        "Moving message to the dead-letter stream after {} deliveries: {}",
+        delivered, error
+    );
+    publish_dead_letter(
+        jetstream,
+        worker.stream_type(),
+        &message.subject,
+        &message.payload,
+        error,
+        delivered,
+    )
+    .await?;
+
+    ack(message).await
+}
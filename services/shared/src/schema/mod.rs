@@ -1 +1,5 @@
+pub mod sample;
+pub mod sample_sheet;
 pub mod schema;
+pub mod trimming;
+pub mod upload;
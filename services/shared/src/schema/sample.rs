@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::schema::schema::{Pipeline, Status};
+use crate::schema::trimming::{AdapterTrimConfig, QualityTrimConfig};
+
+// Query parameters and response bodies for reading samples.
+// These are shared between the API and the frontend.
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum SampleSort {
+    #[serde(rename = "created_at")]
+    #[default]
+    CreatedAt,
+    #[serde(rename = "updated_at")]
+    UpdatedAt,
+    #[serde(rename = "name")]
+    Name,
+    #[serde(rename = "status")]
+    Status,
+}
+
+impl SampleSort {
+    /// The field to sort on. Fields can't be bound as query parameters,
+    /// so only ever put these in a query, never user input.
+    pub fn field(&self) -> &'static str {
+        match self {
+            SampleSort::CreatedAt => "created_at",
+            SampleSort::UpdatedAt => "updated_at",
+            SampleSort::Name => "name",
+            SampleSort::Status => "status",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum SortOrder {
+    #[serde(rename = "asc")]
+    Asc,
+    #[serde(rename = "desc")]
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_page_size() -> usize {
+    DEFAULT_PAGE_SIZE
+}
+
+/// e.g., /samples?page=2&per_page=50&pipeline=wgs_metagenome&status=done&from=2025-01-01&sort=name&order=asc
+///
+/// `from` and `to` are dates ("2025-01-31") or timestamps ("2025-01-31 12:00:00")
+/// on created_at, both inclusive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SampleListQuery {
+    #[serde(default = "default_page")]
+    pub page: usize,
+    #[serde(default = "default_page_size")]
+    pub per_page: usize,
+    pub pipeline: Option<Pipeline>,
+    pub status: Option<Status>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(default)]
+    pub sort: SampleSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+impl Default for SampleListQuery {
+    fn default() -> Self {
+        Self {
+            page: default_page(),
+            per_page: default_page_size(),
+            pipeline: None,
+            status: None,
+            from: None,
+            to: None,
+            sort: SampleSort::default(),
+            order: SortOrder::default(),
+        }
+    }
+}
+
+impl SampleListQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.page == 0 {
+            return Err("page starts at 1".into());
+        }
+
+        if self.per_page == 0 || self.per_page > MAX_PAGE_SIZE {
+            return Err(format!("per_page must be between 1 and {}", MAX_PAGE_SIZE));
+        }
+
+        Ok(())
+    }
+
+    /// Number of samples to skip.
+    pub fn start(&self) -> usize {
+        (self.page - 1) * self.per_page
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SampleSummary {
+    /// The id without the table name.
+    pub id: String,
+    pub name: String,
+    pub status: Status,
+    /// Why preprocessing failed, if it did.
+    pub error: Option<String>,
+    pub attempts: usize,
+    pub pipeline: Pipeline,
+    pub paired: bool,
+    pub metadata: BTreeMap<String, String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SampleListResponse {
+    pub samples: Vec<SampleSummary>,
+    pub page: usize,
+    pub per_page: usize,
+    /// Number of samples that match the filters, over all pages.
+    pub total: usize,
+}
+
+/// Result of the fastq preprocessor. `result` holds the metrics as they are
+/// stored, i.e., raw and filtered metrics per mate and pair metrics.
+/// The thresholds are the ones of this run, which are not necessarily
+/// the current thresholds of the sample.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PreprocessSummary {
+    pub id: String,
+    pub status: Status,
+    pub url: String,
+    pub mate_url: Option<String>,
+    pub runtime: usize,
+    pub min_len: usize,
+    pub max_len: Option<usize>,
+    pub min_phred: usize,
+    pub adapter_trim: AdapterTrimConfig,
+    pub quality_trim: QualityTrimConfig,
+    pub result: serde_json::Value,
+    /// Standalone HTML report with the QC plots, not set for older runs.
+    pub report_url: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SampleDetail {
+    #[serde(flatten)]
+    pub summary: SampleSummary,
+    pub url: String,
+    pub mate_url: Option<String>,
+    pub min_len: usize,
+    pub max_len: Option<usize>,
+    pub min_phred: usize,
+    pub adapter_trim: AdapterTrimConfig,
+    pub quality_trim: QualityTrimConfig,
+    /// Not set until the sample has been preprocessed.
+    pub preprocess: Option<PreprocessSummary>,
+}
+
+/// Name of the server-sent events that carry a SampleStatusEvent.
+pub const SAMPLE_STATUS_EVENT: &str = "status";
+
+/// Sent to the browser whenever the status of one of the user's samples changes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SampleStatusEvent {
+    /// The id without the table name.
+    pub sample_id: String,
+    pub status: Status,
+    #[serde(default)]
+    pub error: Option<String>,
+    pub updated_at: String,
+}
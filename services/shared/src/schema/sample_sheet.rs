@@ -0,0 +1,426 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use strum::IntoEnumIterator;
+
+use crate::schema::schema::Pipeline;
+use crate::schema::upload::MAX_MIN_PHRED;
+
+// Sample sheets map sample names to their fastq files, e.g.,
+//
+// sample,fastq_1,fastq_2,pipeline,min_len,max_len,min_phred,plate_well
+// S1,S1_R1.fastq.gz,S1_R2.fastq.gz,wgs_single_isolate,200,,15,A01
+// S2,S2.fastq.gz,,,,,,A02
+//
+// Either comma or tab separated. sample and fastq_1 are required, fastq_2
+// is only for paired-end samples and empty values fall back to the defaults.
+// Any other column is stored as metadata on the sample.
+
+const SAMPLE_COLUMN: &str = "sample";
+const FASTQ_1_COLUMN: &str = "fastq_1";
+const FASTQ_2_COLUMN: &str = "fastq_2";
+const PIPELINE_COLUMN: &str = "pipeline";
+const MIN_LEN_COLUMN: &str = "min_len";
+const MAX_LEN_COLUMN: &str = "max_len";
+const MIN_PHRED_COLUMN: &str = "min_phred";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SampleSheetRow {
+    pub sample: String,
+    pub fastq_1: String,
+    pub fastq_2: Option<String>,
+    pub pipeline: Option<Pipeline>,
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+    pub min_phred: Option<usize>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl SampleSheetRow {
+    pub fn files(&self) -> Vec<&str> {
+        std::iter::once(self.fastq_1.as_str())
+            .chain(self.fastq_2.as_deref())
+            .collect()
+    }
+}
+
+/// `line` is 1-based and includes the header, so that it matches
+/// what the user sees in their editor. Line 0 is the sheet as a whole.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SampleSheetError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl SampleSheetError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SampleSheetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+/// Split a line on the delimiter, with support for double quoted
+/// values (e.g., "a, b") as exported by spreadsheet programs.
+fn split_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut values: Vec<String> = Vec::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => {
+                values.push(value.trim().to_string());
+                value.clear();
+            }
+            c => value.push(c),
+        }
+    }
+    values.push(value.trim().to_string());
+
+    values
+}
+
+/// Accepts both serde names ("wgs_single_isolate") and display names ("WGS Single Isolate").
+pub fn parse_pipeline(value: &str) -> Option<Pipeline> {
+    let normalize = |s: &str| -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+
+    Pipeline::iter().find(|pipeline| normalize(&pipeline.to_string()) == normalize(value))
+}
+
+fn parse_number(
+    value: Option<&String>,
+    column: &str,
+    line: usize,
+    errors: &mut Vec<SampleSheetError>,
+) -> Option<usize> {
+    let value = value.filter(|v| !v.is_empty())?;
+
+    match value.parse() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            errors.push(SampleSheetError::new(
+                line,
+                format!("Invalid {} '{}'", column, value),
+            ));
+            None
+        }
+    }
+}
+
+/// Parse a CSV or TSV sample sheet. Returns every error in the sheet
+/// at once, so that the user can fix them all in one go.
+pub fn parse_sample_sheet(text: &str) -> Result<Vec<SampleSheetRow>, Vec<SampleSheetError>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+
+    let Some((header_line, header)) = lines.next() else {
+        return Err(vec![SampleSheetError::new(0, "Sample sheet is empty")]);
+    };
+
+    let delimiter = if header.contains('\t') { '\t' } else { ',' };
+    let columns: Vec<String> = split_line(header, delimiter)
+        .into_iter()
+        .map(|column| column.to_lowercase())
+        .collect();
+
+    let mut errors: Vec<SampleSheetError> = Vec::new();
+    for required in [SAMPLE_COLUMN, FASTQ_1_COLUMN] {
+        if !columns.iter().any(|column| column == required) {
+            errors.push(SampleSheetError::new(
+                header_line,
+                format!("Missing required column '{}'", required),
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut rows: Vec<SampleSheetRow> = Vec::new();
+    let mut samples: HashSet<String> = HashSet::new();
+    let mut files: HashSet<String> = HashSet::new();
+
+    for (line, text) in lines {
+        let values = split_line(text, delimiter);
+        if values.len() != columns.len() {
+            errors.push(SampleSheetError::new(
+                line,
+                format!("Expected {} columns, got {}", columns.len(), values.len()),
+            ));
+            continue;
+        }
+
+        let mut values: BTreeMap<String, String> = columns.iter().cloned().zip(values).collect();
+        let mut take = |column: &str| values.remove(column).filter(|v| !v.is_empty());
+
+        let sample = take(SAMPLE_COLUMN);
+        let fastq_1 = take(FASTQ_1_COLUMN);
+        let fastq_2 = take(FASTQ_2_COLUMN);
+        let pipeline = take(PIPELINE_COLUMN);
+        let min_len = take(MIN_LEN_COLUMN);
+        let max_len = take(MAX_LEN_COLUMN);
+        let min_phred = take(MIN_PHRED_COLUMN);
+
+        let (Some(sample), Some(fastq_1)) = (sample, fastq_1) else {
+            errors.push(SampleSheetError::new(
+                line,
+                format!("{} and {} are required", SAMPLE_COLUMN, FASTQ_1_COLUMN),
+            ));
+            continue;
+        };
+
+        if !samples.insert(sample.clone()) {
+            errors.push(SampleSheetError::new(
+                line,
+                format!("Duplicate sample '{}'", sample),
+            ));
+        }
+        for file in std::iter::once(&fastq_1).chain(fastq_2.as_ref()) {
+            if !files.insert(file.clone()) {
+                errors.push(SampleSheetError::new(
+                    line,
+                    format!("File '{}' is used more than once", file),
+                ));
+            }
+        }
+
+        let pipeline = match pipeline {
+            Some(value) => match parse_pipeline(&value) {
+                Some(pipeline) => Some(pipeline),
+                None => {
+                    errors.push(SampleSheetError::new(
+                        line,
+                        format!("Unknown pipeline '{}'", value),
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let min_len = parse_number(min_len.as_ref(), MIN_LEN_COLUMN, line, &mut errors);
+        let max_len = parse_number(max_len.as_ref(), MAX_LEN_COLUMN, line, &mut errors);
+        let min_phred = parse_number(min_phred.as_ref(), MIN_PHRED_COLUMN, line, &mut errors);
+
+        if let (Some(min_len), Some(max_len)) = (min_len, max_len)
+            && min_len > max_len
+        {
+            errors.push(SampleSheetError::new(
+                line,
+                format!("{} is larger than {}", MIN_LEN_COLUMN, MAX_LEN_COLUMN),
+            ));
+        }
+
+        if min_phred.is_some_and(|min_phred| min_phred > MAX_MIN_PHRED) {
+            errors.push(SampleSheetError::new(
+                line,
+                format!("{} is larger than {}", MIN_PHRED_COLUMN, MAX_MIN_PHRED),
+            ));
+        }
+
+        rows.push(SampleSheetRow {
+            sample,
+            fastq_1,
+            fastq_2,
+            pipeline,
+            min_len,
+            max_len,
+            min_phred,
+            // Whatever is left is metadata.
+            metadata: values.into_iter().filter(|(_, v)| !v.is_empty()).collect(),
+        });
+    }
+
+    if rows.is_empty() && errors.is_empty() {
+        errors.push(SampleSheetError::new(0, "Sample sheet has no samples"));
+    }
+
+    match errors.is_empty() {
+        true => Ok(rows),
+        false => Err(errors),
+    }
+}
+
+/// Check that the sheet and the files that are about to be uploaded match,
+/// i.e., that every file in the sheet is there and that there are no extra files.
+pub fn validate_sample_sheet_files(
+    rows: &[SampleSheetRow],
+    file_names: &[String],
+) -> Vec<SampleSheetError> {
+    let file_names: HashSet<&str> = file_names.iter().map(String::as_str).collect();
+    let mut referenced: HashSet<&str> = HashSet::new();
+    let mut errors: Vec<SampleSheetError> = Vec::new();
+
+    for row in rows {
+        for file in row.files() {
+            referenced.insert(file);
+            if !file_names.contains(file) {
+                errors.push(SampleSheetError::new(
+                    0,
+                    format!("Missing file '{}' for sample '{}'", file, row.sample),
+                ));
+            }
+        }
+    }
+
+    let mut extra: Vec<&&str> = file_names.difference(&referenced).collect();
+    extra.sort();
+    for file in extra {
+        errors.push(SampleSheetError::new(
+            0,
+            format!("File '{}' is not in the sample sheet", file),
+        ));
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(text: &str) -> Vec<String> {
+        parse_sample_sheet(text)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn parse_rows() {
+        let rows = parse_sample_sheet(
+            "# exported sheet\n\
+             sample,fastq_1,fastq_2,pipeline,min_len,max_len,min_phred,plate_well\r\n\
+             S1,S1_R1.fastq.gz,S1_R2.fastq.gz,WGS Single Isolate,200,,15,A01\r\n\
+             \n\
+             S2,S2.fastq.gz,,,,,,\n",
+        )
+        .unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].sample, "S1");
+        assert_eq!(rows[0].files(), vec!["S1_R1.fastq.gz", "S1_R2.fastq.gz"]);
+        assert_eq!(rows[0].pipeline, Some(Pipeline::WgsSingleIsolate));
+        assert_eq!(rows[0].min_len, Some(200));
+        assert_eq!(rows[0].max_len, None);
+        assert_eq!(rows[0].min_phred, Some(15));
+        assert_eq!(
+            rows[0].metadata.get("plate_well").map(String::as_str),
+            Some("A01")
+        );
+        assert_eq!(rows[1].files(), vec!["S2.fastq.gz"]);
+        assert!(rows[1].metadata.is_empty());
+    }
+
+    #[test]
+    fn parse_tsv_with_quoted_values() {
+        let rows =
+            parse_sample_sheet("Sample\tFASTQ_1\tnote\nS1\tS1.fq\t\"a\tb, \"\"c\"\"\"\n").unwrap();
+
+        assert_eq!(
+            rows[0].metadata.get("note").map(String::as_str),
+            Some("a\tb, \"c\"")
+        );
+    }
+
+    #[test]
+    fn missing_columns_and_values() {
+        assert_eq!(errors(""), vec!["Line 0: Sample sheet is empty"]);
+        assert_eq!(
+            errors("sample,fastq_2\nS1,S1.fq\n"),
+            vec!["Line 1: Missing required column 'fastq_1'"]
+        );
+        assert_eq!(
+            errors("# exported sheet\n\nsample\nS1\n"),
+            vec!["Line 3: Missing required column 'fastq_1'"]
+        );
+        assert_eq!(
+            errors("sample,fastq_1\n"),
+            vec!["Line 0: Sample sheet has no samples"]
+        );
+        assert_eq!(
+            errors("sample,fastq_1\nS1,\n,S2.fq\nS3\n"),
+            vec![
+                "Line 2: sample and fastq_1 are required",
+                "Line 3: sample and fastq_1 are required",
+                "Line 4: Expected 2 columns, got 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_samples_and_files() {
+        assert_eq!(
+            errors("sample,fastq_1,fastq_2\nS1,a.fq,b.fq\nS1,c.fq,\nS2,b.fq,b.fq\n"),
+            vec![
+                "Line 3: Duplicate sample 'S1'",
+                "Line 4: File 'b.fq' is used more than once",
+                "Line 4: File 'b.fq' is used more than once",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(
+            errors(
+                "sample,fastq_1,pipeline,min_len,max_len,min_phred\n\
+                 S1,a.fq,nope,x,,\n\
+                 S2,b.fq,,300,200,\n\
+                 S3,c.fq,,,,1000\n"
+            ),
+            vec![
+                "Line 2: Unknown pipeline 'nope'",
+                "Line 2: Invalid min_len 'x'",
+                "Line 3: min_len is larger than max_len",
+                format!("Line 4: min_phred is larger than {}", MAX_MIN_PHRED).as_str(),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_and_extra_files() {
+        let rows =
+            parse_sample_sheet("sample,fastq_1,fastq_2\nS1,a_R1.fq,a_R2.fq\nS2,b.fq,\n").unwrap();
+
+        let file_names = ["a_R1.fq", "a_R2.fq", "b.fq"].map(String::from);
+        assert!(validate_sample_sheet_files(&rows, &file_names).is_empty());
+
+        let file_names = ["a_R1.fq", "d.fq", "c.fq"].map(String::from);
+        let errors: Vec<String> = validate_sample_sheet_files(&rows, &file_names)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Line 0: Missing file 'a_R2.fq' for sample 'S1'",
+                "Line 0: Missing file 'b.fq' for sample 'S2'",
+                "Line 0: File 'c.fq' is not in the sample sheet",
+                "Line 0: File 'd.fq' is not in the sample sheet",
+            ]
+        );
+    }
+}
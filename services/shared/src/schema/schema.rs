@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use strum;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum Status {
     #[serde(rename = "created")]
     Created,
@@ -11,6 +11,37 @@ pub enum Status {
     Done,
     #[serde(rename = "error")]
     Error,
+    #[serde(rename = "cancelled")]
+    Cancelled,
+}
+
+impl Status {
+    /// The statuses that a record may move to `next` from, i.e.,
+    ///
+    /// created -> pending -> done
+    ///               |  ^
+    ///               v  |
+    ///              error
+    ///
+    /// Pending can be entered again, since a message is redelivered when
+    /// a worker dies, and so can error, when a replayed dead letter fails
+    /// too. Done is
+    /// final, so that a late redelivery can't overwrite a finished result.
+    /// Anything that is not done can be cancelled, which is final too.
+    /// Until it is reprocessed, which queues it again as created.
+    pub fn sources(next: Status) -> Vec<Status> {
+        match next {
+            Status::Created => vec![Status::Done, Status::Error, Status::Cancelled],
+            Status::Pending => vec![Status::Created, Status::Pending, Status::Error],
+            Status::Done => vec![Status::Pending],
+            Status::Error => vec![Status::Pending],
+            Status::Cancelled => vec![Status::Created, Status::Pending, Status::Error],
+        }
+    }
+
+    pub fn can_transition_to(&self, next: Status) -> bool {
+        Status::sources(next).contains(self)
+    }
 }
 
 #[derive(
@@ -35,3 +66,42 @@ pub enum Pipeline {
     #[strum(serialize = "Amplicon Metagenome")]
     AmpliconMetgenome,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUSES: [Status; 5] = [
+        Status::Created,
+        Status::Pending,
+        Status::Done,
+        Status::Error,
+        Status::Cancelled,
+    ];
+
+    #[test]
+    fn transitions() {
+        use Status::*;
+
+        // Rows are the current status, columns the next one, in the order of STATUSES.
+        let table = [
+            (Created, [false, true, false, false, true]),
+            (Pending, [false, true, true, true, true]),
+            (Done, [true, false, false, false, false]),
+            (Error, [true, true, false, false, true]),
+            (Cancelled, [true, false, false, false, false]),
+        ];
+
+        for (current, allowed) in table {
+            for (next, allowed) in STATUSES.into_iter().zip(allowed) {
+                assert_eq!(
+                    current.can_transition_to(next),
+                    allowed,
+                    "{:?} -> {:?}",
+                    current,
+                    next
+                );
+            }
+        }
+    }
+}
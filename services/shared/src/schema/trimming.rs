@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use crate::schema::upload::MAX_MIN_PHRED;
+
+// Trimming options of a sample, applied before reads are filtered.
+// These are shared between the API, the frontend and the fastq_service.
+
+/// The upper bound for max_error_rate, beyond that anything matches.
+pub const MAX_ADAPTER_ERROR_RATE: f64 = 0.5;
+
+/// IUPAC nucleotide codes that primers may contain.
+const IUPAC_CODES: &str = "ACGTURYSWKMBDHVN";
+
+/// Built-in adapter sequences, see the fastq_service for the sequences.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum AdapterSet {
+    #[serde(rename = "none")]
+    #[default]
+    None,
+    #[serde(rename = "illumina")]
+    Illumina,
+    #[serde(rename = "nanopore")]
+    Nanopore,
+}
+
+fn default_max_error_rate() -> f64 {
+    0.1
+}
+
+fn default_min_overlap() -> usize {
+    3
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AdapterTrimConfig {
+    #[serde(default)]
+    pub adapters: AdapterSet,
+    /// Primers of amplicon runs, e.g., "GTGYCAGCMGCCGCGGTAA". Trimmed off
+    /// the start of reads, and their reverse complement off the end.
+    #[serde(default)]
+    pub primers: Vec<String>,
+    /// Mismatches allowed per matched base, e.g., 0.1 allows
+    /// 2 mismatches in a 20 bp primer.
+    #[serde(default = "default_max_error_rate")]
+    pub max_error_rate: f64,
+    /// An adapter that runs off the end of a read is only
+    /// trimmed if at least this many bases of it are in the read.
+    #[serde(default = "default_min_overlap")]
+    pub min_overlap: usize,
+}
+
+impl Default for AdapterTrimConfig {
+    fn default() -> Self {
+        Self {
+            adapters: AdapterSet::default(),
+            primers: Vec::new(),
+            max_error_rate: default_max_error_rate(),
+            min_overlap: default_min_overlap(),
+        }
+    }
+}
+
+impl AdapterTrimConfig {
+    /// Whether there is anything to trim.
+    pub fn is_enabled(&self) -> bool {
+        self.adapters != AdapterSet::None || !self.primers.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=MAX_ADAPTER_ERROR_RATE).contains(&self.max_error_rate) {
+            return Err(format!(
+                "Max adapter error rate ({}) is not between 0 and {}",
+                self.max_error_rate, MAX_ADAPTER_ERROR_RATE
+            ));
+        }
+
+        if self.min_overlap == 0 {
+            return Err("Min adapter overlap must be at least 1".into());
+        }
+
+        for primer in &self.primers {
+            if primer.is_empty() {
+                return Err("Primers can't be empty".into());
+            }
+            if let Some(base) = primer
+                .chars()
+                .find(|base| !IUPAC_CODES.contains(base.to_ascii_uppercase()))
+            {
+                return Err(format!(
+                    "Primer {} contains {}, which is not a IUPAC nucleotide code",
+                    primer, base
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Cut a read at the first window of `size` bases whose mean phred is below `min_phred`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SlidingWindow {
+    pub size: usize,
+    pub min_phred: usize,
+}
+
+/// Trimming on base quality and position, after adapters are trimmed off.
+/// Steps are applied in the order of the fields, and empty ones are skipped.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct QualityTrimConfig {
+    /// Remove this many bases from the start.
+    #[serde(default)]
+    pub head_crop: Option<usize>,
+    /// Remove bases below this phred from the start.
+    #[serde(default)]
+    pub leading: Option<usize>,
+    /// Remove bases below this phred from the end.
+    #[serde(default)]
+    pub trailing: Option<usize>,
+    #[serde(default)]
+    pub sliding_window: Option<SlidingWindow>,
+    /// Keep at most this many bases.
+    #[serde(default)]
+    pub crop: Option<usize>,
+}
+
+impl QualityTrimConfig {
+    /// Whether there is anything to trim.
+    pub fn is_enabled(&self) -> bool {
+        self.head_crop.is_some()
+            || self.leading.is_some()
+            || self.trailing.is_some()
+            || self.sliding_window.is_some()
+            || self.crop.is_some()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let phreds = [
+            ("Leading", self.leading),
+            ("Trailing", self.trailing),
+            (
+                "Sliding window",
+                self.sliding_window.as_ref().map(|window| window.min_phred),
+            ),
+        ];
+        for (name, phred) in phreds {
+            if let Some(phred) = phred
+                && phred > MAX_MIN_PHRED
+            {
+                return Err(format!(
+                    "{} min phred ({}) is larger than {}",
+                    name, phred, MAX_MIN_PHRED
+                ));
+            }
+        }
+
+        if let Some(window) = &self.sliding_window
+            && window.size == 0
+        {
+            return Err("Sliding window size must be at least 1".into());
+        }
+
+        if self.crop == Some(0) {
+            return Err("Crop must keep at least 1 base".into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use crate::schema::sample_sheet::SampleSheetError;
+use crate::schema::schema::Pipeline;
+use crate::schema::trimming::{AdapterTrimConfig, QualityTrimConfig};
+
+// Request and response bodies for the presigned upload flow.
+// These are shared between the API and the frontend.
+
+/// The upper bound for min_phred that the upload form allows.
+pub const MAX_MIN_PHRED: usize = 60;
+
+/// Thresholds and metadata from the upload form, applied to every
+/// sample of an upload. Empty fields fall back to the defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct UploadConfig {
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+    pub min_phred: Option<usize>,
+    pub identifier: Option<String>,
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub adapter_trim: Option<AdapterTrimConfig>,
+    #[serde(default)]
+    pub quality_trim: Option<QualityTrimConfig>,
+}
+
+impl UploadConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if let (Some(min_len), Some(max_len)) = (self.min_len, self.max_len)
+            && min_len > max_len
+        {
+            return Err(format!(
+                "Min read length ({}) is larger than max read length ({})",
+                min_len, max_len
+            ));
+        }
+
+        if let Some(min_phred) = self.min_phred
+            && min_phred > MAX_MIN_PHRED
+        {
+            return Err(format!(
+                "Min phred ({}) is larger than {}",
+                min_phred, MAX_MIN_PHRED
+            ));
+        }
+
+        if let Some(adapter_trim) = &self.adapter_trim {
+            adapter_trim.validate()?;
+        }
+
+        if let Some(quality_trim) = &self.quality_trim {
+            quality_trim.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sent by the client to start an upload session for a single file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadSessionRequest {
+    pub file_name: String,
+    pub size: u64,
+    pub pipeline: Pipeline,
+    /// Set when the file is part of a sample sheet batch.
+    #[serde(default)]
+    pub batch_id: Option<String>,
+    #[serde(default)]
+    pub config: UploadConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PresignedPart {
+    pub part_number: u16,
+    pub url: String,
+}
+
+/// The client should split the file into chunks of `part_size`
+/// bytes (the last one may be smaller) and PUT chunk i to parts[i].url.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadSessionResponse {
+    pub session_id: String,
+    pub part_size: usize,
+    pub parts: Vec<PresignedPart>,
+}
+
+/// The ETag header that MinIO returns for each uploaded part.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompletedPart {
+    pub part_number: u16,
+    pub etag: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompleteUploadRequest {
+    pub parts: Vec<CompletedPart>,
+}
+
+// Resumable (tus-style) uploads. The client PATCHes chunks of exactly
+// `chunk_size` bytes (the last one may be smaller) with the offset in
+// the Upload-Offset header, and can HEAD the session for the current offset.
+
+pub const UPLOAD_OFFSET_HEADER: &str = "Upload-Offset";
+pub const UPLOAD_LENGTH_HEADER: &str = "Upload-Length";
+pub const UPLOAD_CHUNK_SIZE_HEADER: &str = "Upload-Chunk-Size";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResumableUploadResponse {
+    pub session_id: String,
+    pub chunk_size: usize,
+    pub offset: u64,
+}
+
+// Sample sheet batches. The client creates a batch from the sheet, then
+// uploads every file in it through an upload session with the batch id.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateBatchRequest {
+    pub name: String,
+    /// Raw CSV/TSV sample sheet.
+    pub sample_sheet: String,
+    /// Default for rows without a pipeline.
+    pub pipeline: Pipeline,
+    /// Names of the files that will be uploaded, which must match the sheet.
+    pub file_names: Vec<String>,
+    /// Default for rows without thresholds.
+    #[serde(default)]
+    pub config: UploadConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateBatchResponse {
+    pub batch_id: String,
+    pub num_samples: usize,
+}
+
+/// Returned with a 422 if the sheet is invalid.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SampleSheetErrors {
+    pub errors: Vec<SampleSheetError>,
+}
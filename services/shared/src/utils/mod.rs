@@ -1,4 +1,5 @@
 pub mod file;
 pub mod macros;
+pub mod pairing;
 pub mod time;
 pub mod url;
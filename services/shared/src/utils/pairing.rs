@@ -0,0 +1,245 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+static MATE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?<sample>.+?)[_.]R(?<mate>[12])(?<suffix>(_\d+)?\.(fastq|fq)(\.gz)?)$")
+        .expect("Invalid mate file name regex")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mate {
+    R1,
+    R2,
+}
+
+/// A sample's read files. `r2` is only set for paired-end samples.
+#[derive(Debug, PartialEq)]
+pub struct ReadFiles<T> {
+    pub name: String,
+    pub r1: T,
+    pub r2: Option<T>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MateName {
+    /// Sample name, i.e., everything before the mate marker.
+    pub sample: String,
+    /// File name without the mate marker, which is the same for both mates.
+    pub pair_key: String,
+    pub mate: Mate,
+}
+
+/// Parse Illumina style mate file names, e.g., "sample_S1_L001_R1_001.fastq.gz"
+/// or "sample_R2.fq".
+pub fn parse_mate(file_name: &str) -> Option<MateName> {
+    let captures = MATE_RE.captures(file_name)?;
+    let sample = captures.name("sample")?.as_str();
+    let suffix = captures.name("suffix")?.as_str();
+
+    Some(MateName {
+        sample: sample.to_string(),
+        pair_key: format!("{}{}", sample, suffix),
+        mate: match captures.name("mate")?.as_str() {
+            "1" => Mate::R1,
+            _ => Mate::R2,
+        },
+    })
+}
+
+/// Group files into samples. `explicit` pairs of (R1, R2) file names are
+/// used as-is, while the rest are paired up by their _R1/_R2 file names.
+/// Files without a mate become single-end samples.
+pub fn pair_files<T>(
+    files: Vec<(String, T)>,
+    explicit: &[(String, String)],
+) -> Result<Vec<ReadFiles<T>>, String> {
+    let mut files: Vec<(String, Option<T>)> = files
+        .into_iter()
+        .map(|(name, file)| (name, Some(file)))
+        .collect();
+
+    let mut take = |name: &str| -> Result<T, String> {
+        files
+            .iter_mut()
+            .find(|(file_name, file)| file_name == name && file.is_some())
+            .and_then(|(_, file)| file.take())
+            .ok_or(format!("Missing file {} for pair", name))
+    };
+
+    let mut samples: Vec<ReadFiles<T>> = Vec::new();
+    for (r1, r2) in explicit {
+        samples.push(ReadFiles {
+            name: parse_mate(r1)
+                .map(|mate_name| mate_name.sample)
+                .unwrap_or(r1.clone()),
+            r1: take(r1)?,
+            r2: Some(take(r2)?),
+        });
+    }
+
+    // Index of each R2, so that we can find the mate of an R1.
+    let mut r2_index: HashMap<String, usize> = HashMap::new();
+    for (i, (name, file)) in files.iter().enumerate() {
+        if let (Some(mate_name), Some(_)) = (parse_mate(name), file)
+            && mate_name.mate == Mate::R2
+        {
+            r2_index.entry(mate_name.pair_key).or_insert(i);
+        }
+    }
+
+    // First, R1s with a matching R2...
+    for i in 0..files.len() {
+        let Some(mate_name) = parse_mate(&files[i].0) else {
+            continue;
+        };
+        if mate_name.mate != Mate::R1 || files[i].1.is_none() {
+            continue;
+        }
+        let Some(j) = r2_index.remove(&mate_name.pair_key) else {
+            continue;
+        };
+
+        samples.push(ReadFiles {
+            name: mate_name.sample,
+            r1: files[i].1.take().unwrap(),
+            r2: files[j].1.take(),
+        });
+    }
+
+    // ...then whatever is left is single-end.
+    for (name, file) in files {
+        if let Some(file) = file {
+            samples.push(ReadFiles {
+                name,
+                r1: file,
+                r2: None,
+            });
+        }
+    }
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<(String, String)> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), name.to_string()))
+            .collect()
+    }
+
+    fn pair(names: &[&str], explicit: &[(&str, &str)]) -> Result<Vec<ReadFiles<String>>, String> {
+        let explicit: Vec<(String, String)> = explicit
+            .iter()
+            .map(|(r1, r2)| (r1.to_string(), r2.to_string()))
+            .collect();
+        pair_files(files(names), &explicit)
+    }
+
+    fn sample(name: &str, r1: &str, r2: Option<&str>) -> ReadFiles<String> {
+        ReadFiles {
+            name: name.to_string(),
+            r1: r1.to_string(),
+            r2: r2.map(String::from),
+        }
+    }
+
+    #[test]
+    fn parse_mate_names() {
+        let mate_name = parse_mate("sample_S1_L001_R2_001.fastq.gz").unwrap();
+        assert_eq!(mate_name.sample, "sample_S1_L001");
+        assert_eq!(mate_name.pair_key, "sample_S1_L001_001.fastq.gz");
+        assert_eq!(mate_name.mate, Mate::R2);
+
+        let mate_name = parse_mate("sample.R1.fq").unwrap();
+        assert_eq!(mate_name.sample, "sample");
+        assert_eq!(mate_name.mate, Mate::R1);
+
+        assert_eq!(parse_mate("sample.fastq.gz"), None);
+        assert_eq!(parse_mate("sample_R3.fastq"), None);
+        assert_eq!(parse_mate("sample_R1.bam"), None);
+        assert_eq!(parse_mate("_R1.fastq"), None);
+    }
+
+    #[test]
+    fn pair_by_name() {
+        let samples = pair(
+            &[
+                "b_R2.fq.gz",
+                "single.fastq",
+                "a_R1.fq.gz",
+                "b_R1.fq.gz",
+                "a_R2.fq.gz",
+            ],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            samples,
+            vec![
+                sample("a", "a_R1.fq.gz", Some("a_R2.fq.gz")),
+                sample("b", "b_R1.fq.gz", Some("b_R2.fq.gz")),
+                sample("single.fastq", "single.fastq", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn unmatched_mates_are_single_end() {
+        // Different suffixes, so these are not mates.
+        let samples = pair(&["a_R1.fq.gz", "a_R2.fq", "b_R2.fq"], &[]).unwrap();
+
+        assert_eq!(
+            samples,
+            vec![
+                sample("a_R1.fq.gz", "a_R1.fq.gz", None),
+                sample("a_R2.fq", "a_R2.fq", None),
+                sample("b_R2.fq", "b_R2.fq", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_mates_pair_once() {
+        let samples = pair(&["a_R1.fq", "a_R1.fq", "a_R2.fq"], &[]).unwrap();
+
+        assert_eq!(
+            samples,
+            vec![
+                sample("a", "a_R1.fq", Some("a_R2.fq")),
+                sample("a_R1.fq", "a_R1.fq", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn explicit_pairs() {
+        let samples = pair(
+            &["forward.fq", "reverse.fq", "x_R1.fq", "x_R2.fq"],
+            &[("forward.fq", "reverse.fq"), ("x_R2.fq", "x_R1.fq")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            samples,
+            vec![
+                sample("forward.fq", "forward.fq", Some("reverse.fq")),
+                sample("x", "x_R2.fq", Some("x_R1.fq")),
+            ]
+        );
+
+        assert_eq!(
+            pair(&["a_R1.fq"], &[("a_R1.fq", "a_R2.fq")]),
+            Err("Missing file a_R2.fq for pair".to_string())
+        );
+        assert_eq!(
+            pair(&["a.fq", "b.fq"], &[("a.fq", "b.fq"), ("a.fq", "b.fq")]),
+            Err("Missing file a.fq for pair".to_string())
+        );
+    }
+}
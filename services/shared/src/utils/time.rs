@@ -4,3 +4,22 @@ pub fn time_now() -> String {
         .format("%Y-%m-%d %H:%M:%S")
         .to_string()
 }
+
+/// Parse a date ("2025-01-31") or timestamp ("2025-01-31 12:00:00") into the
+/// format of time_now(), so that it can be compared with stored timestamps.
+/// A date is the start of that day, or the end of it if `end_of_day` is set.
+pub fn parse_time_bound(value: &str, end_of_day: bool) -> Option<String> {
+    let value = value.trim();
+
+    if let Ok(time) = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Some(time.format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let time = match end_of_day {
+        true => date.and_hms_opt(23, 59, 59)?,
+        false => date.and_hms_opt(0, 0, 0)?,
+    };
+
+    Some(time.format("%Y-%m-%d %H:%M:%S").to_string())
+}